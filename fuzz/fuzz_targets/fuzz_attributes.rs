@@ -0,0 +1,46 @@
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+use mft::attribute::data_run::decode_data_runs;
+use mft::attribute::header::MftAttributeHeader;
+use mft::attribute::raw::RawAttribute;
+use mft::attribute::x10::StandardInfoAttr;
+use mft::attribute::x20::{AttributeListAttr, AttributeListEntry};
+use mft::attribute::x30::FileNameAttr;
+use mft::attribute::x40::ObjectIdAttr;
+use mft::attribute::x80::DataAttr;
+use mft::attribute::x90::{IndexEntryHeader, IndexRootAttr};
+use mft::attribute::MftAttributeType;
+
+use std::io::Cursor;
+
+// The first byte selects the parser, and the next two the size passed to size-aware parsers.
+fuzz_target!(|data: &[u8]| {
+    let (selector, size, data) = match data {
+        [selector, size_low, size_high, rest @ ..] => (
+            *selector,
+            usize::from(u16::from_le_bytes([*size_low, *size_high])),
+            rest,
+        ),
+        _ => return,
+    };
+    let mut stream = Cursor::new(data);
+
+    match selector % 11 {
+        0 => drop(MftAttributeHeader::from_stream(&mut stream)),
+        1 => drop(StandardInfoAttr::from_reader(&mut stream)),
+        2 => drop(AttributeListAttr::from_stream(&mut stream, None)),
+        3 => drop(AttributeListEntry::from_stream(&mut stream)),
+        4 => drop(FileNameAttr::from_stream(&mut stream)),
+        5 => drop(ObjectIdAttr::from_stream(&mut stream, size)),
+        6 => drop(DataAttr::from_stream(&mut stream, size)),
+        7 => drop(IndexRootAttr::from_stream(&mut stream)),
+        8 => drop(IndexEntryHeader::from_stream(&mut stream)),
+        9 => drop(RawAttribute::from_stream(
+            &mut stream,
+            MftAttributeType::EA,
+            size,
+        )),
+        _ => drop(decode_data_runs(data)),
+    }
+});
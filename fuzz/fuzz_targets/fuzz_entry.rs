@@ -0,0 +1,18 @@
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+use mft::MftEntry;
+
+fuzz_target!(|data: &[u8]| {
+    if let Ok(entry) = MftEntry::from_buffer(data.to_vec(), 0) {
+        // Serializing walks (and parses) every attribute of the entry.
+        let _ = serde_json::to_vec(&entry);
+        let _ = entry.find_best_name_attribute();
+        entry.iter_attributes().for_each(drop);
+    }
+
+    let mut scratch = Vec::new();
+    if let Ok(entry) = mft::MftEntryRef::from_slice(data, &mut scratch, 0) {
+        let _ = entry.attribute_records();
+    }
+});
@@ -0,0 +1,182 @@
+//! Heuristics for timestamp manipulation ("timestomping").
+//!
+//! Tools which set timestamps usually only set the ones of `$STANDARD_INFORMATION`, and often with a
+//! precision of a second, so inconsistencies between it and the `$FILE_NAME` attributes are telling.
+//! None of these rules is proof on its own (copying files, archives and installers trip some of them),
+//! so they are reported as findings for an analyst to triage.
+use crate::timestamp::FileTimeValue;
+use crate::{FileNameAttr, FileTime, MftEntry, StandardInfoAttr};
+
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum TimestompRule {
+    /// `$STANDARD_INFORMATION` was created before one of the `$FILE_NAME` attributes, which are set when the file is created.
+    SiCreatedBeforeFnCreated,
+    /// A `$STANDARD_INFORMATION` time has no 100ns fraction, as if it was set with a precision of a second.
+    SiZeroFraction,
+    /// `$STANDARD_INFORMATION` was modified before it was created.
+    SiModifiedBeforeCreated,
+    /// A time is before the volume was created, after the end of its lifetime, or out of range.
+    OutsideVolumeLifetime,
+}
+
+/// An entry which broke a `TimestompRule`.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct TimestompFinding {
+    pub rule: TimestompRule,
+    pub message: String,
+}
+
+impl TimestompFinding {
+    fn new(rule: TimestompRule, message: String) -> Self {
+        TimestompFinding { rule, message }
+    }
+}
+
+/// Applies every `TimestompRule` to entries.
+///
+/// `OutsideVolumeLifetime` is only checked once the lifetime is set, see `with_volume_lifetime` and `for_volume`.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct TimestompAnalyzer {
+    volume_lifetime: Option<(DateTime<Utc>, DateTime<Utc>)>,
+}
+
+impl TimestompAnalyzer {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Times before `created` or after `until` are flagged as `OutsideVolumeLifetime`.
+    pub fn with_volume_lifetime(mut self, created: DateTime<Utc>, until: DateTime<Utc>) -> Self {
+        self.volume_lifetime = Some((created, until));
+        self
+    }
+
+    /// An analyzer for the volume of the given `$MFT` entry (entry 0), which was created with the volume.
+    /// The lifetime of the volume ends now. Any other entry gives an analyzer without a lifetime.
+    pub fn for_volume(mft_entry: &MftEntry) -> Self {
+        let is_mft = mft_entry
+            .find_best_name_attribute()
+            .is_some_and(|file_name| file_name.name == "$MFT");
+
+        match standard_info(mft_entry).and_then(|info| info.created.to_datetime()) {
+            Some(created) if is_mft => Self::new().with_volume_lifetime(created, Utc::now()),
+            _ => Self::new(),
+        }
+    }
+
+    pub fn analyze(&self, entry: &MftEntry) -> Vec<TimestompFinding> {
+        let mut findings = vec![];
+
+        let info = standard_info(entry);
+        let file_names: Vec<FileNameAttr> = entry
+            .iter_attributes()
+            .filter_map(Result::ok)
+            .filter_map(|a| a.data.into_file_name())
+            .collect();
+
+        if let Some(info) = &info {
+            if let Some(file_name) = file_names
+                .iter()
+                .find(|file_name| is_before(info.created, file_name.created))
+            {
+                findings.push(TimestompFinding::new(
+                    TimestompRule::SiCreatedBeforeFnCreated,
+                    format!(
+                        "$STANDARD_INFORMATION was created at {}, before $FILE_NAME `{}` at {}",
+                        info.created, file_name.name, file_name.created
+                    ),
+                ));
+            }
+
+            let zero_fractions: Vec<&str> = si_times(info)
+                .iter()
+                .filter(|(_, time)| time.is_set() && time.subsecond_intervals() == 0)
+                .map(|(name, _)| *name)
+                .collect();
+            if !zero_fractions.is_empty() {
+                findings.push(TimestompFinding::new(
+                    TimestompRule::SiZeroFraction,
+                    format!(
+                        "$STANDARD_INFORMATION times without a 100ns fraction: {}",
+                        zero_fractions.join(", ")
+                    ),
+                ));
+            }
+
+            if is_before(info.modified, info.created) {
+                findings.push(TimestompFinding::new(
+                    TimestompRule::SiModifiedBeforeCreated,
+                    format!(
+                        "$STANDARD_INFORMATION was modified at {}, before it was created at {}",
+                        info.modified, info.created
+                    ),
+                ));
+            }
+        }
+
+        if let Some((created, until)) = self.volume_lifetime {
+            let outside = info
+                .iter()
+                .flat_map(|info| {
+                    si_times(info).map(|(name, time)| ("$STANDARD_INFORMATION", name, time))
+                })
+                .chain(file_names.iter().flat_map(|file_name| {
+                    fn_times(file_name).map(|(name, time)| ("$FILE_NAME", name, time))
+                }))
+                .find(|(_, _, time)| match time.value() {
+                    FileTimeValue::NotSet => false,
+                    FileTimeValue::OutOfRange => true,
+                    FileTimeValue::Time(time) => time < created || time > until,
+                });
+
+            if let Some((attribute, name, time)) = outside {
+                findings.push(TimestompFinding::new(
+                    TimestompRule::OutsideVolumeLifetime,
+                    format!(
+                        "{} {} time {} is outside of the lifetime of the volume ({} to {})",
+                        attribute, name, time, created, until
+                    ),
+                ));
+            }
+        }
+
+        findings
+    }
+}
+
+/// Whether both times are in range, and the first is before the second.
+/// Unset and out of range times are left to `TimestompRule::OutsideVolumeLifetime`.
+fn is_before(time: FileTime, other: FileTime) -> bool {
+    match (time.value(), other.value()) {
+        (FileTimeValue::Time(time), FileTimeValue::Time(other)) => time < other,
+        _ => false,
+    }
+}
+
+fn standard_info(entry: &MftEntry) -> Option<StandardInfoAttr> {
+    entry
+        .iter_attributes()
+        .filter_map(Result::ok)
+        .find_map(|a| a.data.into_standard_info())
+}
+
+fn si_times(info: &StandardInfoAttr) -> [(&'static str, FileTime); 4] {
+    [
+        ("modified", info.modified),
+        ("accessed", info.accessed),
+        ("MFT modified", info.mft_modified),
+        ("created", info.created),
+    ]
+}
+
+fn fn_times(file_name: &FileNameAttr) -> [(&'static str, FileTime); 4] {
+    [
+        ("modified", file_name.modified),
+        ("accessed", file_name.accessed),
+        ("MFT modified", file_name.mft_modified),
+        ("created", file_name.created),
+    ]
+}
@@ -0,0 +1,64 @@
+//! Conditions found while parsing an entry, which mean parts of the record cannot be trusted.
+use crate::attribute::error::AttributeError;
+
+use serde::{Deserialize, Serialize};
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AnomalyKind {
+    /// The last two bytes of a 512-byte stride did not match the update sequence number,
+    /// usually because of a torn write.
+    FixupMismatch,
+    /// An attribute could not be parsed, and is missing from the entry's attributes.
+    AttributeError,
+    /// Part of a damaged record was recovered on a best-effort basis, see `MftEntry::recover`.
+    Recovered,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct Anomaly {
+    pub kind: AnomalyKind,
+    /// Offset of the affected bytes, from the start of the record.
+    pub offset: u64,
+    /// The instance of the affected attribute, if it is known.
+    pub attribute_instance: Option<u16>,
+    pub message: String,
+}
+
+impl Anomaly {
+    pub fn fixup_mismatch(offset: u64, message: String) -> Anomaly {
+        Anomaly {
+            kind: AnomalyKind::FixupMismatch,
+            offset,
+            attribute_instance: None,
+            message,
+        }
+    }
+
+    pub fn recovered(offset: u64, message: String) -> Anomaly {
+        Anomaly {
+            kind: AnomalyKind::Recovered,
+            offset,
+            attribute_instance: None,
+            message,
+        }
+    }
+
+    pub fn attribute_error(
+        offset: u64,
+        attribute_instance: Option<u16>,
+        message: String,
+    ) -> Anomaly {
+        Anomaly {
+            kind: AnomalyKind::AttributeError,
+            offset,
+            attribute_instance,
+            message,
+        }
+    }
+}
+
+impl From<&AttributeError> for Anomaly {
+    fn from(error: &AttributeError) -> Anomaly {
+        Anomaly::attribute_error(error.offset, error.instance, error.error.clone())
+    }
+}
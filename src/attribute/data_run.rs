@@ -1,15 +1,15 @@
-use serde::Serialize;
+use serde::{Deserialize, Serialize};
 
 // adapted from https://github.com/rkapl/ntfs-reclaim/blob/a68e87b21c12631311fc3f279f5b03bd8f23d57b/src/data_runs.rs
 // original didn't support sparse clusters
 
-#[derive(Serialize, Debug, Copy, Clone, Eq, PartialEq)]
+#[derive(Serialize, Deserialize, Debug, Copy, Clone, Eq, PartialEq)]
 pub enum RunType {
     Standard,
     Sparse,
 }
 
-#[derive(Serialize, Debug, Copy, Clone, Eq, PartialEq)]
+#[derive(Serialize, Deserialize, Debug, Copy, Clone, Eq, PartialEq)]
 pub struct DataRun {
     pub lcn_offset: u64,
     pub lcn_length: u64,
@@ -17,6 +17,9 @@ pub struct DataRun {
 }
 
 fn decode_run_value<T: Iterator<Item = u8>>(it: &mut T, bytes: u8) -> Option<u64> {
+    if bytes == 0 {
+        return Some(0);
+    }
     let mut acc = 0u64;
     for _ in 0..bytes {
         let v = it.next()?;
@@ -27,6 +30,9 @@ fn decode_run_value<T: Iterator<Item = u8>>(it: &mut T, bytes: u8) -> Option<u64
 }
 
 fn decode_run_svalue<T: Iterator<Item = u8>>(it: &mut T, bytes: u8) -> Option<i64> {
+    if bytes == 0 {
+        return Some(0);
+    }
     let mut acc = decode_run_value(it, bytes)? as i64;
     // sign extend
     acc <<= (8 - bytes) * 8;
@@ -54,7 +60,7 @@ pub fn decode_data_runs(runs: &[u8]) -> Option<Vec<DataRun>> {
         if offset_size != 0 { // offset_size of 0 == sparse cluster
             if let Some(last) = out.last() {
                 let rel_offset  = decode_run_svalue(&mut it, offset_size)?;
-                abs_offset = (last.lcn_offset as i64 + rel_offset) as u64;
+                abs_offset = (last.lcn_offset as i64).checked_add(rel_offset)? as u64;
             } else {
                 abs_offset = decode_run_value(&mut it, offset_size)?;
             }
@@ -82,5 +88,7 @@ mod tests {
         assert_eq!(decode_run_value(&mut vec![0x34, 0x56].into_iter(), 2), Some(0x5634));
         assert_eq!(decode_run_svalue(&mut vec![0xE0].into_iter(), 1), Some(-0x20));
         assert_eq!(decode_run_svalue(&mut vec![0xE0].into_iter(), 2), None);
+        assert_eq!(decode_run_value(&mut vec![].into_iter(), 0), Some(0));
+        assert_eq!(decode_run_svalue(&mut vec![].into_iter(), 0), Some(0));
     }
 }
@@ -0,0 +1,75 @@
+use crate::attribute::raw::data_as_hex;
+use crate::attribute::MftAttributeType;
+use crate::err::{Error, ErrorReport};
+
+use num_traits::FromPrimitive;
+use serde::{Deserialize, Serialize};
+
+/// An attribute which could not be parsed.
+/// Whatever could be read of the attribute record is kept, so it can be inspected manually.
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct AttributeError {
+    /// The type code of the attribute, if it could be read.
+    pub type_code: Option<u32>,
+    /// The attribute type, if the type code is a known one.
+    pub attribute_type: Option<MftAttributeType>,
+    /// Offset of the attribute record, from the start of the entry.
+    pub offset: u64,
+    /// The unique instance of the attribute, if its header could be parsed.
+    pub instance: Option<u16>,
+    /// The message of the `Error` which stopped the attribute from being parsed.
+    pub error: String,
+    /// The bytes of the attribute record, as far as they could be salvaged.
+    #[serde(
+        serialize_with = "data_as_hex",
+        deserialize_with = "crate::utils::data_from_hex"
+    )]
+    pub raw: Vec<u8>,
+}
+
+impl AttributeError {
+    /// Salvages what can be read of the attribute record found at `offset` of the entry's `data`.
+    /// The raw bytes are bounded by the record length when it is plausible, otherwise by the end of the entry.
+    pub fn salvage(
+        data: &[u8],
+        offset: u64,
+        instance: Option<u16>,
+        error: &Error,
+    ) -> AttributeError {
+        let start = usize::try_from(offset).map_or(data.len(), |offset| offset.min(data.len()));
+        let record = &data[start..];
+
+        let read_u32 = |at: usize| {
+            record
+                .get(at..at + 4)
+                .map(|bytes| u32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]))
+        };
+
+        let type_code = read_u32(0);
+        let raw = match read_u32(4) {
+            Some(length) if length >= 8 && length as usize <= record.len() => {
+                &record[..length as usize]
+            }
+            _ => record,
+        };
+
+        AttributeError {
+            type_code,
+            attribute_type: type_code.and_then(MftAttributeType::from_u32),
+            offset,
+            instance,
+            error: error.to_string(),
+            raw: raw.to_vec(),
+        }
+    }
+
+    /// Describes the error for an error report, given the number of the entry it was found in.
+    pub fn report(&self, record_number: u64) -> ErrorReport {
+        ErrorReport {
+            record_number: Some(record_number),
+            attribute_offset: Some(self.offset),
+            attribute_type: self.attribute_type.clone(),
+            message: self.error.clone(),
+        }
+    }
+}
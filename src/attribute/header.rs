@@ -4,12 +4,12 @@ use crate::utils::read_utf16_string;
 
 use byteorder::{LittleEndian, ReadBytesExt};
 use num_traits::FromPrimitive;
-use serde::Serialize;
+use serde::{Deserialize, Serialize};
 use std::io::{Read, Seek, SeekFrom};
 
 /// Represents the union defined in
 /// <https://docs.microsoft.com/en-us/windows/desktop/devnotes/attribute-record-header>
-#[derive(Serialize, Clone, Debug)]
+#[derive(Serialize, Deserialize, Clone, Debug)]
 pub struct MftAttributeHeader {
     pub type_code: MftAttributeType,
     /// The size of the attribute record, in bytes.
@@ -33,7 +33,7 @@ pub struct MftAttributeHeader {
     pub start_offset: u64
 }
 
-#[derive(Serialize, Clone, Debug)]
+#[derive(Serialize, Deserialize, Clone, Debug)]
 #[serde(untagged)]
 pub enum ResidentialHeader {
     Resident(ResidentHeader),
@@ -112,14 +112,20 @@ impl MftAttributeHeader {
             start_offset: attribute_header_start_offset
         }))
     }
+
+    /// The size of the value of the attribute (for $DATA, the size of the stream), in bytes.
+    pub fn data_size(&self) -> u64 {
+        match &self.residential_header {
+            ResidentialHeader::Resident(r) => u64::from(r.data_size),
+            ResidentialHeader::NonResident(nr) => nr.file_size,
+        }
+    }
 }
 
-#[derive(Serialize, Clone, Debug)]
+#[derive(Serialize, Deserialize, Clone, Debug)]
 pub struct ResidentHeader {
-    #[serde(skip_serializing)]
     /// The size of the attribute value, in bytes.
     pub data_size: u32,
-    #[serde(skip_serializing)]
     /// The offset to the value from the start of the attribute record, in bytes.
     pub data_offset: u16,
     pub index_flag: u8,
@@ -137,18 +143,16 @@ impl ResidentHeader {
     }
 }
 
-#[derive(Serialize, Clone, Debug)]
+#[derive(Serialize, Deserialize, Clone, Debug)]
 pub struct NonResidentHeader {
     /// The lowest virtual cluster number (VCN) covered by this attribute record.
     pub vnc_first: u64,
     /// The highest VCN covered by this attribute record.
     pub vnc_last: u64,
-    #[serde(skip_serializing)]
     /// The offset to the mapping pairs array from the start of the attribute record, in bytes. For more information, see Remarks.
     pub datarun_offset: u16,
     /// Reserved UCHAR\[6]
     pub unit_compression_size: u16,
-    #[serde(skip_serializing)]
     pub padding: u32,
 
     /// The allocated size of the file, in bytes.
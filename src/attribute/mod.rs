@@ -1,3 +1,4 @@
+pub mod error;
 pub mod header;
 pub mod raw;
 pub mod x10;
@@ -11,11 +12,15 @@ pub mod data_run;
 
 use crate::err::Result;
 use crate::impl_serialize_for_bitflags;
+use crate::impl_deserialize_for_bitflags;
+use crate::limits::ParseLimits;
+use crate::utils;
 
 use std::io::{Cursor, Read, Seek};
 
 use bitflags::bitflags;
 
+use crate::attribute::error::AttributeError;
 use crate::attribute::raw::RawAttribute;
 use crate::attribute::x10::StandardInfoAttr;
 use crate::attribute::x20::AttributeListAttr;
@@ -26,20 +31,62 @@ use crate::attribute::x40::ObjectIdAttr;
 use crate::attribute::x80::DataAttr;
 use crate::attribute::x90::IndexRootAttr;
 use crate::attribute::non_resident_attr::NonResidentAttr;
-use serde::Serialize;
+use serde::{Deserialize, Serialize};
 
-#[derive(Serialize, Clone, Debug)]
+#[derive(Serialize, Deserialize, Clone, Debug)]
 pub struct MftAttribute {
     pub header: MftAttributeHeader,
     pub data: MftAttributeContent,
 }
 
+/// An attribute carved from the slack of an entry, left over from an earlier use of the record.
+/// See `MftEntry::iter_residual_attributes`.
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct ResidualAttribute {
+    /// How far past the used size of the entry the attribute was found,
+    /// which labels it as residual when serialized.
+    pub slack_offset: u64,
+    #[serde(flatten)]
+    pub attribute: MftAttribute,
+}
+
+/// An attribute record of an entry, which is either parsed, or reported as an error.
+#[derive(Serialize, Deserialize, Clone, Debug)]
+#[serde(untagged)]
+pub enum AttributeRecord {
+    Attribute(MftAttribute),
+    Error(AttributeError),
+}
+
+impl AttributeRecord {
+    pub fn attribute(&self) -> Option<&MftAttribute> {
+        match self {
+            AttributeRecord::Attribute(attribute) => Some(attribute),
+            AttributeRecord::Error(_) => None,
+        }
+    }
+
+    pub fn error(&self) -> Option<&AttributeError> {
+        match self {
+            AttributeRecord::Attribute(_) => None,
+            AttributeRecord::Error(error) => Some(error),
+        }
+    }
+}
+
 impl MftAttributeContent {    
     pub fn from_stream_non_resident<S: Read + Seek>(
         stream: &mut S,
         header: &MftAttributeHeader,
         resident: &NonResidentHeader,
+        limits: &ParseLimits,
     ) -> Result<Self> { 
+        limits.check_attribute_size(
+            header
+                .record_length
+                .saturating_sub(u32::from(resident.datarun_offset)),
+        )?;
+
         Ok(MftAttributeContent::DataRun(
             NonResidentAttr::from_stream(stream, header, resident)?,
         ))
@@ -49,7 +96,11 @@ impl MftAttributeContent {
         stream: &mut S,
         header: &MftAttributeHeader,
         resident: &ResidentHeader,
+        limits: &ParseLimits,
     ) -> Result<Self> {
+        // Checked before anything is allocated, the content is bounded by the record anyway.
+        limits.check_attribute_size(resident.data_size)?;
+
         match header.type_code {
             MftAttributeType::StandardInformation => Ok(MftAttributeContent::AttrX10(
                 StandardInfoAttr::from_reader(stream)?,
@@ -61,14 +112,16 @@ impl MftAttributeContent {
                 // entries when we reach the end of the buffer.
                 let content_size = resident.data_size;
 
-                let mut attribute_buffer = vec![0; content_size as usize];
-                stream.read_exact(&mut attribute_buffer)?;
+                let attribute_buffer = utils::read_bytes(stream, u64::from(content_size))?;
 
                 // Create a new stream that the attribute will read from.
                 let mut new_stream = Cursor::new(attribute_buffer);
 
-                let attr_list =
-                    AttributeListAttr::from_stream(&mut new_stream, Some(content_size as u64))?;
+                let attr_list = AttributeListAttr::from_stream_with_limits(
+                    &mut new_stream,
+                    Some(content_size as u64),
+                    limits,
+                )?;
 
                 Ok(MftAttributeContent::AttrX20(attr_list))
             }
@@ -86,7 +139,7 @@ impl MftAttributeContent {
             )),
             // Always Resident
             MftAttributeType::IndexRoot => Ok(MftAttributeContent::AttrX90(
-                IndexRootAttr::from_stream(stream)?,
+                IndexRootAttr::from_stream_with_limits(stream, limits)?,
             )),
             // An unparsed resident attribute
             _ => Ok(MftAttributeContent::Raw(RawAttribute::from_stream(
@@ -153,23 +206,33 @@ impl MftAttributeContent {
     }
 }
 
-#[derive(Serialize, Clone, Debug)]
-#[serde(untagged)]
+/// Serialized with the name of the variant as `type`, and its value as `content`
+/// (see the JSON Schema in `mft::schema`).
+#[derive(Serialize, Deserialize, Clone, Debug)]
+#[serde(tag = "type", content = "content")]
 pub enum MftAttributeContent {
     Raw(RawAttribute),
+    #[serde(rename = "StandardInformation")]
     AttrX10(StandardInfoAttr),
+    #[serde(rename = "AttributeList")]
     AttrX20(AttributeListAttr),
+    #[serde(rename = "FileName")]
     AttrX30(FileNameAttr),
+    #[serde(rename = "ObjectId")]
     AttrX40(ObjectIdAttr),
+    #[serde(rename = "Data")]
     AttrX80(DataAttr),
+    #[serde(rename = "IndexRoot")]
     AttrX90(IndexRootAttr),
+    #[serde(rename = "NonResident")]
     DataRun(NonResidentAttr),
     /// Empty - used when data is non resident.
+    #[serde(rename = "Empty")]
     None,
 }
 
 /// MFT Possible attribute types, from <https://docs.microsoft.com/en-us/windows/desktop/devnotes/attribute-list-entry>
-#[derive(Serialize, Debug, Clone, FromPrimitive, ToPrimitive, PartialOrd, PartialEq)]
+#[derive(Serialize, Deserialize, Debug, Clone, FromPrimitive, ToPrimitive, PartialOrd, PartialEq)]
 #[repr(u32)]
 pub enum MftAttributeType {
     /// File attributes (such as read-only and archive), time stamps (such as file creation and last modified), and the hard link count.
@@ -235,6 +298,7 @@ bitflags! {
 }
 
 impl_serialize_for_bitflags! {FileAttributeFlags}
+impl_deserialize_for_bitflags! {FileAttributeFlags}
 
 bitflags! {
     #[derive(Default)]
@@ -247,3 +311,4 @@ bitflags! {
 }
 
 impl_serialize_for_bitflags! {AttributeDataFlags}
+impl_deserialize_for_bitflags! {AttributeDataFlags}
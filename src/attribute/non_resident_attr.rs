@@ -1,11 +1,12 @@
 use crate::err::{Error, Result};
+use crate::utils;
 use crate::attribute::header::{MftAttributeHeader, NonResidentHeader};
 use crate::attribute::data_run::{DataRun, decode_data_runs};
 
 use std::io::{Read, Seek, SeekFrom};
-use serde::Serialize;
+use serde::{Deserialize, Serialize};
 
-#[derive(Serialize, Clone, Debug)]
+#[derive(Serialize, Deserialize, Clone, Debug)]
 pub struct NonResidentAttr {
     pub data_runs: Vec<DataRun>
 }
@@ -16,11 +17,15 @@ impl NonResidentAttr {
         header: &MftAttributeHeader,
         resident: &NonResidentHeader,
     ) -> Result<Self> {
-        let data_run_bytes_count = (header.record_length - u32::from(resident.datarun_offset)) as usize;
-        let mut data_run_bytes = vec![0_u8; data_run_bytes_count];
         if resident.valid_data_length != 0 {            
+            let data_run_bytes_count = header
+                .record_length
+                .checked_sub(u32::from(resident.datarun_offset))
+                .ok_or(Error::InvalidRecordLength {
+                    record_length: header.record_length,
+                })?;
             stream.seek(SeekFrom::Start(header.start_offset + u64::from(resident.datarun_offset)))?;
-            stream.read_exact(&mut data_run_bytes)?;
+            let data_run_bytes = utils::read_bytes(stream, u64::from(data_run_bytes_count))?;
             if let Some(data_runs) = decode_data_runs(&data_run_bytes) {
                 Ok(Self {
                     data_runs
@@ -3,13 +3,16 @@ use std::io::{Read, Seek};
 use crate::attribute::MftAttributeType;
 use crate::err::Result;
 use crate::utils;
-use serde::{ser, Serialize};
+use serde::{ser, Deserialize, Serialize};
 
 /// Placeholder attribute for currently unparsed attributes.
-#[derive(Serialize, Clone, Debug)]
+#[derive(Serialize, Deserialize, Clone, Debug)]
 pub struct RawAttribute {
     pub attribute_type: MftAttributeType,
-    #[serde(serialize_with = "data_as_hex")]
+    #[serde(
+        serialize_with = "data_as_hex",
+        deserialize_with = "crate::utils::data_from_hex"
+    )]
     pub data: Vec<u8>,
 }
 
@@ -19,9 +22,7 @@ impl RawAttribute {
         attribute_type: MftAttributeType,
         data_size: usize,
     ) -> Result<Self> {
-        let mut data = vec![0_u8; data_size];
-
-        stream.read_exact(&mut data)?;
+        let data = utils::read_bytes(stream, data_size as u64)?;
 
         Ok(RawAttribute {
             attribute_type,
@@ -30,7 +31,7 @@ impl RawAttribute {
     }
 }
 
-fn data_as_hex<S>(x: &[u8], s: S) -> std::result::Result<S::Ok, S::Error>
+pub(crate) fn data_as_hex<S>(x: &[u8], s: S) -> std::result::Result<S::Ok, S::Error>
 where
     S: ser::Serializer,
 {
@@ -1,20 +1,19 @@
 use std::io::{Read, Seek};
 
 use crate::attribute::FileAttributeFlags;
-use crate::err::{Error, Result};
+use crate::err::Result;
+use crate::timestamp::FileTime;
 
 use byteorder::{LittleEndian, ReadBytesExt};
-use chrono::{DateTime, Utc};
 use log::trace;
-use serde::Serialize;
-use winstructs::timestamp::WinTimestamp;
+use serde::{Deserialize, Serialize};
 
-#[derive(Serialize, Debug, Clone)]
+#[derive(Serialize, Deserialize, Debug, Clone)]
 pub struct StandardInfoAttr {
-    pub created: DateTime<Utc>,
-    pub modified: DateTime<Utc>,
-    pub mft_modified: DateTime<Utc>,
-    pub accessed: DateTime<Utc>,
+    pub created: FileTime,
+    pub modified: FileTime,
+    pub mft_modified: FileTime,
+    pub accessed: FileTime,
     /// DOS File Permissions
     pub file_flags: FileAttributeFlags,
     pub max_version: u32,
@@ -47,10 +46,10 @@ impl StandardInfoAttr {
     ///
     /// let attribute = StandardInfoAttr::from_reader(&mut Cursor::new(attribute_buffer)).unwrap();
     ///
-    /// assert_eq!(attribute.created.timestamp(), 1376278290);
-    /// assert_eq!(attribute.modified.timestamp(), 1379621073);
-    /// assert_eq!(attribute.mft_modified.timestamp(), 1379621073);
-    /// assert_eq!(attribute.accessed.timestamp(), 1379621073);
+    /// assert_eq!(attribute.created.to_datetime().unwrap().timestamp(), 1376278290);
+    /// assert_eq!(attribute.modified.to_datetime().unwrap().timestamp(), 1379621073);
+    /// assert_eq!(attribute.mft_modified.to_datetime().unwrap().timestamp(), 1379621073);
+    /// assert_eq!(attribute.accessed.to_datetime().unwrap().timestamp(), 1379621073);
     /// assert_eq!(attribute.file_flags.bits(), 32);
     /// assert_eq!(attribute.max_version, 0);
     /// assert_eq!(attribute.version, 0);
@@ -61,18 +60,10 @@ impl StandardInfoAttr {
     /// ```
     pub fn from_reader<S: Read + Seek>(reader: &mut S) -> Result<StandardInfoAttr> {
         trace!("Offset {}: StandardInfoAttr", reader.stream_position()?);
-        let created = WinTimestamp::from_reader(reader)
-            .map_err(Error::failed_to_read_windows_time)?
-            .to_datetime();
-        let modified = WinTimestamp::from_reader(reader)
-            .map_err(Error::failed_to_read_windows_time)?
-            .to_datetime();
-        let mft_modified = WinTimestamp::from_reader(reader)
-            .map_err(Error::failed_to_read_windows_time)?
-            .to_datetime();
-        let accessed = WinTimestamp::from_reader(reader)
-            .map_err(Error::failed_to_read_windows_time)?
-            .to_datetime();
+        let created = FileTime::from_reader(reader)?;
+        let modified = FileTime::from_reader(reader)?;
+        let mft_modified = FileTime::from_reader(reader)?;
+        let accessed = FileTime::from_reader(reader)?;
 
         Ok(StandardInfoAttr {
             created,
@@ -1,10 +1,11 @@
 use crate::err::{Error, Result};
+use crate::limits::ParseLimits;
 
 use byteorder::{LittleEndian, ReadBytesExt};
 use encoding::all::UTF_16LE;
 use encoding::{DecoderTrap, Encoding};
 
-use serde::Serialize;
+use serde::{Deserialize, Serialize};
 
 use std::io::{Read, Seek, SeekFrom};
 use winstructs::ntfs::mft_reference::MftReference;
@@ -12,7 +13,7 @@ use winstructs::ntfs::mft_reference::MftReference;
 /// The AttributeListAttr represents the $20 attribute, which contains a list
 /// of attribute entries in child entries.
 ///
-#[derive(Serialize, Clone, Debug)]
+#[derive(Serialize, Deserialize, Clone, Debug)]
 pub struct AttributeListAttr {
     /// A list of AttributeListEntry that make up this AttributeListAttr
     pub entries: Vec<AttributeListEntry>,
@@ -57,8 +58,18 @@ impl AttributeListAttr {
     /// assert_eq!(attribute_list.entries.len(), 7);
     /// ```
     pub fn from_stream<S: Read + Seek>(
+        stream: &mut S,
+        stream_size: Option<u64>,
+    ) -> Result<AttributeListAttr> {
+        Self::from_stream_with_limits(stream, stream_size, &ParseLimits::default())
+    }
+
+    /// Like `from_stream`, but fails with `Error::LimitExceeded` if the list has more entries
+    /// than `limits.max_attribute_list_entries`.
+    pub fn from_stream_with_limits<S: Read + Seek>(
         mut stream: &mut S,
         stream_size: Option<u64>,
+        limits: &ParseLimits,
     ) -> Result<AttributeListAttr> {
         let mut start_offset = stream.stream_position()?;
         let end_offset = match stream_size {
@@ -84,11 +95,19 @@ impl AttributeListAttr {
             // parse the entry from the stream
             let attr_entry = AttributeListEntry::from_stream(&mut stream)?;
 
+            // An empty entry would never advance the stream.
+            if attr_entry.record_length == 0 {
+                return Err(Error::InvalidRecordLength {
+                    record_length: u32::from(attr_entry.record_length),
+                });
+            }
+
             // update the starting offset
             start_offset += attr_entry.record_length as u64;
 
             // add attribute entry to entries vec
             entries.push(attr_entry);
+            limits.check_attribute_list_entries(entries.len())?;
 
             // seek the stream to next start offset to avoid padding
             stream.seek(SeekFrom::Start(start_offset))?;
@@ -101,7 +120,7 @@ impl AttributeListAttr {
 /// An AttributeListAttr is made up off multiple AttributeListEntry structs.
 /// <https://docs.microsoft.com/en-us/windows/win32/devnotes/attribute-list-entry>
 ///
-#[derive(Serialize, Clone, Debug)]
+#[derive(Serialize, Deserialize, Clone, Debug)]
 pub struct AttributeListEntry {
     /// The attribute code
     pub attribute_type: u32,
@@ -116,6 +135,7 @@ pub struct AttributeListEntry {
     /// In this case, this value is the lowest VCN that is described by the referenced segment.
     pub lowest_vcn: u64,
     /// The segments MFT reference
+    #[serde(deserialize_with = "crate::utils::deserialize_mft_reference")]
     pub segment_reference: MftReference,
     /// The attribute's id
     pub reserved: u16,
@@ -2,20 +2,19 @@ use std::io::{Read, Seek};
 
 use crate::attribute::FileAttributeFlags;
 use crate::err::{Error, Result};
+use crate::timestamp::FileTime;
 use log::trace;
 
 use byteorder::{LittleEndian, ReadBytesExt};
 use encoding::all::UTF_16LE;
 use encoding::{DecoderTrap, Encoding};
 
-use chrono::{DateTime, Utc};
 use num_traits::FromPrimitive;
-use serde::Serialize;
+use serde::{Deserialize, Serialize};
 
 use winstructs::ntfs::mft_reference::MftReference;
-use winstructs::timestamp::WinTimestamp;
 
-#[derive(FromPrimitive, Serialize, Clone, Debug, PartialOrd, PartialEq)]
+#[derive(FromPrimitive, Serialize, Deserialize, Clone, Debug, PartialOrd, PartialEq)]
 #[repr(u8)]
 pub enum FileNamespace {
     POSIX = 0,
@@ -24,13 +23,14 @@ pub enum FileNamespace {
     Win32AndDos = 3,
 }
 
-#[derive(Serialize, Clone, Debug, PartialEq)]
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
 pub struct FileNameAttr {
+    #[serde(deserialize_with = "crate::utils::deserialize_mft_reference")]
     pub parent: MftReference,
-    pub created: DateTime<Utc>,
-    pub modified: DateTime<Utc>,
-    pub mft_modified: DateTime<Utc>,
-    pub accessed: DateTime<Utc>,
+    pub created: FileTime,
+    pub modified: FileTime,
+    pub mft_modified: FileTime,
+    pub accessed: FileTime,
     pub logical_size: u64,
     pub physical_size: u64,
     pub flags: FileAttributeFlags,
@@ -62,10 +62,10 @@ impl FileNameAttr {
     /// let attribute = FileNameAttr::from_stream(&mut Cursor::new(attribute_buffer)).unwrap();
     ///
     /// assert_eq!(attribute.parent.entry, 5);
-    /// assert_eq!(attribute.created.timestamp(), 1370144608);
-    /// assert_eq!(attribute.modified.timestamp(), 1370144608);
-    /// assert_eq!(attribute.mft_modified.timestamp(), 1370144608);
-    /// assert_eq!(attribute.accessed.timestamp(), 1370144608);
+    /// assert_eq!(attribute.created.to_datetime().unwrap().timestamp(), 1370144608);
+    /// assert_eq!(attribute.modified.to_datetime().unwrap().timestamp(), 1370144608);
+    /// assert_eq!(attribute.mft_modified.to_datetime().unwrap().timestamp(), 1370144608);
+    /// assert_eq!(attribute.accessed.to_datetime().unwrap().timestamp(), 1370144608);
     /// assert_eq!(attribute.logical_size, 67108864);
     /// assert_eq!(attribute.physical_size, 67108864);
     /// assert_eq!(attribute.flags.bits(), 6);
@@ -78,18 +78,10 @@ impl FileNameAttr {
         trace!("Offset {}: FilenameAttr", stream.stream_position()?);
         let parent =
             MftReference::from_reader(stream).map_err(Error::failed_to_read_mft_reference)?;
-        let created = WinTimestamp::from_reader(stream)
-            .map_err(Error::failed_to_read_windows_time)?
-            .to_datetime();
-        let modified = WinTimestamp::from_reader(stream)
-            .map_err(Error::failed_to_read_windows_time)?
-            .to_datetime();
-        let mft_modified = WinTimestamp::from_reader(stream)
-            .map_err(Error::failed_to_read_windows_time)?
-            .to_datetime();
-        let accessed = WinTimestamp::from_reader(stream)
-            .map_err(Error::failed_to_read_windows_time)?
-            .to_datetime();
+        let created = FileTime::from_reader(stream)?;
+        let modified = FileTime::from_reader(stream)?;
+        let mft_modified = FileTime::from_reader(stream)?;
+        let accessed = FileTime::from_reader(stream)?;
 
         let logical_size = stream.read_u64::<LittleEndian>()?;
         let physical_size = stream.read_u64::<LittleEndian>()?;
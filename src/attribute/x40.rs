@@ -1,19 +1,23 @@
 use std::io::{Read, Seek};
 
 use crate::err::{Error, Result};
-use serde::Serialize;
+use serde::{Deserialize, Serialize};
 use winstructs::guid::Guid;
 
 /// $Data Attribute
-#[derive(Serialize, Clone, Debug)]
+#[derive(Serialize, Deserialize, Clone, Debug)]
 pub struct ObjectIdAttr {
     /// Unique Id assigned to file
+    #[serde(deserialize_with = "crate::utils::deserialize_guid")]
     pub object_id: Guid,
     /// Volume where file was created
+    #[serde(deserialize_with = "crate::utils::deserialize_optional_guid")]
     pub birth_volume_id: Option<Guid>,
     /// Original Object Id of file
+    #[serde(deserialize_with = "crate::utils::deserialize_optional_guid")]
     pub birth_object_id: Option<Guid>,
     /// Domain in which object was created
+    #[serde(deserialize_with = "crate::utils::deserialize_optional_guid")]
     pub domain_id: Option<Guid>,
 }
 
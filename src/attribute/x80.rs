@@ -2,7 +2,7 @@ use std::io::{Read, Seek};
 
 use crate::err::Result;
 use crate::utils;
-use serde::ser;
+use serde::{de, ser};
 
 /// $Data Attribute
 #[derive(Clone, Debug)]
@@ -10,9 +10,7 @@ pub struct DataAttr(Vec<u8>);
 
 impl DataAttr {
     pub fn from_stream<S: Read + Seek>(stream: &mut S, data_size: usize) -> Result<DataAttr> {
-        let mut data = vec![0_u8; data_size];
-
-        stream.read_exact(&mut data)?;
+        let data = utils::read_bytes(stream, data_size as u64)?;
 
         Ok(DataAttr(data))
     }
@@ -30,3 +28,12 @@ impl ser::Serialize for DataAttr {
         serializer.serialize_str(&utils::to_hex_string(&self.0))
     }
 }
+
+impl<'de> de::Deserialize<'de> for DataAttr {
+    fn deserialize<D>(deserializer: D) -> ::std::result::Result<Self, D::Error>
+    where
+        D: de::Deserializer<'de>,
+    {
+        utils::data_from_hex(deserializer).map(DataAttr)
+    }
+}
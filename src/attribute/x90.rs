@@ -1,20 +1,25 @@
 use std::io::{Read, Seek};
 
 use crate::impl_serialize_for_bitflags;
+use crate::impl_deserialize_for_bitflags;
 use crate::err::{Error, Result};
+use crate::limits::ParseLimits;
 use crate::attribute::x30::FileNameAttr;
 
 use byteorder::{LittleEndian, ReadBytesExt};
 
 use bitflags::bitflags;
-use serde::Serialize;
+use serde::{Deserialize, Serialize};
 use winstructs::ntfs::mft_reference::MftReference;
 use std::io::SeekFrom;
 use num_derive::FromPrimitive;    
 use num_traits::FromPrimitive;
 
+/// The MFT reference, record length, file name length and flags.
+const INDEX_ENTRY_HEADER_LENGTH: u16 = 16;
+
 /// $IndexRoot Attribute
-#[derive(Serialize, Clone, Debug)]
+#[derive(Serialize, Deserialize, Clone, Debug)]
 pub struct IndexRootAttr {
     /// Unique Id assigned to file
     pub attribute_type: u32,
@@ -37,7 +42,7 @@ pub struct IndexRootAttr {
 /// https://opensource.apple.com/source/ntfs/ntfs-52/kext/ntfs_layout.h
 /// https://docs.huihoo.com/doxygen/linux/kernel/3.7/layout_8h_source.html
 /// 
-#[derive(Serialize, Debug, Copy, Clone, Eq, PartialEq)]
+#[derive(Serialize, Deserialize, Debug, Copy, Clone, Eq, PartialEq)]
 #[repr(u32)]
 #[derive(FromPrimitive)]
 pub enum IndexCollationRules {
@@ -57,10 +62,20 @@ bitflags! {
     }
 }
 impl_serialize_for_bitflags! {IndexRootFlags}
+impl_deserialize_for_bitflags! {IndexRootFlags}
 
 impl IndexRootAttr {
     /// Data size should be either 16 or 64
     pub fn from_stream<S: Read + Seek>(stream: &mut S) -> Result<IndexRootAttr> {
+        Self::from_stream_with_limits(stream, &ParseLimits::default())
+    }
+
+    /// Like `from_stream`, but fails with `Error::LimitExceeded` if the node has more entries
+    /// than `limits.max_index_entries`.
+    pub fn from_stream_with_limits<S: Read + Seek>(
+        stream: &mut S,
+        limits: &ParseLimits,
+    ) -> Result<IndexRootAttr> {
         let attribute_type = stream.read_u32::<LittleEndian>()?;
         let collation_rule_val = stream.read_u32::<LittleEndian>()?;
         let collation_rule = IndexCollationRules::from_u32(collation_rule_val);
@@ -72,12 +87,17 @@ impl IndexRootAttr {
         };
         let index_entry_size = stream.read_u32::<LittleEndian>()?;
         let index_entry_number_of_cluster_blocks = stream.read_u32::<LittleEndian>()?;        
-        let index_node_start_pos = stream.stream_position().unwrap();
+        let index_node_start_pos = stream.stream_position()?;
         let relative_offset_to_index_node = stream.read_u32::<LittleEndian>()?;
         let index_node_length = stream.read_u32::<LittleEndian>()?;
         let index_node_allocation_length = stream.read_u32::<LittleEndian>()?;            
         let index_root_flags = IndexRootFlags::from_bits_truncate(stream.read_u32::<LittleEndian>()?);
-        let index_entries = IndexEntries::from_stream(stream, index_node_length, index_node_start_pos)?;
+        let index_entries = IndexEntries::from_stream_with_limits(
+            stream,
+            index_node_length,
+            index_node_start_pos,
+            limits,
+        )?;
 
         Ok(IndexRootAttr {
             attribute_type,
@@ -93,8 +113,9 @@ impl IndexRootAttr {
     }
 }
 
-#[derive(Serialize, Clone, Debug, PartialEq)]
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
 pub struct IndexEntryHeader {    
+    #[serde(deserialize_with = "crate::utils::deserialize_mft_reference")]
     pub mft_reference: MftReference,
     pub index_record_length: u16,
     pub attr_fname_length: u16,
@@ -108,21 +129,28 @@ bitflags! {
     }
 }
 impl_serialize_for_bitflags! {IndexEntryFlags}
+impl_deserialize_for_bitflags! {IndexEntryFlags}
 
 impl IndexEntryHeader {  
     pub fn from_stream<S: Read + Seek>(stream: &mut S) -> Result<Option<IndexEntryHeader>> {          
-        let start_pos = stream.stream_position().unwrap();
+        let start_pos = stream.stream_position()?;
 
         let mft_reference =
             MftReference::from_reader(stream).map_err(Error::failed_to_read_mft_reference)?;
         if mft_reference.entry > 0 && mft_reference.sequence > 0 {
             let index_record_length = stream.read_u16::<LittleEndian>()?;            
+            // The next entry follows this one, an empty entry would never advance.
+            if index_record_length < INDEX_ENTRY_HEADER_LENGTH {
+                return Err(Error::InvalidRecordLength {
+                    record_length: u32::from(index_record_length),
+                });
+            }
             let end_pos = start_pos + u64::from(index_record_length);           
             let attr_fname_length = stream.read_u16::<LittleEndian>()?;
             let flags = IndexEntryFlags::from_bits_truncate(stream.read_u32::<LittleEndian>()?);
             let fname_info = FileNameAttr::from_stream(stream)?;
 
-            stream.seek(SeekFrom::Start(end_pos)).unwrap(); 
+            stream.seek(SeekFrom::Start(end_pos))?;
             
             Ok(Some(IndexEntryHeader {
                 mft_reference,
@@ -138,7 +166,7 @@ impl IndexEntryHeader {
     }
 }
 
-#[derive(Serialize, Clone, Debug)]
+#[derive(Serialize, Deserialize, Clone, Debug)]
 pub struct IndexEntries {
     pub index_entries: Vec<IndexEntryHeader>
 }
@@ -148,15 +176,34 @@ impl IndexEntries {
         stream: &mut S,
         index_node_length: u32,
         index_node_start_pos: u64
-    ) -> Result<Self> {          
+    ) -> Result<Self> {
+        Self::from_stream_with_limits(
+            stream,
+            index_node_length,
+            index_node_start_pos,
+            &ParseLimits::default(),
+        )
+    }
+
+    /// Like `from_stream`, but fails with `Error::LimitExceeded` if the node has more entries
+    /// than `limits.max_index_entries`.
+    pub fn from_stream_with_limits<S: Read + Seek>(
+        stream: &mut S,
+        index_node_length: u32,
+        index_node_start_pos: u64,
+        limits: &ParseLimits,
+    ) -> Result<Self> {
         let end_pos = index_node_start_pos + u64::from(index_node_length);
 
         let mut index_entries: Vec<IndexEntryHeader> = Vec::new();
-        while stream.stream_position().unwrap() < end_pos
+        while stream.stream_position()? < end_pos
         {            
             let index_entry = IndexEntryHeader::from_stream(stream)?;
             match index_entry {
-                Some(inner)   => index_entries.push(inner),
+                Some(inner)   => {
+                    index_entries.push(inner);
+                    limits.check_index_entries(index_entries.len())?;
+                }
                 None          => break,
             }
         }
@@ -2,8 +2,10 @@
 extern crate criterion;
 extern crate mft;
 
-use criterion::Criterion;
+use criterion::{black_box, Criterion};
 use mft::{MftEntry, MftParser, ReadSeek};
+#[cfg(feature = "mmap")]
+use std::io::Cursor;
 
 fn process_1000_mft_records(sample: &[u8]) {
     let mut parser = MftParser::from_buffer(sample.to_vec()).unwrap();
@@ -14,6 +16,20 @@ fn process_1000_mft_records(sample: &[u8]) {
             count += 1;
         }
     }
+
+    black_box(count);
+}
+
+#[cfg(feature = "mmap")]
+fn process_1000_mft_records_borrowed(parser: &mut MftParser<Cursor<impl AsRef<[u8]>>>) {
+    let mut count = 0;
+    for i in 0..1000 {
+        if let Ok(entry) = parser.get_entry_ref(i) {
+            count += entry.iter_attributes().count();
+        }
+    }
+
+    black_box(count);
 }
 
 fn get_full_path(parser: &mut MftParser<impl ReadSeek>, entries: &[MftEntry]) {
@@ -30,6 +46,15 @@ fn criterion_benchmark(c: &mut Criterion) {
         b.iter(|| process_1000_mft_records(sample))
     });
 
+    // Borrowed entries, from the memory mapped sample.
+    #[cfg(feature = "mmap")]
+    c.bench_function("read 1000 records (borrowed)", move |b| {
+        let mut parser =
+            MftParser::from_path_mmap(concat!(env!("CARGO_MANIFEST_DIR"), "/samples/MFT")).unwrap();
+
+        b.iter(|| process_1000_mft_records_borrowed(&mut parser))
+    });
+
     c.bench_function("get_full_path", move |b| {
         let mut parser = MftParser::from_buffer(sample.to_vec()).unwrap();
 
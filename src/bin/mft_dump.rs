@@ -2,16 +2,31 @@ use clap::{Arg, ArgAction, ArgMatches};
 use indoc::indoc;
 use log::Level;
 
-use mft::attribute::MftAttributeType;
+use mft::analysis::{TimestompAnalyzer, TimestompFinding};
+use mft::attribute::x80::DataAttr;
+use mft::attribute::{MftAttributeType, ResidualAttribute};
+use mft::carve::MftCarver;
 use mft::mft::MftParser;
-use mft::MftEntry;
+use mft::partition::list_partitions;
+use mft::schema::SCHEMA_VERSION;
+use mft::stream::{MftStreamParser, PathTable};
+use mft::timeline::{TimelineEvent, TimelineSorter};
+use mft::timestamp::{TimestampFormat, TimestampOptions};
+use mft::timezone::OutputTimeZone;
+use mft::{FileTime, FixupOptions, FixupPolicy, MftEntry};
 
 use dialoguer::Confirm;
+use mft::bodyfile::BodyfileLine;
 use mft::csv::FlatMftEntryWithName;
+use mft::derived::DerivedFields;
+use mft::err::ErrorReport;
+use mft::fields::{FieldSelection, FIELDS};
 
 use anyhow::{anyhow, Context, Error, Result};
+use chrono::{DateTime, NaiveDate, TimeZone, Utc};
+use serde::{Deserialize, Serialize};
 use std::fs::File;
-use std::io::{Read, Seek, Write};
+use std::io::{BufReader, BufWriter, Read, Seek, SeekFrom, Write};
 use std::path::{Path, PathBuf};
 
 use mft::entry::ZERO_HEADER;
@@ -19,6 +34,7 @@ use std::fmt::Write as FmtWrite;
 use std::ops::RangeInclusive;
 use std::str::FromStr;
 use std::{fs, io, path};
+use winstructs::ntfs::mft_reference::MftReference;
 
 #[derive(Debug, PartialOrd, PartialEq)]
 #[allow(clippy::upper_case_acronyms)]
@@ -26,6 +42,8 @@ enum OutputFormat {
     JSON,
     JSONL,
     CSV,
+    /// The Sleuth Kit's body file, for `mactime`.
+    Bodyfile,
 }
 
 impl OutputFormat {
@@ -34,6 +52,7 @@ impl OutputFormat {
             "json" => Some(OutputFormat::JSON),
             "jsonl" => Some(OutputFormat::JSONL),
             "csv" => Some(OutputFormat::CSV),
+            "bodyfile" => Some(OutputFormat::Bodyfile),
             _ => None,
         }
     }
@@ -45,6 +64,10 @@ impl Ranges {
     pub fn chain(&self) -> impl Iterator<Item = usize> + '_ {
         self.0.iter().cloned().flatten()
     }
+
+    pub fn contains(&self, n: usize) -> bool {
+        self.0.iter().any(|range| range.contains(&n))
+    }
 }
 
 impl FromStr for Ranges {
@@ -115,6 +138,14 @@ mod tests {
         assert_eq!(ranges.0, vec![1..=10, 20..=25]);
     }
 
+    #[test]
+    fn it_checks_membership() {
+        let ranges = Ranges::from_str("1-5,8").unwrap();
+        assert!(ranges.contains(5));
+        assert!(ranges.contains(8));
+        assert!(!ranges.contains(6));
+    }
+
     #[test]
     fn it_errors_on_a_random_string() {
         let ranges = Ranges::from_str("hello");
@@ -128,21 +159,97 @@ mod tests {
     }
 }
 
+struct TimelineOptions {
+    from: Option<DateTime<Utc>>,
+    to: Option<DateTime<Utc>>,
+    max_events_in_memory: usize,
+}
+
+impl TimelineOptions {
+    /// Timestamps which are not set (or out of range) are only in unbounded timelines.
+    fn contains(&self, timestamp: &FileTime) -> bool {
+        match timestamp.to_datetime() {
+            Some(time) => {
+                self.from.map_or(true, |from| time >= from) && self.to.map_or(true, |to| time < to)
+            }
+            None => self.from.is_none() && self.to.is_none(),
+        }
+    }
+}
+
+/// Parses either an RFC 3339 time, or a date (which is midnight UTC).
+fn parse_time(s: &str) -> Result<DateTime<Utc>> {
+    if let Ok(time) = DateTime::parse_from_rfc3339(s) {
+        return Ok(time.with_timezone(&Utc));
+    }
+
+    let date = NaiveDate::parse_from_str(s, "%Y-%m-%d")
+        .map_err(|_| anyhow!("Expected an RFC 3339 time or a date, got `{}`", s))?;
+    Ok(Utc.from_utc_datetime(&date.and_hms_opt(0, 0, 0).expect("midnight is valid")))
+}
+
 struct MftDump {
+    /// `-` means the MFT is streamed from stdin.
     filepath: PathBuf,
+    entry_size: Option<u32>,
+    /// Index of the NTFS partition to read, for whole-disk images.
+    #[cfg(feature = "images")]
+    partition: Option<usize>,
+    list_partitions: bool,
     // We use an option here to be able to move the output out of mftdump from a mutable reference.
     output: Option<Box<dyn Write>>,
     data_streams_output: Option<PathBuf>,
+    /// Errors are also written here as JSON lines, see `ErrorReport`.
+    errors_output: Option<Box<dyn Write>>,
     verbosity_level: Option<Level>,
     output_format: OutputFormat,
     ranges: Option<Ranges>,
+    fixup: FixupOptions,
+    recover: bool,
+    /// When set, records are carved from the input (with this sector size) instead of reading it as an MFT.
+    carve: Option<usize>,
+    /// When set, the timestamps of the entries are written as a timeline instead of the entries.
+    timeline: Option<TimelineOptions>,
+    /// Adds the attributes carved from the slack of each entry to the JSON output.
+    slack: bool,
+    /// Writes JSON entries without their derived fields (see `mft::derived`).
+    /// When streaming, entries are then written as they are read, instead of once the paths are known.
+    raw: bool,
+    /// Flags timestomped entries, with the lifetime of the volume once its `$MFT` entry was read.
+    analyzer: TimestompAnalyzer,
+    /// The format and time zone of the timestamps in JSON, CSV and timeline outputs.
+    timestamps: TimestampOptions,
+    /// When set, entries are written as rows of these fields (see `mft::fields`), in JSON as well as in CSV.
+    fields: Option<FieldSelection>,
 }
 
 impl MftDump {
-    pub fn from_cli_matches(matches: &ArgMatches) -> Result<Self> {
+    pub fn from_cli_matches(app_matches: &ArgMatches) -> Result<Self> {
+        // Global options are also available from the matches of the subcommand.
+        let (matches, carve, timeline) = match app_matches.subcommand() {
+            Some(("carve", carve_matches)) => (
+                carve_matches,
+                carve_matches.get_one::<usize>("sector-size").copied(),
+                None,
+            ),
+            Some(("timeline", timeline_matches)) => (
+                timeline_matches,
+                None,
+                Some(TimelineOptions {
+                    from: timeline_matches.get_one("from").copied(),
+                    to: timeline_matches.get_one("to").copied(),
+                    max_events_in_memory: *timeline_matches
+                        .get_one("max-events-in-memory")
+                        .expect("has default"),
+                }),
+            ),
+            _ => (app_matches, None, None),
+        };
+
         let output_format: &String = matches.get_one("output-format").expect("has default");
         let output_target: Option<&String> = matches.get_one("output-target");
         let data_streams_target: Option<&String> = matches.get_one("data-streams-target");
+        let errors_target: Option<&String> = matches.get_one("errors-target");
         let input: &String = matches.get_one("INPUT").expect("required");
 
         let output_format =
@@ -167,6 +274,22 @@ impl MftDump {
             Some(Box::new(io::stdout()))
         };
 
+        let errors_output: Option<Box<dyn Write>> = match errors_target {
+            Some(path) => {
+                match Self::create_output_file(path, !matches.get_flag("no-confirm-overwrite")) {
+                    Ok(f) => Some(Box::new(io::BufWriter::new(f))),
+                    Err(e) => {
+                        return Err(anyhow!(
+                            "An error occurred while creating errors file at `{}` - `{}`",
+                            path,
+                            e
+                        ));
+                    }
+                }
+            }
+            None => None,
+        };
+
         let data_streams_output = if let Some(path) = data_streams_target {
             let path = PathBuf::from(path);
             Self::create_output_dir(&path)?;
@@ -186,18 +309,81 @@ impl MftDump {
             }
         };
 
-        let ranges = match matches.get_one::<&String>("entry-range") {
+        let ranges = match app_matches.get_one::<&String>("entry-range") {
             Some(range) => Some(Ranges::from_str(range)?),
             None => None,
         };
 
+        let entry_size = app_matches.get_one::<u32>("entry-size").copied();
+        let partition = app_matches.get_one::<usize>("partition").copied();
+        let list_partitions = app_matches.get_flag("list-partitions");
+
+        let fixup = FixupOptions {
+            policy: match matches
+                .get_one::<String>("fixup-policy")
+                .expect("has default")
+                .as_str()
+            {
+                "strict" => FixupPolicy::Strict,
+                "preserve" => FixupPolicy::Preserve,
+                _ => FixupPolicy::Lenient,
+            },
+            stride: *matches
+                .get_one::<usize>("fixup-stride")
+                .expect("has default"),
+        };
+
+        if input == "-" && (partition.is_some() || list_partitions) {
+            return Err(anyhow!(
+                "Partitions cannot be read when streaming from stdin"
+            ));
+        }
+
+        #[cfg(not(feature = "images"))]
+        if partition.is_some() {
+            return Err(anyhow!("Reading partitions requires the `images` feature"));
+        }
+
+        let fields = match matches.get_one::<String>("fields") {
+            Some(fields) => Some(FieldSelection::parse(fields)?),
+            None => None,
+        };
+        if fields.is_some() && (output_format == OutputFormat::Bodyfile || timeline.is_some()) {
+            return Err(anyhow!(
+                "`--fields` selects the fields of entries written as JSON, JSONL or CSV"
+            ));
+        }
+
+        let timestamp_format: &String = matches.get_one("timestamp-format").expect("has default");
+        let time_zone: &String = matches.get_one("timezone").expect("has default");
+        let timestamps = TimestampOptions::default()
+            .with_format(
+                TimestampFormat::from_str(timestamp_format)
+                    .expect("Validated with clap default values"),
+            )
+            .with_time_zone(OutputTimeZone::parse(time_zone)?);
+
         Ok(MftDump {
             filepath: PathBuf::from(input),
+            entry_size,
+            #[cfg(feature = "images")]
+            partition,
+            list_partitions,
             output,
             data_streams_output,
+            errors_output,
             verbosity_level,
             output_format,
             ranges,
+            fixup,
+            recover: matches.get_flag("recover"),
+            carve,
+            timeline,
+            slack: matches.get_flag("slack"),
+            raw: matches.get_flag("raw"),
+            analyzer: TimestompAnalyzer::new(),
+            timestamps,
+            fields,
         })
     }
 
@@ -272,19 +458,60 @@ impl MftDump {
     pub fn run(&mut self) -> Result<()> {
         self.try_to_initialize_logging();
 
-        let mut parser = MftParser::from_path(&self.filepath)?;
+        let timestamps = self.timestamps.clone();
+        timestamps.scope(|| self.dump())
+    }
 
-        // Since the JSON parser can do away with a &mut Write, but the csv parser needs ownership
-        // of `Write`, we eagerly create the csv writer here, moving the Box<Write> out from
-        // `Mftdump` and replacing it with None placeholder.
-        let mut csv_writer = match self.output_format {
-            OutputFormat::CSV => {
-                Some(csv::Writer::from_writer(self.output.take().expect(
-                    "There can only be one flow accessing the output at a time",
-                )))
+    fn dump(&mut self) -> Result<()> {
+        if self.filepath == Path::new("-") {
+            #[cfg(feature = "compression")]
+            let input = mft::container::decompress_stream(io::stdin().lock())?;
+            #[cfg(not(feature = "compression"))]
+            let input = io::stdin().lock();
+
+            if self.timeline.is_some() {
+                let mut parser = MftStreamParser::from_read(input, self.entry_size)?
+                    .with_fixup_options(self.fixup)
+                    .with_recovery(self.recover);
+                return self.run_timeline(parser.iter_entries());
             }
-            _ => None,
-        };
+
+            return match self.carve {
+                Some(sector_size) => self.run_carving(input, sector_size),
+                None => self.run_streaming(input),
+            };
+        }
+
+        if let Some(sector_size) = self.carve {
+            #[cfg(feature = "images")]
+            let input = mft::image::open(&self.filepath)?;
+            #[cfg(not(feature = "images"))]
+            let input = File::open(&self.filepath)?;
+
+            return self.run_carving(input, sector_size);
+        }
+
+        if self.list_partitions {
+            return self.print_partitions();
+        }
+
+        #[cfg(feature = "images")]
+        let parser = MftParser::from_image_path_partition(&self.filepath, self.partition)?;
+        #[cfg(not(feature = "images"))]
+        let parser = MftParser::from_path(&self.filepath)?;
+        let mut parser = parser
+            .with_fixup_options(self.fixup)
+            .with_recovery(self.recover);
+
+        if self.timeline.is_some() {
+            return self.run_timeline(parser.iter_entries());
+        }
+
+        if let Ok(mft_entry) = parser.get_entry(0) {
+            self.analyzer = TimestompAnalyzer::for_volume(&mft_entry);
+        }
+
+        let mut csv_writer = self.take_csv_writer()?;
 
         let number_of_entries = parser.get_entry_count();
 
@@ -307,75 +534,343 @@ impl MftDump {
                 },
                 Err(error) => {
                     eprintln!("{}", error);
+                    self.write_error_report(&error.report())?;
                     continue;
                 }
             };
 
+            self.report_attribute_errors(&entry)?;
+
             if let Some(data_streams_dir) = &self.data_streams_output {
                 if let Ok(Some(path)) = parser.get_full_path_for_entry(&entry) {
-                    let sanitized_path = sanitized(&path.to_string_lossy());
-
-                    for (i, (name, stream)) in entry
-                        .iter_attributes()
-                        .filter_map(|a| a.ok())
-                        .filter_map(|a| {
-                            if a.header.type_code == MftAttributeType::DATA {
-                                // resident
-                                let name = a.header.name.clone();
-                                a.data.into_data().map(|data| (name, data))
-                            } else {
-                                None
-                            }
-                        })
-                        .enumerate()
-                    {
-                        let orig_path_component: String = data_streams_dir
-                            .join(&sanitized_path)
-                            .to_string_lossy()
-                            .to_string();
-
-                        // Add some random bits to prevent collisions
-                        let random: [u8; 6] = rand::random();
-                        let rando_string: String = to_hex_string(&random);
-
-                        let truncated: String = orig_path_component.chars().take(150).collect();
-                        let data_stream_path = format!(
-                            "{path}__{random}_{stream_number}_{stream_name}.dontrun",
-                            path = truncated,
-                            random = rando_string,
-                            stream_number = i,
-                            stream_name = name
-                        );
-
-                        if PathBuf::from(&data_stream_path).exists() {
-                            return Err(anyhow!(
-                                "Tried to override an existing stream {} already exists!\
-                                 This is a bug, please report to github!",
-                                data_stream_path
-                            ));
-                        }
-
-                        let mut f = File::create(&data_stream_path)?;
-                        f.write_all(stream.data())?;
+                    write_data_streams(data_streams_dir, &path, resident_streams(&entry))?;
+                }
+            }
+
+            match self.output_format {
+                OutputFormat::JSON | OutputFormat::JSONL if self.fields.is_none() => {
+                    let derived = if self.raw {
+                        None
+                    } else {
+                        Some(DerivedFields::from_entry(&entry, &mut parser)?)
+                    };
+                    self.print_json_entry(&entry, None, derived)?
+                }
+                OutputFormat::JSON | OutputFormat::JSONL | OutputFormat::CSV => {
+                    let row = FlatMftEntryWithName::from_entry(&entry, &mut parser);
+                    let row = self.with_findings(row, &entry);
+                    self.print_row(row, csv_writer.as_mut())?
+                }
+                OutputFormat::Bodyfile => {
+                    for line in BodyfileLine::from_entry(&entry, &mut parser) {
+                        self.print_bodyfile_line(&line)?;
                     }
                 }
             }
+        }
+
+        Ok(())
+    }
+
+    /// Lists the partitions of a disk image, one per line.
+    fn print_partitions(&mut self) -> Result<()> {
+        #[cfg(feature = "images")]
+        let mut image = mft::image::open(&self.filepath)?;
+        #[cfg(not(feature = "images"))]
+        let mut image = io::BufReader::new(File::open(&self.filepath)?);
+
+        let partitions = list_partitions(&mut image)?;
+        if partitions.is_empty() {
+            eprintln!("No partition table found, the input may be a volume (or an MFT)");
+        }
+
+        let output = self.output.as_mut().expect("Output is only taken for CSV");
+
+        writeln!(
+            output,
+            "{:<6} {:<7} {:>14} {:>14} {:<36} {:<5} NAME",
+            "INDEX", "SCHEME", "OFFSET", "SIZE", "TYPE", "NTFS"
+        )?;
+
+        for partition in partitions {
+            writeln!(
+                output,
+                "{:<6} {:<7} {:>14} {:>14} {:<36} {:<5} {}",
+                partition.index,
+                format!("{:?}", partition.scheme).to_uppercase(),
+                partition.offset,
+                partition.size,
+                partition.partition_type.to_string(),
+                if partition.is_ntfs { "yes" } else { "no" },
+                partition.name.unwrap_or_default()
+            )?;
+        }
+
+        Ok(())
+    }
+
+    /// Reads the MFT in a single forward pass.
+    /// Paths are only known once all the entries were seen,
+    /// so any output which needs them is deferred until the end of the stream.
+    fn run_streaming(&mut self, input: impl Read) -> Result<()> {
+        let mut parser = MftStreamParser::from_read(input, self.entry_size)?
+            .with_fixup_options(self.fixup)
+            .with_recovery(self.recover);
+        let mut paths = PathTable::new();
+
+        let csv_writer = self.take_csv_writer()?;
+        let mut deferred = DeferredOutput::default();
+
+        let ranges = self.ranges.take();
+
+        for entry in parser.iter_entries() {
+            let entry = match entry {
+                Ok(entry) => entry,
+                Err(error) => {
+                    eprintln!("{}", error);
+                    self.write_error_report(&error.report())?;
+                    continue;
+                }
+            };
+
+            // Entries outside of the requested ranges are still needed to resolve paths.
+            paths.add_entry(&entry);
+
+            if entry.header.record_number == 0 {
+                self.analyzer = TimestompAnalyzer::for_volume(&entry);
+            }
+
+            if let Some(ranges) = &ranges {
+                if !ranges.contains(entry.header.record_number as usize) {
+                    continue;
+                }
+            }
+
+            if &entry.header.signature == ZERO_HEADER {
+                continue;
+            }
+
+            self.report_attribute_errors(&entry)?;
+
+            if self.data_streams_output.is_some() {
+                let streams = resident_streams(&entry);
+                if !streams.is_empty() {
+                    deferred.streams.push((reference_of(&entry), streams));
+                }
+            }
+
+            match self.output_format {
+                OutputFormat::JSON | OutputFormat::JSONL if self.raw && self.fields.is_none() => {
+                    self.print_json_entry(&entry, None, None)?
+                }
+                _ => deferred.spool_entry(self.spooled_entry(entry, None))?,
+            }
+        }
+
+        self.write_deferred(&paths, csv_writer, deferred)
+    }
+
+    /// Carves records from the input in a single forward pass.
+    /// Like when streaming, paths are resolved (from the carved entries) once the input is exhausted.
+    fn run_carving(&mut self, input: impl Read, sector_size: usize) -> Result<()> {
+        let carver = MftCarver::new(input)
+            .with_sector_size(sector_size)
+            .with_fixup_options(self.fixup)
+            .with_recovery(self.recover);
+        // Records of several MFTs, or several uses of a record, may have been carved.
+        let mut paths = PathTable::new().with_sequence_matching(true);
+
+        let csv_writer = self.take_csv_writer()?;
+        let mut deferred = DeferredOutput::default();
+
+        for carved in carver {
+            let carved = match carved {
+                Ok(carved) => carved,
+                Err(error) => {
+                    eprintln!("{}", error);
+                    self.write_error_report(&error.report())?;
+                    continue;
+                }
+            };
+            let entry = &carved.entry;
+
+            paths.add_entry(entry);
+            self.report_attribute_errors(entry)?;
+
+            if self.data_streams_output.is_some() {
+                let streams = resident_streams(entry);
+                if !streams.is_empty() {
+                    deferred.streams.push((reference_of(entry), streams));
+                }
+            }
 
             match self.output_format {
-                OutputFormat::JSON | OutputFormat::JSONL => self.print_json_entry(&entry)?,
-                OutputFormat::CSV => self.print_csv_entry(
-                    &entry,
-                    &mut parser,
-                    csv_writer
+                OutputFormat::JSON | OutputFormat::JSONL if self.raw && self.fields.is_none() => {
+                    self.print_json_entry(entry, Some(carved.offset), None)?
+                }
+                _ => deferred.spool_entry(self.spooled_entry(carved.entry, Some(carved.offset)))?,
+            }
+        }
+
+        self.write_deferred(&paths, csv_writer, deferred)
+    }
+
+    /// Explodes the entries into timeline events, and writes them sorted by time.
+    /// Like when streaming, paths are resolved once all the entries were seen.
+    fn run_timeline(
+        &mut self,
+        entries: impl Iterator<Item = mft::err::Result<MftEntry>>,
+    ) -> Result<()> {
+        let options = self.timeline.take().expect("Only called for `timeline`");
+        if self.output_format == OutputFormat::Bodyfile {
+            return Err(anyhow!("A timeline can be written as JSON, JSONL or CSV, use `-o bodyfile` without `timeline` for a body file"));
+        }
+
+        let mut sorter = TimelineSorter::new().with_max_in_memory(options.max_events_in_memory);
+        let mut paths = PathTable::new();
+
+        for entry in entries {
+            let entry = match entry {
+                Ok(entry) => entry,
+                Err(error) => {
+                    eprintln!("{}", error);
+                    self.write_error_report(&error.report())?;
+                    continue;
+                }
+            };
+
+            paths.add_entry(&entry);
+            if &entry.header.signature == ZERO_HEADER {
+                continue;
+            }
+
+            self.report_attribute_errors(&entry)?;
+
+            for event in TimelineEvent::iter_from_entry(&entry, PathBuf::new()) {
+                if options.contains(&event.timestamp) {
+                    sorter.push(event)?;
+                }
+            }
+        }
+
+        let mut csv_writer = self.take_csv_writer()?;
+
+        for event in sorter.into_sorted()? {
+            let mut event = event?;
+            event.full_path = paths.get_full_path(event.entry_id).unwrap_or_default();
+
+            match csv_writer.as_mut() {
+                Some(writer) => writer.serialize(event)?,
+                None => {
+                    let out = self
+                        .output
                         .as_mut()
-                        .expect("CSV Writer is for OutputFormat::CSV"),
-                )?,
+                        .expect("CSV Flow cannot occur, so `Mftdump` should still Own `output`");
+                    out.write_all(&Self::to_json(&event, &self.output_format))?;
+                    out.write_all(b"\n")?;
+                }
             }
         }
 
         Ok(())
     }
 
+    /// Writes the output which was waiting for all the paths to be known.
+    fn write_deferred(
+        &mut self,
+        paths: &PathTable,
+        mut csv_writer: Option<csv::Writer<Box<dyn Write>>>,
+        deferred: DeferredOutput,
+    ) -> Result<()> {
+        if let Some(data_streams_dir) = &self.data_streams_output {
+            for (reference, streams) in deferred.streams {
+                if let Some(path) = paths.get_full_path_for_reference(reference) {
+                    write_data_streams(data_streams_dir, &path, streams)?;
+                }
+            }
+        }
+
+        if let Some(spool) = deferred.entries {
+            let mut spool = spool.into_inner().map_err(io::IntoInnerError::into_error)?;
+            spool.seek(SeekFrom::Start(0))?;
+
+            let mut entries =
+                serde_json::Deserializer::from_reader(BufReader::new(spool)).into_iter();
+            while let Some(spooled) = spooled_timestamps().scope(|| entries.next()) {
+                self.write_spooled_entry(spooled?, paths, csv_writer.as_mut())?;
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Writes an entry read back from the spool, in the output format, now that its path is known.
+    fn write_spooled_entry(
+        &mut self,
+        spooled: SpooledEntry,
+        paths: &PathTable,
+        csv_writer: Option<&mut csv::Writer<Box<dyn Write>>>,
+    ) -> Result<()> {
+        match self.output_format {
+            OutputFormat::JSON | OutputFormat::JSONL if self.fields.is_none() => {
+                let derived = DerivedFields::from_entry_and_path_table(&spooled.entry, paths);
+
+                self.write_json_entry(&JsonEntry {
+                    schema_version: SCHEMA_VERSION,
+                    carved_offset: spooled.carved_offset,
+                    derived: Some(derived),
+                    entry: &spooled.entry,
+                    residual_attributes: spooled.residual_attributes,
+                    timestomp_findings: spooled.timestomp_findings,
+                })
+            }
+            OutputFormat::JSON | OutputFormat::JSONL | OutputFormat::CSV => {
+                let full_path = paths
+                    .get_full_path_for_entry(&spooled.entry)
+                    .unwrap_or_default();
+                let mut row = FlatMftEntryWithName::from_entry_and_path(&spooled.entry, full_path)
+                    .with_findings(&spooled.timestomp_findings);
+                row.carved_offset = spooled.carved_offset;
+
+                self.print_row(row, csv_writer)
+            }
+            OutputFormat::Bodyfile => {
+                let full_path = paths
+                    .get_full_path_for_entry(&spooled.entry)
+                    .unwrap_or_default();
+
+                for line in BodyfileLine::from_entry_and_path(&spooled.entry, full_path) {
+                    self.print_bodyfile_line(&line)?;
+                }
+
+                Ok(())
+            }
+        }
+    }
+
+    /// Since the JSON parser can do away with a &mut Write, but the csv parser needs ownership
+    /// of `Write`, we eagerly create the csv writer here, moving the Box<Write> out from
+    /// `Mftdump` and replacing it with None placeholder.
+    fn take_csv_writer(&mut self) -> Result<Option<csv::Writer<Box<dyn Write>>>> {
+        match self.output_format {
+            OutputFormat::CSV => {
+                let mut writer = csv::Writer::from_writer(
+                    self.output
+                        .take()
+                        .expect("There can only be one flow accessing the output at a time"),
+                );
+
+                // Projected rows are written as records, so the header isn't written by `serialize`.
+                if let Some(fields) = &self.fields {
+                    writer.write_record(fields.header())?;
+                }
+
+                Ok(Some(writer))
+            }
+            _ => Ok(None),
+        }
+    }
+
     fn try_to_initialize_logging(&self) {
         if let Some(level) = self.verbosity_level {
             match simplelog::WriteLogger::init(
@@ -389,17 +884,69 @@ impl MftDump {
         }
     }
 
-    pub fn print_json_entry(&mut self, entry: &MftEntry) -> Result<()> {
+    /// Writes the attributes of the entry which failed to parse to the errors file, if there is one.
+    fn report_attribute_errors(&mut self, entry: &MftEntry) -> Result<()> {
+        if self.errors_output.is_none() {
+            return Ok(());
+        }
+
+        for record in entry.attribute_records() {
+            if let Some(error) = record.error() {
+                self.write_error_report(&error.report(entry.header.record_number))?;
+            }
+        }
+
+        Ok(())
+    }
+
+    fn write_error_report(&mut self, report: &ErrorReport) -> Result<()> {
+        if let Some(out) = self.errors_output.as_mut() {
+            serde_json::to_writer(&mut *out, report)?;
+            out.write_all(b"\n")?;
+        }
+
+        Ok(())
+    }
+
+    pub fn print_json_entry(
+        &mut self,
+        entry: &MftEntry,
+        carved_offset: Option<u64>,
+        derived: Option<DerivedFields>,
+    ) -> Result<()> {
+        let entry = JsonEntry {
+            schema_version: SCHEMA_VERSION,
+            carved_offset,
+            derived,
+            entry,
+            residual_attributes: self
+                .slack
+                .then(|| entry.iter_residual_attributes().collect()),
+            timestomp_findings: self.analyzer.analyze(entry),
+        };
+
+        self.write_json_entry(&entry)
+    }
+
+    /// Everything which goes into the JSON entry but its derived fields, which need the record of the entry.
+    fn spooled_entry(&self, entry: MftEntry, carved_offset: Option<u64>) -> SpooledEntry {
+        SpooledEntry {
+            carved_offset,
+            residual_attributes: self
+                .slack
+                .then(|| entry.iter_residual_attributes().collect()),
+            timestomp_findings: self.analyzer.analyze(&entry),
+            entry,
+        }
+    }
+
+    fn write_json_entry(&mut self, entry: &JsonEntry) -> Result<()> {
         let out = self
             .output
             .as_mut()
             .expect("CSV Flow cannot occur, so `Mftdump` should still Own `output`");
 
-        let json_str = if self.output_format == OutputFormat::JSON {
-            serde_json::to_vec_pretty(&entry).expect("It should be valid UTF-8")
-        } else {
-            serde_json::to_vec(&entry).expect("It should be valid UTF-8")
-        };
+        let json_str = Self::to_json(entry, &self.output_format);
 
         out.write_all(&json_str)?;
         out.write_all(b"\n")?;
@@ -407,20 +954,185 @@ impl MftDump {
         Ok(())
     }
 
-    pub fn print_csv_entry<W: Write>(
-        &self,
-        entry: &MftEntry,
-        parser: &mut MftParser<impl Read + Seek>,
-        writer: &mut csv::Writer<W>,
+    fn to_json(value: &impl Serialize, output_format: &OutputFormat) -> Vec<u8> {
+        if *output_format == OutputFormat::JSON {
+            serde_json::to_vec_pretty(value).expect("It should be valid UTF-8")
+        } else {
+            serde_json::to_vec(value).expect("It should be valid UTF-8")
+        }
+    }
+
+    /// Writes a row to the CSV writer (when writing CSV), or as JSON, projected to the selected fields if there are any.
+    fn print_row(
+        &mut self,
+        row: FlatMftEntryWithName,
+        csv_writer: Option<&mut csv::Writer<Box<dyn Write>>>,
     ) -> Result<()> {
-        let flat_entry = FlatMftEntryWithName::from_entry(entry, parser);
+        match (csv_writer, &self.fields) {
+            (Some(writer), Some(fields)) => {
+                writer.write_record(fields.project(&row).to_record())?
+            }
+            (Some(writer), None) => writer.serialize(row)?,
+            (None, fields) => {
+                let json = match fields {
+                    Some(fields) => Self::to_json(&fields.project(&row), &self.output_format),
+                    None => Self::to_json(&row, &self.output_format),
+                };
+
+                let out = self
+                    .output
+                    .as_mut()
+                    .expect("CSV Flow cannot occur, so `Mftdump` should still Own `output`");
+                out.write_all(&json)?;
+                out.write_all(b"\n")?;
+            }
+        }
+
+        Ok(())
+    }
+
+    /// The CSV row already has the findings of the default analyzer, which doesn't know the lifetime of the volume.
+    fn with_findings(&self, row: FlatMftEntryWithName, entry: &MftEntry) -> FlatMftEntryWithName {
+        if self.analyzer == TimestompAnalyzer::default() {
+            return row;
+        }
+
+        row.with_findings(&self.analyzer.analyze(entry))
+    }
+
+    pub fn print_bodyfile_line(&mut self, line: &BodyfileLine) -> Result<()> {
+        let out = self
+            .output
+            .as_mut()
+            .expect("CSV Flow cannot occur, so `Mftdump` should still Own `output`");
+
+        writeln!(out, "{}", line)?;
+
+        Ok(())
+    }
+}
+
+/// Output which is written once all the paths are known, see `run_streaming`.
+#[derive(Default)]
+struct DeferredOutput {
+    /// Entries waiting for their paths (see `SpooledEntry`), whatever the output format, which are
+    /// spooled to a temporary file as JSON lines rather than kept in memory.
+    entries: Option<BufWriter<File>>,
+    /// Resident data streams, by entry.
+    streams: Vec<(MftReference, Vec<(String, DataAttr)>)>,
+}
+
+impl DeferredOutput {
+    fn spool_entry(&mut self, entry: SpooledEntry) -> Result<()> {
+        let spool = match &mut self.entries {
+            Some(spool) => spool,
+            None => self.entries.insert(BufWriter::new(tempfile::tempfile()?)),
+        };
 
-        writer.serialize(flat_entry)?;
+        spooled_timestamps().scope(|| serde_json::to_writer(&mut *spool, &entry))?;
+        spool.write_all(b"\n")?;
 
         Ok(())
     }
 }
 
+/// Spooled entries keep their raw FILETIMEs, whatever the format of the output.
+fn spooled_timestamps() -> TimestampOptions {
+    TimestampOptions::default().with_format(TimestampFormat::Filetime)
+}
+
+/// An entry which waits for its path (and, in JSON, its other derived fields), see `DeferredOutput`.
+/// Its residual attributes and findings are taken before it is spooled, since entries read back have no record.
+#[derive(Serialize, Deserialize)]
+struct SpooledEntry {
+    carved_offset: Option<u64>,
+    entry: MftEntry,
+    residual_attributes: Option<Vec<ResidualAttribute>>,
+    timestomp_findings: Vec<TimestompFinding>,
+}
+
+/// An entry, with the version of its schema (see `mft::schema`), the offset it was carved from (see `carve`)
+/// and its derived fields (unless `--raw`),
+/// and followed by the attributes carved from its slack (see `--slack`) and its timestomping findings.
+#[derive(Serialize)]
+struct JsonEntry<'a> {
+    schema_version: u32,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    carved_offset: Option<u64>,
+    #[serde(flatten)]
+    derived: Option<DerivedFields>,
+    #[serde(flatten)]
+    entry: &'a MftEntry,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    residual_attributes: Option<Vec<ResidualAttribute>>,
+    /// See `mft::analysis`.
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    timestomp_findings: Vec<TimestompFinding>,
+}
+
+/// The record number and sequence number of the entry, which identify it in the `PathTable`.
+fn reference_of(entry: &MftEntry) -> MftReference {
+    MftReference::new(entry.header.record_number, entry.header.sequence)
+}
+
+/// Collects the resident data streams of the entry, with their names.
+fn resident_streams(entry: &MftEntry) -> Vec<(String, DataAttr)> {
+    entry
+        .iter_attributes()
+        .filter_map(|a| a.ok())
+        .filter_map(|a| {
+            if a.header.type_code == MftAttributeType::DATA {
+                // resident
+                let name = a.header.name.clone();
+                a.data.into_data().map(|data| (name, data))
+            } else {
+                None
+            }
+        })
+        .collect()
+}
+
+fn write_data_streams(
+    data_streams_dir: &Path,
+    path: &Path,
+    streams: Vec<(String, DataAttr)>,
+) -> Result<()> {
+    let sanitized_path = sanitized(&path.to_string_lossy());
+
+    for (i, (name, stream)) in streams.into_iter().enumerate() {
+        let orig_path_component: String = data_streams_dir
+            .join(&sanitized_path)
+            .to_string_lossy()
+            .to_string();
+
+        // Add some random bits to prevent collisions
+        let random: [u8; 6] = rand::random();
+        let rando_string: String = to_hex_string(&random);
+
+        let truncated: String = orig_path_component.chars().take(150).collect();
+        let data_stream_path = format!(
+            "{path}__{random}_{stream_number}_{stream_name}.dontrun",
+            path = truncated,
+            random = rando_string,
+            stream_number = i,
+            stream_name = name
+        );
+
+        if PathBuf::from(&data_stream_path).exists() {
+            return Err(anyhow!(
+                "Tried to override an existing stream {} already exists!\
+                 This is a bug, please report to github!",
+                data_stream_path
+            ));
+        }
+
+        let mut f = File::create(&data_stream_path)?;
+        f.write_all(stream.data())?;
+    }
+
+    Ok(())
+}
+
 fn to_hex_string(bytes: &[u8]) -> String {
     let len = bytes.len();
     // Each byte is represented by 2 ascii bytes.
@@ -452,13 +1164,44 @@ fn main() -> Result<()> {
         .version(env!("CARGO_PKG_VERSION"))
         .author("Omer B. <omerbenamram@gmail.com>")
         .about("Utility for parsing MFT snapshots")
-        .arg(Arg::new("INPUT").required(true))
+        .arg(
+            Arg::new("INPUT")
+                .required_unless_present("list-fields")
+                .help(indoc!("Path to an MFT file, or `-` to stream the MFT from stdin.
+                             gzip, zstd and zip files are unpacked automatically,
+                             use `archive.zip::path/to/$MFT` to select a member of a zip archive.
+                             NTFS volume and disk images are also accepted, including EWF (`.E01`), VHD, VHDX and split raw (`.001`) images.")),
+        )
+        .arg(
+            Arg::new("entry-size")
+                .long("entry-size")
+                .action(ArgAction::Set)
+                .value_parser(clap::value_parser!(u32))
+                .help(indoc!("The size of each MFT entry, in bytes, when streaming from stdin.
+                             If not given, it is taken from the header of the first entry.")),
+        )
+        .arg(
+            Arg::new("partition")
+                .long("partition")
+                .short('p')
+                .action(ArgAction::Set)
+                .value_parser(clap::value_parser!(usize))
+                .help(indoc!("For whole-disk images, the index of the NTFS partition to read (see `--list-partitions`).
+                             Not needed if the disk has a single NTFS partition.")),
+        )
+        .arg(
+            Arg::new("list-partitions")
+                .long("list-partitions")
+                .action(ArgAction::SetTrue)
+                .help("Lists the partitions of a disk image, instead of dumping the MFT."),
+        )
         .arg(
             Arg::new("output-format")
+                .global(true)
                 .short('o')
                 .long("output-format")
                 .action(ArgAction::Set)
-                .value_parser(clap::builder::PossibleValuesParser::new(["csv", "json", "jsonl"]))
+                .value_parser(clap::builder::PossibleValuesParser::new(["csv", "json", "jsonl", "bodyfile"]))
                 .default_value("json")
                 .help("Output format."),
         )
@@ -471,6 +1214,7 @@ fn main() -> Result<()> {
         )
         .arg(
             Arg::new("output-target")
+                .global(true)
                 .long("output")
                 .short('f')
                 .action(ArgAction::Set)
@@ -480,6 +1224,7 @@ fn main() -> Result<()> {
         )
         .arg(
             Arg::new("data-streams-target")
+                .global(true)
                 .long("extract-resident-streams")
                 .short('e')
                 .action(ArgAction::Set)
@@ -487,13 +1232,104 @@ fn main() -> Result<()> {
                              Resident streams will be named like - `{path}__<random_bytes>_{stream_number}_{stream_name}.dontrun`
                              random is added to prevent collisions.")),
         )
+        .arg(
+            Arg::new("errors-target")
+                .global(true)
+                .long("errors")
+                .action(ArgAction::Set)
+                .help(indoc!("Writes a report of the errors found while parsing to the given file, one JSON object per line.
+                             Each error has the entry number, and the offset and type of the attribute where relevant.")),
+        )
+        .arg(
+            Arg::new("fixup-policy")
+                .global(true)
+                .long("fixup-policy")
+                .action(ArgAction::Set)
+                .value_parser(clap::builder::PossibleValuesParser::new(["strict", "lenient", "preserve"]))
+                .default_value("lenient")
+                .help(indoc!("What to do with torn records, whose fixup values don't match the update sequence number.
+                             `strict` reports them as errors, `lenient` reports them as anomalies of the entry,
+                             and `preserve` also adds the record as it was read to the JSON output, as `original_data`.")),
+        )
+        .arg(
+            Arg::new("fixup-stride")
+                .global(true)
+                .long("fixup-stride")
+                .action(ArgAction::Set)
+                .value_parser(clap::value_parser!(usize))
+                .default_value("512")
+                .help("The size of the strides protected by fixups, in bytes."),
+        )
+        .arg(
+            Arg::new("recover")
+                .global(true)
+                .long("recover")
+                .action(ArgAction::SetTrue)
+                .help(indoc!("Attempts to parse damaged records, such as `BAAD` records and records with broken headers.
+                             Recovered entries are marked with `\"recovered\": true`.")),
+        )
+        .arg(
+            Arg::new("slack")
+                .global(true)
+                .long("slack")
+                .action(ArgAction::SetTrue)
+                .help(indoc!("Carves attributes left over from earlier uses of each record from its slack,
+                             and adds them to the JSON output as `residual_attributes`.")),
+        )
+        .arg(
+            Arg::new("fields")
+                .global(true)
+                .long("fields")
+                .action(ArgAction::Set)
+                .help(indoc!("Writes only the given fields of entries, in the given order, for example `entry_id,full_path,si_created`.
+                             Applies to CSV columns and to the keys of JSON objects, see `--list-fields` for the field catalog.")),
+        )
+        .arg(
+            Arg::new("list-fields")
+                .long("list-fields")
+                .action(ArgAction::SetTrue)
+                .help("Lists the fields which can be selected with `--fields`, instead of dumping the MFT."),
+        )
+        .arg(
+            Arg::new("timestamp-format")
+                .global(true)
+                .long("timestamp-format")
+                .action(ArgAction::Set)
+                .value_parser(clap::builder::PossibleValuesParser::new(["iso8601", "epoch", "epoch-fraction", "filetime"]))
+                .default_value("iso8601")
+                .help(indoc!("The format of timestamps in the JSON, CSV and timeline outputs.
+                             `epoch` is in whole seconds, `epoch-fraction` has 7 fractional digits, and `filetime` is the raw value.
+                             Timestamps which were never set are empty, and out of range ones are `{\"out_of_range\": <raw value>}`
+                             in JSON, or `Out of range (0x<raw value>)` in CSV and timelines.")),
+        )
+        .arg(
+            Arg::new("timezone")
+                .global(true)
+                .long("timezone")
+                .action(ArgAction::Set)
+                .allow_hyphen_values(true)
+                .default_value("UTC")
+                .help(indoc!("The time zone of `iso8601` timestamps: `UTC`, a fixed offset such as `+02:00`,
+                             or an IANA time zone such as `Europe/Berlin`.")),
+        )
+        .arg(
+            Arg::new("raw")
+                .global(true)
+                .long("raw")
+                .action(ArgAction::SetTrue)
+                .help(indoc!("Writes JSON entries as they are parsed, without the fields derived from them and the rest of the MFT
+                             (`full_path`, `hard_link_paths`, `is_deleted`, `is_directory`, `file_size` and `alternate_data_streams`).
+                             When streaming from stdin, entries are then written as they are read, instead of once all the paths are known.")),
+        )
         .arg(
             Arg::new("no-confirm-overwrite")
+                .global(true)
                 .long("no-confirm-overwrite")
                 .action(ArgAction::SetTrue)
                 .help(indoc!("When set, will not ask for confirmation before overwriting files, useful for automation")),
         )
         .arg(Arg::new("verbose")
+            .global(true)
             .short('v')
             .action(ArgAction::Count)
             .help(indoc!(r#"
@@ -505,11 +1341,70 @@ fn main() -> Result<()> {
         )
         .arg(
             Arg::new("backtraces")
+                .global(true)
                 .long("backtraces")
                 .action(ArgAction::SetTrue)
                 .help("If set, a backtrace will be printed with some errors if available"))
+        .subcommand(
+            clap::Command::new("carve")
+                .about("Carves MFT records from any file, such as a disk image, unallocated space, a pagefile or a memory dump")
+                .arg(
+                    Arg::new("INPUT")
+                        .required(true)
+                        .help("Path to the file to carve, EWF, VHD, VHDX and split raw images are read as the disk they contain."),
+                )
+                .arg(
+                    Arg::new("sector-size")
+                        .long("sector-size")
+                        .action(ArgAction::Set)
+                        .value_parser(clap::value_parser!(usize))
+                        .default_value("512")
+                        .help("Records are looked for at multiples of the sector size."),
+                ),
+        )
+        .subcommand(
+            clap::Command::new("timeline")
+                .about("Writes every timestamp in the MFT as a separate event, sorted by time")
+                .arg(
+                    Arg::new("INPUT")
+                        .required(true)
+                        .help("Path to the MFT (or image), or `-` to stream it from stdin."),
+                )
+                .arg(
+                    Arg::new("from")
+                        .long("from")
+                        .action(ArgAction::Set)
+                        .value_parser(parse_time)
+                        .help("Only events at or after this time (RFC 3339, or a date in UTC such as `2020-01-31`)."),
+                )
+                .arg(
+                    Arg::new("to")
+                        .long("to")
+                        .action(ArgAction::Set)
+                        .value_parser(parse_time)
+                        .help("Only events before this time (RFC 3339, or a date in UTC such as `2020-01-31`)."),
+                )
+                .arg(
+                    Arg::new("max-events-in-memory")
+                        .long("max-events-in-memory")
+                        .action(ArgAction::Set)
+                        .value_parser(clap::value_parser!(usize))
+                        .default_value("1000000")
+                        .help("Events are sorted in runs of this size, which are spilled to temporary files."),
+                ),
+        )
+        .args_conflicts_with_subcommands(true)
+        .subcommand_negates_reqs(true)
         .get_matches();
 
+    if matches.get_flag("list-fields") {
+        let mut out = io::stdout().lock();
+        for field in FIELDS {
+            writeln!(out, "{:<30} {}", field.name, field.description)?;
+        }
+        return Ok(());
+    }
+
     let mut app = MftDump::from_cli_matches(&matches).context("Failed setting up the app")?;
     app.run().context("A runtime error has occurred")?;
 
@@ -0,0 +1,140 @@
+//! The Sleuth Kit's body file format, which `mactime` turns into a timeline.
+//! See <https://wiki.sleuthkit.org/index.php?title=Body_file>.
+use crate::attribute::header::ResidentialHeader;
+use crate::attribute::{MftAttributeContent, MftAttributeType};
+use crate::{FileTime, MftEntry, MftParser};
+
+use std::fmt;
+use std::io::{Read, Seek};
+use std::path::PathBuf;
+
+/// One line of a body file, for either the `$STANDARD_INFORMATION` or a `$FILE_NAME` attribute of an entry.
+///
+/// Lines are formatted (with `Display`) as `MD5|name|inode|mode|UID|GID|size|atime|mtime|ctime|crtime`,
+/// like `fls -m` does: the inode is `entry-type-instance`, `$FILE_NAME` lines are suffixed with ` ($FILE_NAME)`,
+/// and times are in seconds since the Unix epoch (ctime being the time the MFT entry was modified).
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BodyfileLine {
+    pub entry_id: u64,
+    /// The type code and instance of the attribute the times are from.
+    pub attribute_type: u32,
+    pub attribute_instance: u16,
+    /// The full path of the entry.
+    pub full_path: PathBuf,
+    /// The name in the `$FILE_NAME` attribute, `None` for the `$STANDARD_INFORMATION` line.
+    pub file_name: Option<String>,
+    pub is_a_directory: bool,
+    /// The size of the file, from the unnamed $DATA attribute.
+    pub file_size: u64,
+    pub accessed: FileTime,
+    pub modified: FileTime,
+    pub mft_modified: FileTime,
+    pub created: FileTime,
+}
+
+impl BodyfileLine {
+    pub fn from_entry(
+        entry: &MftEntry,
+        parser: &mut MftParser<impl Read + Seek>,
+    ) -> Vec<BodyfileLine> {
+        let full_path = parser
+            .get_full_path_for_entry(entry)
+            .expect("I/O Err")
+            .unwrap_or_default();
+
+        Self::from_entry_and_path(entry, full_path)
+    }
+
+    /// Like `from_entry`, but with a path which was already resolved by the caller
+    /// (for example, with a `PathTable` when streaming).
+    pub fn from_entry_and_path(entry: &MftEntry, full_path: PathBuf) -> Vec<BodyfileLine> {
+        let file_size = entry
+            .iter_attributes()
+            .filter_map(Result::ok)
+            .find(|a| a.header.type_code == MftAttributeType::DATA && a.header.name.is_empty())
+            .map(|a| match &a.header.residential_header {
+                ResidentialHeader::Resident(r) => u64::from(r.data_size),
+                ResidentialHeader::NonResident(nr) => nr.file_size,
+            })
+            .unwrap_or(0);
+
+        let mut lines = vec![];
+        for attribute in entry.iter_attributes().filter_map(Result::ok) {
+            let (file_name, [accessed, modified, mft_modified, created]) = match &attribute.data {
+                MftAttributeContent::AttrX10(info) => (
+                    None,
+                    [
+                        info.accessed,
+                        info.modified,
+                        info.mft_modified,
+                        info.created,
+                    ],
+                ),
+                MftAttributeContent::AttrX30(name) => (
+                    Some(name.name.clone()),
+                    [
+                        name.accessed,
+                        name.modified,
+                        name.mft_modified,
+                        name.created,
+                    ],
+                ),
+                _ => continue,
+            };
+
+            lines.push(BodyfileLine {
+                entry_id: entry.header.record_number,
+                attribute_type: attribute.header.type_code as u32,
+                attribute_instance: attribute.header.instance,
+                full_path: full_path.clone(),
+                file_name,
+                is_a_directory: entry.is_dir(),
+                file_size,
+                accessed,
+                modified,
+                mft_modified,
+                created,
+            });
+        }
+
+        lines
+    }
+
+    /// The name column: the full path, with the name of the `$FILE_NAME` attribute (which may be a DOS name or another hard link).
+    pub fn name(&self) -> String {
+        match &self.file_name {
+            Some(file_name) => format!(
+                "{} ($FILE_NAME)",
+                self.full_path.with_file_name(file_name).to_string_lossy()
+            ),
+            None => self.full_path.to_string_lossy().into_owned(),
+        }
+    }
+}
+
+impl fmt::Display for BodyfileLine {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        // Times before the Unix epoch, unset and out of range times are 0, like `fls` does.
+        let seconds = |time: &FileTime| time.to_datetime().map_or(0, |t| t.timestamp().max(0));
+        let mode = if self.is_a_directory {
+            "d/drwxrwxrwx"
+        } else {
+            "r/rrwxrwxrwx"
+        };
+
+        write!(
+            f,
+            "0|{}|{}-{}-{}|{}|0|0|{}|{}|{}|{}|{}",
+            self.name(),
+            self.entry_id,
+            self.attribute_type,
+            self.attribute_instance,
+            mode,
+            self.file_size,
+            seconds(&self.accessed),
+            seconds(&self.modified),
+            seconds(&self.mft_modified),
+            seconds(&self.created),
+        )
+    }
+}
@@ -0,0 +1,255 @@
+//! Carving MFT records out of arbitrary data, such as disk images, unallocated space, pagefiles or memory dumps.
+use crate::entry::{
+    FixupOptions, MftEntry, BAAD_HEADER, ENTRY_HEADER_SIZE, FILE_HEADER, MAX_ENTRY_SIZE,
+};
+use crate::err::Result;
+use crate::limits::ParseLimits;
+
+use log::{debug, trace};
+use serde::{Deserialize, Serialize};
+
+use std::io::{self, Read};
+
+/// How much is read from the source at a time.
+const READ_SIZE: usize = 1024 * 1024;
+/// Headers with an update sequence array before this offset are not NTFS records.
+const MIN_USA_OFFSET: usize = 0x28;
+/// Where NTFS 3.1 records store their own record number.
+const RECORD_NUMBER_OFFSET: usize = 0x2C;
+
+/// An entry carved from the source, and the offset where its record was found.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct CarvedEntry {
+    #[serde(rename = "carved_offset")]
+    pub offset: u64,
+    #[serde(flatten)]
+    pub entry: MftEntry,
+}
+
+/// Scans any `Read` for MFT records, regardless of the file system structures around them.
+///
+/// Candidates are `FILE` and `BAAD` signatures at sector boundaries, whose update sequence array is
+/// consistent with the size of the record (and, for `FILE` records, with the end of every stride).
+/// Entries are numbered by the record number stored in NTFS 3.1 headers, or 0 for older records.
+pub struct MftCarver<T: Read> {
+    data: T,
+    /// A window over the source, starting at `buffer_offset`.
+    buffer: Vec<u8>,
+    buffer_offset: u64,
+    /// Where the next candidate is, in `buffer`.
+    position: usize,
+    eof: bool,
+    sector_size: usize,
+    fixup: FixupOptions,
+    limits: ParseLimits,
+    recover: bool,
+}
+
+impl<T: Read> MftCarver<T> {
+    pub fn new(data: T) -> Self {
+        MftCarver {
+            data,
+            buffer: Vec::with_capacity(READ_SIZE),
+            buffer_offset: 0,
+            position: 0,
+            eof: false,
+            sector_size: 512,
+            fixup: FixupOptions::default(),
+            limits: ParseLimits::default(),
+            recover: false,
+        }
+    }
+
+    /// Sets the alignment of the candidates, 512 by default.
+    /// Records directly following a carved record are found regardless of the sector size.
+    pub fn with_sector_size(mut self, sector_size: usize) -> Self {
+        self.sector_size = sector_size.max(1);
+        self
+    }
+
+    /// Sets the fixup policy used when parsing records, and the stride used when validating them.
+    pub fn with_fixup_options(mut self, fixup: FixupOptions) -> Self {
+        self.fixup = fixup;
+        self
+    }
+
+    /// Sets the limits used when parsing entries.
+    pub fn with_limits(mut self, limits: ParseLimits) -> Self {
+        self.limits = limits;
+        self
+    }
+
+    /// When set, carved records are parsed with `MftEntry::recover`.
+    pub fn with_recovery(mut self, recover: bool) -> Self {
+        self.recover = recover;
+        self
+    }
+
+    /// Carves the next record from the source.
+    /// Returns `None` once the source is exhausted.
+    pub fn next_entry(&mut self) -> Option<Result<CarvedEntry>> {
+        loop {
+            if !self.eof && self.buffer.len().saturating_sub(self.position) < MAX_ENTRY_SIZE {
+                if let Err(e) = self.fill() {
+                    self.eof = true;
+                    return Some(Err(e.into()));
+                }
+            }
+
+            let window = self.buffer.get(self.position..).unwrap_or_default();
+            if window.is_empty() {
+                return None;
+            }
+
+            let offset = self.buffer_offset + self.position as u64;
+
+            let record_size = match record_size(window, self.fixup.stride) {
+                Some(record_size) => record_size,
+                None => {
+                    // Move on to the next sector boundary.
+                    let sector_size = self.sector_size as u64;
+                    self.position += (sector_size - offset % sector_size) as usize;
+                    continue;
+                }
+            };
+
+            debug!("Carved a record at offset {}", offset);
+            let record = window[..record_size].to_vec();
+            self.position += record_size;
+
+            let entry_number = record_number(&record);
+            let entry = if self.recover {
+                MftEntry::recover(record, entry_number, self.fixup)
+            } else {
+                MftEntry::from_buffer_with_fixup(record, entry_number, self.fixup)
+            };
+
+            return Some(entry.map(|entry| CarvedEntry {
+                offset,
+                entry: entry.with_limits(self.limits),
+            }));
+        }
+    }
+
+    /// Iterates over all the remaining records in the source.
+    pub fn iter_entries(&mut self) -> impl Iterator<Item = Result<CarvedEntry>> + '_ {
+        std::iter::from_fn(move || self.next_entry())
+    }
+
+    /// Drops the scanned part of the window, and reads until it is full (or the source is exhausted).
+    fn fill(&mut self) -> io::Result<()> {
+        let scanned = self.position.min(self.buffer.len());
+        self.buffer.drain(..scanned);
+        self.buffer_offset += scanned as u64;
+        self.position -= scanned;
+
+        while !self.eof && self.buffer.len() < READ_SIZE {
+            let filled = self.buffer.len();
+            self.buffer.resize(READ_SIZE, 0);
+
+            match self.data.read(&mut self.buffer[filled..]) {
+                Ok(n) => {
+                    self.buffer.truncate(filled + n);
+                    self.eof = n == 0;
+                }
+                Err(e) if e.kind() == io::ErrorKind::Interrupted => self.buffer.truncate(filled),
+                Err(e) => {
+                    self.buffer.truncate(filled);
+                    return Err(e);
+                }
+            }
+        }
+
+        trace!(
+            "Scanning {} bytes from offset {}",
+            self.buffer.len(),
+            self.buffer_offset
+        );
+
+        Ok(())
+    }
+}
+
+impl<T: Read> Iterator for MftCarver<T> {
+    type Item = Result<CarvedEntry>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.next_entry()
+    }
+}
+
+/// The size of the record at the start of `data`, if it looks like an MFT record.
+fn record_size(data: &[u8], stride: usize) -> Option<usize> {
+    if data.len() < ENTRY_HEADER_SIZE || (&data[..4] != FILE_HEADER && &data[..4] != BAAD_HEADER) {
+        return None;
+    }
+
+    let u16_at = |offset: usize| usize::from(u16::from_le_bytes([data[offset], data[offset + 1]]));
+    let u32_at = |offset: usize| {
+        u32::from_le_bytes([
+            data[offset],
+            data[offset + 1],
+            data[offset + 2],
+            data[offset + 3],
+        ]) as usize
+    };
+
+    let usa_offset = u16_at(4);
+    let usa_size = u16_at(6);
+    let first_attribute_offset = u16_at(20);
+    let used_entry_size = u32_at(24);
+    let total_entry_size = u32_at(28);
+
+    if !total_entry_size.is_power_of_two()
+        || !(512..=MAX_ENTRY_SIZE).contains(&total_entry_size)
+        || total_entry_size > data.len()
+    {
+        return None;
+    }
+
+    // One update sequence number, and one fixup for every stride of the record.
+    if stride < 2 || usa_size < 2 || (usa_size - 1).checked_mul(stride) != Some(total_entry_size) {
+        return None;
+    }
+
+    // The array lies between the fixed header and the first attribute.
+    if usa_offset < MIN_USA_OFFSET
+        || usa_offset % 2 != 0
+        || usa_offset + usa_size * 2 > first_attribute_offset
+        || first_attribute_offset % 8 != 0
+        || first_attribute_offset >= used_entry_size
+        || used_entry_size > total_entry_size
+    {
+        return None;
+    }
+
+    // chkdsk marks records whose fixups didn't match as BAAD, intact records match at every stride.
+    if &data[..4] == FILE_HEADER {
+        let update_sequence = &data[usa_offset..usa_offset + 2];
+        let torn = (stride..=total_entry_size)
+            .step_by(stride)
+            .any(|stride_end| &data[stride_end - 2..stride_end] != update_sequence);
+
+        if torn {
+            return None;
+        }
+    }
+
+    Some(total_entry_size)
+}
+
+/// The record number stored in the header of NTFS 3.1 records, 0 if it's not there.
+fn record_number(record: &[u8]) -> u64 {
+    let usa_offset = u16::from_le_bytes([record[4], record[5]]) as usize;
+
+    if usa_offset < RECORD_NUMBER_OFFSET + 4 {
+        return 0;
+    }
+
+    u64::from(u32::from_le_bytes([
+        record[RECORD_NUMBER_OFFSET],
+        record[RECORD_NUMBER_OFFSET + 1],
+        record[RECORD_NUMBER_OFFSET + 2],
+        record[RECORD_NUMBER_OFFSET + 3],
+    ]))
+}
@@ -0,0 +1,231 @@
+//! Transparent decompression of MFTs collected inside compressed files or archives.
+//!
+//! Compressed input is detected by its magic bytes, and spooled to an anonymous temporary file,
+//! since the parser needs to seek.
+use crate::err::{Error, Result};
+
+use log::debug;
+
+use std::fs::File;
+use std::io::{self, BufRead, BufReader, Read, Seek};
+use std::path::{Path, PathBuf};
+
+/// Separates an archive path from the path of a member inside it, for example `triage.zip::C/$MFT`.
+pub const ARCHIVE_MEMBER_SEPARATOR: &str = "::";
+
+/// Compressed files can contain other compressed files (`$MFT.gz` inside a zip),
+/// but there is no good reason for more than a few layers.
+const MAX_NESTED_CONTAINERS: usize = 4;
+
+/// The default limit of `open` on the size of unpacked data, which is spooled to disk.
+/// Far larger than any MFT, but small enough that a decompression bomb cannot fill the disk.
+pub const DEFAULT_MAX_UNPACKED_SIZE: u64 = 32 * 1024 * 1024 * 1024;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ContainerFormat {
+    Gzip,
+    Zstd,
+    Zip,
+}
+
+impl ContainerFormat {
+    /// Detects the container format from the first bytes of a file.
+    /// Returns `None` for anything which isn't compressed.
+    pub fn detect(magic: &[u8]) -> Option<Self> {
+        if magic.starts_with(&[0x1F, 0x8B]) {
+            Some(ContainerFormat::Gzip)
+        } else if magic.starts_with(&[0x28, 0xB5, 0x2F, 0xFD]) {
+            Some(ContainerFormat::Zstd)
+        } else if magic.starts_with(b"PK\x03\x04") {
+            Some(ContainerFormat::Zip)
+        } else {
+            None
+        }
+    }
+}
+
+/// Opens a file for parsing, unpacking it first if it is compressed.
+///
+/// Members of zip archives are selected with `archive.zip::path/inside/archive`.
+/// If no member is given, the archive must contain a single file, or a single `$MFT`.
+///
+/// Unpacked data is limited to `DEFAULT_MAX_UNPACKED_SIZE`, see `open_with_limit`.
+pub fn open(path: impl AsRef<Path>) -> Result<File> {
+    open_with_limit(path, DEFAULT_MAX_UNPACKED_SIZE)
+}
+
+/// Like `open`, but fails with `Error::FailedToReadArchive` if any layer of the file
+/// unpacks to more than `max_unpacked_size` bytes.
+pub fn open_with_limit(path: impl AsRef<Path>, max_unpacked_size: u64) -> Result<File> {
+    let (path, member) = split_member_path(path.as_ref());
+
+    let mut file = File::open(&path).map_err(|e| Error::failed_to_open_file(&path, e))?;
+    let mut member = member.as_deref();
+
+    for _ in 0..MAX_NESTED_CONTAINERS {
+        let format = match ContainerFormat::detect(&read_magic(&mut file)?) {
+            Some(format) => format,
+            None => break,
+        };
+
+        debug!("Unpacking {:?} container at {}", format, path.display());
+
+        file = match format {
+            ContainerFormat::Gzip => spool(
+                flate2::read::MultiGzDecoder::new(BufReader::new(file)),
+                &path,
+                max_unpacked_size,
+            )?,
+            ContainerFormat::Zstd => spool(
+                zstd_decoder(BufReader::new(file), &path)?,
+                &path,
+                max_unpacked_size,
+            )?,
+            // A member name only applies to the first archive.
+            ContainerFormat::Zip => {
+                unpack_zip_member(file, &path, member.take(), max_unpacked_size)?
+            }
+        };
+    }
+
+    if let Some(member) = member {
+        let detail = format!(
+            "`{}` is not an archive, cannot open `{}`",
+            path_name(&path),
+            member
+        );
+        return Err(Error::FailedToReadArchive { path, detail });
+    }
+
+    Ok(file)
+}
+
+/// Wraps a non-seekable stream with a decompressor, if the stream is compressed.
+/// Zip archives cannot be read from a stream, since their directory is at the end.
+pub fn decompress_stream<'a, R: Read + 'a>(reader: R) -> Result<Box<dyn Read + 'a>> {
+    let mut reader = BufReader::new(reader);
+
+    match ContainerFormat::detect(reader.fill_buf()?) {
+        None => Ok(Box::new(reader)),
+        Some(ContainerFormat::Gzip) => Ok(Box::new(flate2::read::MultiGzDecoder::new(reader))),
+        Some(ContainerFormat::Zstd) => Ok(Box::new(zstd_decoder(reader, Path::new("-"))?)),
+        Some(ContainerFormat::Zip) => Err(Error::FailedToReadArchive {
+            path: PathBuf::from("-"),
+            detail: "zip archives cannot be streamed, extract the MFT or pass the archive path"
+                .to_string(),
+        }),
+    }
+}
+
+/// Splits `archive.zip::member` into its parts.
+/// Paths which exist as-is are never split, so files with `::` in their names can still be opened.
+pub fn split_member_path(path: &Path) -> (PathBuf, Option<String>) {
+    if path.exists() {
+        return (path.to_path_buf(), None);
+    }
+
+    let as_str = path.to_string_lossy();
+    match as_str.rsplit_once(ARCHIVE_MEMBER_SEPARATOR) {
+        Some((archive, member)) if !member.is_empty() => {
+            (PathBuf::from(archive), Some(member.to_string()))
+        }
+        _ => (path.to_path_buf(), None),
+    }
+}
+
+fn read_magic(file: &mut File) -> Result<Vec<u8>> {
+    let mut magic = Vec::with_capacity(4);
+    file.by_ref().take(4).read_to_end(&mut magic)?;
+    file.rewind()?;
+
+    Ok(magic)
+}
+
+fn zstd_decoder<R: Read>(
+    reader: R,
+    path: &Path,
+) -> Result<ruzstd::StreamingDecoder<R, ruzstd::FrameDecoder>> {
+    ruzstd::StreamingDecoder::new(reader).map_err(|e| Error::FailedToReadArchive {
+        path: path.to_path_buf(),
+        detail: format!("invalid zstd frame: {}", e),
+    })
+}
+
+/// Copies the decompressed stream to an anonymous temporary file, which is deleted on drop.
+/// Streams larger than `max_size` are not copied any further.
+fn spool(reader: impl Read, path: &Path, max_size: u64) -> Result<File> {
+    let mut spooled = tempfile::tempfile()?;
+    let size = io::copy(&mut reader.take(max_size.saturating_add(1)), &mut spooled)?;
+
+    if size > max_size {
+        return Err(Error::FailedToReadArchive {
+            path: path.to_path_buf(),
+            detail: format!("unpacks to more than {} bytes", max_size),
+        });
+    }
+
+    spooled.rewind()?;
+
+    Ok(spooled)
+}
+
+fn unpack_zip_member(file: File, path: &Path, member: Option<&str>, max_size: u64) -> Result<File> {
+    let archive_error = |detail: String| Error::FailedToReadArchive {
+        path: path.to_path_buf(),
+        detail,
+    };
+
+    let mut archive =
+        zip::ZipArchive::new(BufReader::new(file)).map_err(|e| archive_error(e.to_string()))?;
+
+    let files: Vec<String> = archive
+        .file_names()
+        .filter(|name| !name.ends_with('/'))
+        .map(str::to_string)
+        .collect();
+
+    let name = match member {
+        // Windows style separators are common in triage collections.
+        Some(member) => {
+            let member = member.replace('\\', "/");
+            files
+                .iter()
+                .find(|name| name.trim_start_matches('/') == member.trim_start_matches('/'))
+                .cloned()
+                .ok_or_else(|| archive_error(format!("no member named `{}`", member)))?
+        }
+        None => {
+            let mfts: Vec<&String> = files
+                .iter()
+                .filter(|name| name.rsplit('/').next() == Some("$MFT"))
+                .collect();
+
+            match (files.as_slice(), mfts.as_slice()) {
+                ([only], _) => only.clone(),
+                (_, [mft]) => (*mft).clone(),
+                _ => {
+                    return Err(archive_error(format!(
+                        "select a member with `{}{}<member>`, available members: {}",
+                        path_name(path),
+                        ARCHIVE_MEMBER_SEPARATOR,
+                        files.join(", ")
+                    )))
+                }
+            }
+        }
+    };
+
+    debug!("Unpacking zip member {}", name);
+
+    let member = archive
+        .by_name(&name)
+        .map_err(|e| archive_error(e.to_string()))?;
+
+    spool(member, path, max_size)
+}
+
+fn path_name(path: &Path) -> String {
+    path.file_name()
+        .map(|name| name.to_string_lossy().to_string())
+        .unwrap_or_default()
+}
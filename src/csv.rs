@@ -1,12 +1,13 @@
-use crate::attribute::header::ResidentialHeader;
+use crate::analysis::{TimestompAnalyzer, TimestompFinding, TimestompRule};
+use crate::attribute::x30::{FileNameAttr, FileNamespace};
+use crate::attribute::{AttributeRecord, FileAttributeFlags, MftAttributeType};
+use crate::entry::{anomalies_of, EntryFlags};
+use crate::timestamp::FileTimeValue;
+use crate::{FileTime, MftAttribute, MftEntry, MftParser};
 
-use crate::attribute::{FileAttributeFlags, MftAttributeType};
-use crate::entry::EntryFlags;
-use crate::{MftAttribute, MftEntry, MftParser};
+use serde::{Serialize, Serializer};
 
-use serde::Serialize;
-
-use chrono::{DateTime, Utc};
+use std::fmt;
 use std::io::{Read, Seek};
 use std::path::PathBuf;
 
@@ -23,6 +24,7 @@ pub struct FlatMftEntryWithName {
     pub base_entry_sequence: u16,
 
     pub hard_link_count: u16,
+    #[serde(serialize_with = "flags_as_string")]
     pub flags: EntryFlags,
 
     /// The size of the file, in bytes.
@@ -40,19 +42,73 @@ pub struct FlatMftEntryWithName {
 
     /// Indicates whether the record has alternate data streams.
     pub has_alternate_data_streams: bool,
+    /// Every $DATA stream, as `name:size`, separated by `; `. The unnamed stream has an empty name.
+    pub data_streams: String,
 
     /// All of these fields are present for entries that have an 0x10 attribute.
+    #[serde(serialize_with = "optional_flags_as_string")]
     pub standard_info_flags: Option<FileAttributeFlags>,
-    pub standard_info_last_modified: Option<DateTime<Utc>>,
-    pub standard_info_last_access: Option<DateTime<Utc>>,
-    pub standard_info_created: Option<DateTime<Utc>>,
+    #[serde(serialize_with = "optional_time_as_string")]
+    pub standard_info_last_modified: Option<FileTime>,
+    #[serde(serialize_with = "optional_time_as_string")]
+    pub standard_info_last_access: Option<FileTime>,
+    #[serde(serialize_with = "optional_time_as_string")]
+    pub standard_info_created: Option<FileTime>,
+    #[serde(serialize_with = "optional_time_as_string")]
+    pub standard_info_mft_modified: Option<FileTime>,
+    pub standard_info_usn: Option<u64>,
+    pub standard_info_security_id: Option<u32>,
+    pub standard_info_owner_id: Option<u32>,
     /// All of these fields are present for entries that have an 0x30 attribute.
+    /// They are taken from the best name (see `MftEntry::find_best_name_attribute`).
+    pub file_name: Option<String>,
+    pub file_name_namespace: Option<FileNamespace>,
+    #[serde(serialize_with = "optional_flags_as_string")]
     pub file_name_flags: Option<FileAttributeFlags>,
-    pub file_name_last_modified: Option<DateTime<Utc>>,
-    pub file_name_last_access: Option<DateTime<Utc>>,
-    pub file_name_created: Option<DateTime<Utc>>,
+    #[serde(serialize_with = "optional_time_as_string")]
+    pub file_name_last_modified: Option<FileTime>,
+    #[serde(serialize_with = "optional_time_as_string")]
+    pub file_name_last_access: Option<FileTime>,
+    #[serde(serialize_with = "optional_time_as_string")]
+    pub file_name_created: Option<FileTime>,
+    #[serde(serialize_with = "optional_time_as_string")]
+    pub file_name_mft_modified: Option<FileTime>,
+    pub file_name_logical_size: Option<u64>,
+    pub file_name_physical_size: Option<u64>,
+    pub parent_entry_id: Option<u64>,
+    pub parent_entry_sequence: Option<u16>,
+    /// All of these fields are present for entries that have a separate DOS (8.3) name.
+    pub short_file_name: Option<String>,
+    #[serde(serialize_with = "optional_time_as_string")]
+    pub short_file_name_last_modified: Option<FileTime>,
+    #[serde(serialize_with = "optional_time_as_string")]
+    pub short_file_name_last_access: Option<FileTime>,
+    #[serde(serialize_with = "optional_time_as_string")]
+    pub short_file_name_created: Option<FileTime>,
+    #[serde(serialize_with = "optional_time_as_string")]
+    pub short_file_name_mft_modified: Option<FileTime>,
+    /// Every 0x30 attribute (including hard links), as `name (namespace, parent entry-sequence)`, separated by `; `.
+    pub file_names: String,
 
     pub full_path: PathBuf,
+
+    /// The number of anomalies found in the entry (see `MftEntry::attributes_with_anomalies`).
+    pub anomaly_count: usize,
+    /// A summary of the attributes which failed to parse, separated by `; `.
+    /// Empty if all the attributes were parsed.
+    pub attribute_errors: String,
+
+    /// Timestomping heuristics (see `mft::analysis`), set when the entry broke the rule.
+    pub si_created_before_fn_created: bool,
+    pub si_zero_fraction: bool,
+    pub si_modified_before_created: bool,
+    /// Only checked when the lifetime of the volume is known, see `with_findings`.
+    pub outside_volume_lifetime: bool,
+
+    /// For carved entries, the offset of the record in the source (see `mft::carve`).
+    /// The column is left out for regular entries.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub carved_offset: Option<u64>,
 }
 
 impl FlatMftEntryWithName {
@@ -60,19 +116,46 @@ impl FlatMftEntryWithName {
         entry: &MftEntry,
         parser: &mut MftParser<impl Read + Seek>,
     ) -> FlatMftEntryWithName {
-        let entry_attributes: Vec<MftAttribute> = entry
-            .iter_attributes_matching(Some(vec![
-                MftAttributeType::FileName,
-                MftAttributeType::StandardInformation,
-                MftAttributeType::DATA,
-            ]))
-            .filter_map(Result::ok)
+        let full_path = parser
+            .get_full_path_for_entry(entry)
+            .expect("I/O Err")
+            .unwrap_or_default();
+
+        Self::from_entry_and_path(entry, full_path)
+    }
+
+    /// Like `from_entry`, but with a path which was already resolved by the caller
+    /// (for example, with a `PathTable` when streaming).
+    pub fn from_entry_and_path(entry: &MftEntry, full_path: PathBuf) -> FlatMftEntryWithName {
+        let records = entry.attribute_records();
+        let anomalies = anomalies_of(&entry.anomalies, &records);
+        let entry_attributes: Vec<&MftAttribute> = records
+            .iter()
+            .filter_map(AttributeRecord::attribute)
+            .collect();
+        let attribute_errors: Vec<String> = records
+            .iter()
+            .filter_map(AttributeRecord::error)
+            .map(|error| {
+                let attribute_type = match (&error.attribute_type, error.type_code) {
+                    (Some(attribute_type), _) => format!("{:?}", attribute_type),
+                    (None, Some(type_code)) => format!("0x{:X}", type_code),
+                    (None, None) => "Unknown".to_string(),
+                };
+
+                format!("{} at {}: {}", attribute_type, error.offset, error.error)
+            })
             .collect();
 
-        let file_name = entry_attributes
+        let file_names: Vec<FileNameAttr> = entry_attributes
             .iter()
-            .find(|a| a.header.type_code == MftAttributeType::FileName)
-            .and_then(|a| a.data.clone().into_file_name());
+            .filter(|a| a.header.type_code == MftAttributeType::FileName)
+            .filter_map(|a| a.data.clone().into_file_name())
+            .collect();
+        let file_name = entry.find_best_name_attribute();
+        let short_file_name = file_names
+            .iter()
+            .find(|file_name| file_name.namespace == FileNamespace::DOS);
 
         let standard_info = entry_attributes
             .iter()
@@ -83,18 +166,18 @@ impl FlatMftEntryWithName {
             .iter()
             .find(|a| a.header.type_code == MftAttributeType::DATA);
 
-        let file_size = match data_attr {
-            Some(attr) => match &attr.header.residential_header {
-                ResidentialHeader::Resident(r) => u64::from(r.data_size),
-                ResidentialHeader::NonResident(nr) => nr.file_size,
-            },
-            _ => 0,
-        };
+        let file_size = data_attr.map_or(0, |attr| attr.header.data_size());
 
         let has_ads = entry_attributes
             .iter()
             .any(|a| a.header.type_code == MftAttributeType::DATA && !a.header.name.is_empty());
 
+        let data_streams: Vec<String> = entry_attributes
+            .iter()
+            .filter(|a| a.header.type_code == MftAttributeType::DATA)
+            .map(|a| format!("{}:{}", a.header.name, a.header.data_size()))
+            .collect();
+
         FlatMftEntryWithName {
             entry_id: entry.header.record_number,
             signature: String::from_utf8(entry.header.signature.to_ascii_uppercase())
@@ -109,19 +192,102 @@ impl FlatMftEntryWithName {
             is_a_directory: entry.is_dir(),
             is_deleted: !entry.header.flags.contains(EntryFlags::ALLOCATED),
             has_alternate_data_streams: has_ads,
+            data_streams: data_streams.join("; "),
             standard_info_flags: standard_info.as_ref().map(|i| i.file_flags),
             standard_info_last_modified: standard_info.as_ref().map(|i| i.modified),
             standard_info_last_access: standard_info.as_ref().map(|i| i.accessed),
             standard_info_created: standard_info.as_ref().map(|i| i.created),
+            standard_info_mft_modified: standard_info.as_ref().map(|i| i.mft_modified),
+            standard_info_usn: standard_info.as_ref().map(|i| i.usn),
+            standard_info_security_id: standard_info.as_ref().map(|i| i.security_id),
+            standard_info_owner_id: standard_info.as_ref().map(|i| i.owner_id),
+            file_name: file_name.as_ref().map(|i| i.name.clone()),
+            file_name_namespace: file_name.as_ref().map(|i| i.namespace.clone()),
             file_name_flags: file_name.as_ref().map(|i| i.flags),
             file_name_last_modified: file_name.as_ref().map(|i| i.modified),
             file_name_last_access: file_name.as_ref().map(|i| i.accessed),
             file_name_created: file_name.as_ref().map(|i| i.created),
+            file_name_mft_modified: file_name.as_ref().map(|i| i.mft_modified),
+            file_name_logical_size: file_name.as_ref().map(|i| i.logical_size),
+            file_name_physical_size: file_name.as_ref().map(|i| i.physical_size),
+            parent_entry_id: file_name.as_ref().map(|i| i.parent.entry),
+            parent_entry_sequence: file_name.as_ref().map(|i| i.parent.sequence),
+            short_file_name: short_file_name.map(|i| i.name.clone()),
+            short_file_name_last_modified: short_file_name.map(|i| i.modified),
+            short_file_name_last_access: short_file_name.map(|i| i.accessed),
+            short_file_name_created: short_file_name.map(|i| i.created),
+            short_file_name_mft_modified: short_file_name.map(|i| i.mft_modified),
+            file_names: file_names
+                .iter()
+                .map(|i| {
+                    format!(
+                        "{} ({:?}, parent {}-{})",
+                        i.name, i.namespace, i.parent.entry, i.parent.sequence
+                    )
+                })
+                .collect::<Vec<_>>()
+                .join("; "),
             file_size,
-            full_path: parser
-                .get_full_path_for_entry(entry)
-                .expect("I/O Err")
-                .unwrap_or_default(),
+            full_path,
+            anomaly_count: anomalies.len(),
+            attribute_errors: attribute_errors.join("; "),
+            si_created_before_fn_created: false,
+            si_zero_fraction: false,
+            si_modified_before_created: false,
+            outside_volume_lifetime: false,
+            carved_offset: None,
         }
+        .with_findings(&TimestompAnalyzer::new().analyze(entry))
+    }
+
+    /// Sets the timestomping columns from the findings of a `TimestompAnalyzer`,
+    /// such as one which knows the lifetime of the volume.
+    pub fn with_findings(mut self, findings: &[TimestompFinding]) -> Self {
+        let broke = |rule| findings.iter().any(|finding| finding.rule == rule);
+
+        self.si_created_before_fn_created = broke(TimestompRule::SiCreatedBeforeFnCreated);
+        self.si_zero_fraction = broke(TimestompRule::SiZeroFraction);
+        self.si_modified_before_created = broke(TimestompRule::SiModifiedBeforeCreated);
+        self.outside_volume_lifetime = broke(TimestompRule::OutsideVolumeLifetime);
+        self
+    }
+}
+
+/// Flags are written like `ALLOCATED | INDEX_PRESENT` in CSV, which can't have their names and value in a cell.
+pub(crate) fn flags_as_string<S: Serializer>(
+    flags: &impl fmt::Debug,
+    serializer: S,
+) -> Result<S::Ok, S::Error> {
+    serializer.collect_str(&format_args!("{:?}", flags))
+}
+
+/// Out of range times are written as `Out of range (0x...)`, since CSV can't have them as an object either.
+pub(crate) fn time_as_string<S: Serializer>(
+    time: &FileTime,
+    serializer: S,
+) -> Result<S::Ok, S::Error> {
+    match time.value() {
+        FileTimeValue::OutOfRange => serializer.collect_str(time),
+        _ => time.serialize(serializer),
+    }
+}
+
+fn optional_time_as_string<S: Serializer>(
+    time: &Option<FileTime>,
+    serializer: S,
+) -> Result<S::Ok, S::Error> {
+    match time {
+        Some(time) => time_as_string(time, serializer),
+        None => serializer.serialize_none(),
+    }
+}
+
+fn optional_flags_as_string<S: Serializer>(
+    flags: &Option<impl fmt::Debug>,
+    serializer: S,
+) -> Result<S::Ok, S::Error> {
+    match flags {
+        Some(flags) => flags_as_string(flags, serializer),
+        None => serializer.serialize_none(),
     }
 }
@@ -0,0 +1,78 @@
+//! Fields derived from an entry and the rest of the MFT, such as its paths,
+//! which are added to the JSON output next to the raw structure of the entry.
+use crate::attribute::MftAttributeType;
+use crate::entry::EntryFlags;
+use crate::err::Result;
+use crate::stream::PathTable;
+use crate::{MftEntry, MftParser};
+
+use serde::{Deserialize, Serialize};
+
+use std::io::{Read, Seek};
+use std::path::PathBuf;
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct DerivedFields {
+    /// The path of the entry, as in the CSV output. `None` for entries without a name or a base entry.
+    pub full_path: Option<PathBuf>,
+    /// The path of every hard link of the entry (see `MftEntry::link_names`).
+    pub hard_link_paths: Vec<PathBuf>,
+    /// Whether the record has the `ALLOCATED` bit turned off.
+    pub is_deleted: bool,
+    pub is_directory: bool,
+    /// The size of the first $DATA stream, 0 without one.
+    pub file_size: u64,
+    /// The names of the named $DATA streams.
+    pub alternate_data_streams: Vec<String>,
+}
+
+impl DerivedFields {
+    pub fn from_entry(
+        entry: &MftEntry,
+        parser: &mut MftParser<impl Read + Seek>,
+    ) -> Result<DerivedFields> {
+        let full_path = parser.get_full_path_for_entry(entry)?;
+        let hard_link_paths = parser.get_hard_link_paths_for_entry(entry)?;
+
+        Ok(Self::from_entry_and_paths(
+            entry,
+            full_path,
+            hard_link_paths,
+        ))
+    }
+
+    /// Like `from_entry`, with the paths resolved from a `PathTable`, when streaming.
+    pub fn from_entry_and_path_table(entry: &MftEntry, paths: &PathTable) -> DerivedFields {
+        Self::from_entry_and_paths(
+            entry,
+            paths.get_full_path_for_entry(entry),
+            paths.get_hard_link_paths(entry),
+        )
+    }
+
+    pub fn from_entry_and_paths(
+        entry: &MftEntry,
+        full_path: Option<PathBuf>,
+        hard_link_paths: Vec<PathBuf>,
+    ) -> DerivedFields {
+        let data_attributes: Vec<_> = entry
+            .iter_attributes_matching(Some(vec![MftAttributeType::DATA]))
+            .filter_map(Result::ok)
+            .collect();
+
+        DerivedFields {
+            full_path,
+            hard_link_paths,
+            is_deleted: !entry.header.flags.contains(EntryFlags::ALLOCATED),
+            is_directory: entry.is_dir(),
+            file_size: data_attributes
+                .first()
+                .map_or(0, |attr| attr.header.data_size()),
+            alternate_data_streams: data_attributes
+                .iter()
+                .filter(|attr| !attr.header.name.is_empty())
+                .map(|attr| attr.header.name.clone())
+                .collect(),
+        }
+    }
+}
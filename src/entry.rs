@@ -1,30 +1,78 @@
+use crate::anomaly::{Anomaly, AnomalyKind};
 use crate::err::{Error, Result};
+use crate::impl_deserialize_for_bitflags;
 use crate::impl_serialize_for_bitflags;
+use crate::limits::ParseLimits;
+use crate::utils::{from_hex_string, to_hex_string};
 
-use log::{trace, warn};
+use log::{debug, trace};
 
 use winstructs::ntfs::mft_reference::MftReference;
 
 use byteorder::{LittleEndian, ReadBytesExt};
+use num_traits::FromPrimitive;
 
 use bitflags::bitflags;
+use serde::de::{self, Deserializer, Unexpected};
 use serde::ser::{self, SerializeStruct, Serializer};
-use serde::Serialize;
+use serde::{Deserialize, Serialize};
 
+use crate::attribute::error::AttributeError;
 use crate::attribute::header::{MftAttributeHeader, ResidentialHeader};
 use crate::attribute::x30::{FileNameAttr, FileNamespace};
-use crate::attribute::{MftAttribute, MftAttributeContent, MftAttributeType};
+use crate::attribute::{
+    AttributeRecord, MftAttribute, MftAttributeContent, MftAttributeType, ResidualAttribute,
+};
 
 use std::io::Read;
 use std::io::SeekFrom;
 use std::io::{Cursor, Seek};
 
-const SEQUENCE_NUMBER_STRIDE: usize = 512;
+/// The size of the fixed part of the entry header, entries can never be smaller.
+pub(crate) const ENTRY_HEADER_SIZE: usize = 48;
+/// Records are never larger than this.
+pub(crate) const MAX_ENTRY_SIZE: usize = 64 * 1024;
+/// The type code, record length, form code, name size, name offset, flags and instance fields.
+const ATTRIBUTE_HEADER_MIN_LENGTH: u32 = 16;
+/// The length of the common and resident (or non-resident) parts of an attribute header.
+const RESIDENT_ATTRIBUTE_HEADER_LENGTH: usize = 24;
+const NON_RESIDENT_ATTRIBUTE_HEADER_LENGTH: usize = 64;
 
 pub const ZERO_HEADER: &[u8; 4] = b"\x00\x00\x00\x00";
 pub const BAAD_HEADER: &[u8; 4] = b"BAAD";
 pub const FILE_HEADER: &[u8; 4] = b"FILE";
 
+/// What to do when the last bytes of a stride don't match the update sequence value,
+/// which usually means the record was torn by an interrupted write.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum FixupPolicy {
+    /// Fail with `Error::FixupMismatch`.
+    Strict,
+    /// Apply the fixups anyway, and report the mismatches as anomalies of the entry.
+    #[default]
+    Lenient,
+    /// Like `Lenient`, but also keep the record as it was read in `MftEntry::original_data`,
+    /// so the torn sectors can be compared with the fixed-up ones.
+    Preserve,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct FixupOptions {
+    pub policy: FixupPolicy,
+    /// The size of the strides protected by the update sequence array.
+    /// NTFS uses 512, regardless of the sector size of the device.
+    pub stride: usize,
+}
+
+impl Default for FixupOptions {
+    fn default() -> Self {
+        FixupOptions {
+            policy: FixupPolicy::default(),
+            stride: 512,
+        }
+    }
+}
+
 #[derive(Debug, Clone)]
 pub struct MftEntry {
     pub header: EntryHeader,
@@ -33,6 +81,29 @@ pub struct MftEntry {
     /// matched the fixup array value. It is optional because in the case of
     /// from_buffer_skip_fixup(), no fixup is even checked, thus, valid_fixup is None
     pub valid_fixup: Option<bool>,
+    /// Anomalies found while reading the record, such as fixup mismatches.
+    /// Attributes which fail to parse are reported by `attributes_with_anomalies`.
+    pub anomalies: Vec<Anomaly>,
+    /// With `FixupPolicy::Preserve`, the record before the fixups were applied,
+    /// if any of them did not match.
+    pub original_data: Option<Vec<u8>>,
+    /// Whether the record was damaged (a `BAAD` record, or one with a broken header),
+    /// and was parsed on a best-effort basis, see `MftEntry::recover`.
+    pub recovered: bool,
+    /// Limits applied when parsing the attributes of the entry.
+    pub limits: ParseLimits,
+    /// The attributes of an entry which was deserialized (for example, from the JSON output of `mft_dump`)
+    /// rather than read from a record. Such entries have no `data`, so their attributes are these ones.
+    deserialized_attributes: Option<Vec<AttributeRecord>>,
+}
+
+/// The parts of an entry which are read from the record itself, before any attribute is parsed.
+struct RecordParts {
+    header: EntryHeader,
+    valid_fixup: Option<bool>,
+    anomalies: Vec<Anomaly>,
+    original_data: Option<Vec<u8>>,
+    recovered: bool,
 }
 
 impl ser::Serialize for MftEntry {
@@ -40,18 +111,93 @@ impl ser::Serialize for MftEntry {
     where
         S: Serializer,
     {
-        let mut state = serializer.serialize_struct("MftEntry", 2)?;
-        let attributes: Vec<MftAttribute> = self.iter_attributes().filter_map(Result::ok).collect();
+        let mut state = serializer.serialize_struct("MftEntry", 6)?;
+        let attributes = self.attribute_records();
+        let anomalies = anomalies_of(&self.anomalies, &attributes);
         state.serialize_field("header", &self.header)?;
         state.serialize_field("attributes", &attributes)?;
         state.serialize_field("valid_fixup", &self.valid_fixup)?;
+        state.serialize_field("anomalies", &anomalies)?;
+        // Only present for torn records, with `FixupPolicy::Preserve`.
+        match &self.original_data {
+            Some(original_data) => {
+                state.serialize_field("original_data", &to_hex_string(original_data))?
+            }
+            None => state.skip_field("original_data")?,
+        }
+        // Only present for damaged records, see `MftEntry::recover`.
+        if self.recovered {
+            state.serialize_field("recovered", &self.recovered)?;
+        } else {
+            state.skip_field("recovered")?;
+        }
         state.end()
     }
 }
 
+/// Entries are read back without their record (`data` is empty), their attributes are kept
+/// with them instead. The anomalies of attributes which failed to parse are not kept,
+/// since they are derived from the attribute records again.
+///
+/// Without a record, some of what was read from it is gone:
+/// - `iter_residual_attributes` carves nothing.
+/// - The fixups can't be checked again, `valid_fixup`, the fixup anomalies and `original_data`
+///   are kept as they were serialized.
+/// - `limits` are the default ones.
+///
+/// Flags with bits which have no name are rejected, like any other value which `mft_dump` never writes.
+impl<'de> Deserialize<'de> for MftEntry {
+    fn deserialize<D>(deserializer: D) -> ::std::result::Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        #[derive(Deserialize)]
+        struct SerializedEntry {
+            header: EntryHeader,
+            attributes: Vec<AttributeRecord>,
+            valid_fixup: Option<bool>,
+            anomalies: Vec<Anomaly>,
+            #[serde(default)]
+            original_data: Option<String>,
+            #[serde(default)]
+            recovered: bool,
+        }
+
+        let entry = SerializedEntry::deserialize(deserializer)?;
+
+        let original_data = match entry.original_data {
+            Some(hex) => match from_hex_string(&hex) {
+                Some(original_data) => Some(original_data),
+                None => {
+                    return Err(de::Error::invalid_value(
+                        Unexpected::Str(&hex),
+                        &"a hex string",
+                    ))
+                }
+            },
+            None => None,
+        };
+
+        Ok(MftEntry {
+            header: entry.header,
+            data: vec![],
+            valid_fixup: entry.valid_fixup,
+            anomalies: entry
+                .anomalies
+                .into_iter()
+                .filter(|anomaly| anomaly.kind != AnomalyKind::AttributeError)
+                .collect(),
+            original_data,
+            recovered: entry.recovered,
+            limits: ParseLimits::default(),
+            deserialized_attributes: Some(entry.attributes),
+        })
+    }
+}
+
 /// <https://docs.microsoft.com/en-us/windows/desktop/devnotes/file-record-segment-header>
 /// The MFT entry can be filled entirely with 0-byte values.
-#[derive(Serialize, Debug, Clone)]
+#[derive(Serialize, Deserialize, Debug, Clone)]
 pub struct EntryHeader {
     /// MULTI_SECTOR_HEADER
     /// The signature. This value is a convenience to the user.
@@ -78,6 +224,7 @@ pub struct EntryHeader {
     pub total_entry_size: u32,
     /// A file reference to the base file record segment for this file.
     /// If this is the base file record, the value is 0. See MFT_SEGMENT_REFERENCE.
+    #[serde(deserialize_with = "crate::utils::deserialize_mft_reference")]
     pub base_reference: MftReference,
     pub first_attribute_id: u16,
     pub record_number: u64,
@@ -92,6 +239,7 @@ bitflags! {
 }
 
 impl_serialize_for_bitflags! {EntryFlags}
+impl_deserialize_for_bitflags! {EntryFlags}
 
 impl EntryHeader {
     /// Reads an entry from a stream, will error if the entry is empty (zeroes)
@@ -112,6 +260,15 @@ impl EntryHeader {
             return Ok(Self::zero());
         }
 
+        Self::read_fields(signature, reader, entry_id)
+    }
+
+    /// Reads the rest of the header, regardless of the signature (used for recovering damaged records).
+    fn read_fields<R: Read>(
+        signature: [u8; 4],
+        reader: &mut R,
+        entry_id: u64,
+    ) -> Result<EntryHeader> {
         let usa_offset = reader.read_u16::<LittleEndian>()?;
         let usa_size = reader.read_u16::<LittleEndian>()?;
         let logfile_sequence_number = reader.read_u64::<LittleEndian>()?;
@@ -174,22 +331,160 @@ impl MftEntry {
     /// Initializes an MFT Entry from a buffer.
     /// Since the parser is the entity responsible for knowing the entry size,
     /// we take ownership of the buffer instead of trying to read it from stream.
-    pub fn from_buffer(mut buffer: Vec<u8>, entry_number: u64) -> Result<MftEntry> {
-        let mut cursor = Cursor::new(&buffer);
-        // Get Header
-        let entry_header = EntryHeader::from_reader(&mut cursor, entry_number)?;
+    pub fn from_buffer(buffer: Vec<u8>, entry_number: u64) -> Result<MftEntry> {
+        Self::from_buffer_with_fixup(buffer, entry_number, FixupOptions::default())
+    }
+
+    /// Like `from_buffer`, with the given fixup policy and stride.
+    pub fn from_buffer_with_fixup(
+        mut buffer: Vec<u8>,
+        entry_number: u64,
+        fixup: FixupOptions,
+    ) -> Result<MftEntry> {
+        let parts = Self::read_record(&mut buffer, entry_number, fixup)
+            .map_err(|e| e.in_entry(entry_number))?;
+
+        Ok(Self::from_parts(parts, buffer))
+    }
+
+    /// Best-effort parsing of a damaged record, such as a `BAAD` record (which chkdsk marks after
+    /// a failed multi-sector transfer), or a record whose header is corrupted.
+    ///
+    /// The header fields are read regardless of the signature, and fixups are applied if the update
+    /// sequence array is usable. Attributes are looked up by their plausible type codes and record
+    /// lengths, instead of trusting the first attribute offset and the length of every record.
+    /// Damaged records are marked as `recovered`, with an anomaly describing what was recovered,
+    /// while intact records are parsed as usual.
+    pub fn recover(
+        mut buffer: Vec<u8>,
+        entry_number: u64,
+        fixup: FixupOptions,
+    ) -> Result<MftEntry> {
+        let parts = Self::recover_record(&mut buffer, entry_number, fixup)
+            .map_err(|e| e.in_entry(entry_number))?;
+
+        Ok(Self::from_parts(parts, buffer))
+    }
+
+    fn from_parts(parts: RecordParts, data: Vec<u8>) -> MftEntry {
+        MftEntry {
+            header: parts.header,
+            data,
+            valid_fixup: parts.valid_fixup,
+            anomalies: parts.anomalies,
+            original_data: parts.original_data,
+            recovered: parts.recovered,
+            limits: ParseLimits::default(),
+            deserialized_attributes: None,
+        }
+    }
+
+    fn read_record(
+        buffer: &mut [u8],
+        entry_number: u64,
+        fixup: FixupOptions,
+    ) -> Result<RecordParts> {
+        let entry_header = EntryHeader::from_reader(&mut Cursor::new(&buffer[..]), entry_number)?;
         trace!("Number of sectors: {:#?}", entry_header);
 
-        let valid_fixup = if entry_header.is_valid() {
-            Some(Self::apply_fixups(&entry_header, &mut buffer)?)
+        let (valid_fixup, anomalies, original_data) = if entry_header.is_valid() {
+            let (anomalies, original_data) = Self::apply_fixups(&entry_header, buffer, fixup)?;
+            (Some(anomalies.is_empty()), anomalies, original_data)
         } else {
-            None
+            (None, vec![], None)
         };
 
-        Ok(MftEntry {
+        Ok(RecordParts {
+            header: entry_header,
+            valid_fixup,
+            anomalies,
+            original_data,
+            recovered: false,
+        })
+    }
+
+    fn recover_record(
+        buffer: &mut [u8],
+        entry_number: u64,
+        fixup: FixupOptions,
+    ) -> Result<RecordParts> {
+        let mut cursor = Cursor::new(&buffer[..]);
+        let mut signature = [0; 4];
+        cursor.read_exact(&mut signature)?;
+
+        if signature == *ZERO_HEADER {
+            return Self::read_record(buffer, entry_number, fixup);
+        }
+
+        let mut entry_header = EntryHeader::read_fields(signature, &mut cursor, entry_number)?;
+        let mut anomalies = vec![];
+        let mut recovered = !entry_header.is_valid();
+
+        if recovered {
+            anomalies.push(Anomaly::recovered(
+                0,
+                format!("Recovered a record with signature {:?}", signature),
+            ));
+        }
+
+        // A damaged record is best read with whatever fixups can be applied.
+        let fixup = FixupOptions {
+            policy: match fixup.policy {
+                FixupPolicy::Strict => FixupPolicy::Lenient,
+                policy => policy,
+            },
+            ..fixup
+        };
+
+        let (valid_fixup, original_data) = match Self::apply_fixups(&entry_header, buffer, fixup) {
+            Ok((fixup_anomalies, original_data)) => {
+                let valid_fixup = fixup_anomalies.is_empty();
+                anomalies.extend(fixup_anomalies);
+                (Some(valid_fixup), original_data)
+            }
+            Err(e) => {
+                recovered = true;
+                anomalies.push(Anomaly::recovered(
+                    u64::from(entry_header.usa_offset),
+                    format!("Fixups were not applied: {}", e),
+                ));
+                (Some(false), None)
+            }
+        };
+
+        let first_attribute_offset = u64::from(entry_header.first_attribute_record_offset);
+        if !is_plausible_attribute(buffer, first_attribute_offset) {
+            recovered = true;
+            match find_plausible_attribute(buffer, ENTRY_HEADER_SIZE as u64) {
+                Some(offset) => {
+                    anomalies.push(Anomaly::recovered(
+                        offset,
+                        format!(
+                            "The first attribute offset ({}) is implausible, attributes were found at {}",
+                            first_attribute_offset, offset
+                        ),
+                    ));
+                    entry_header.first_attribute_record_offset = offset as u16;
+                }
+                // Without a known signature or any attribute, this is not a record at all.
+                None if signature != *BAAD_HEADER && signature != *FILE_HEADER => {
+                    return Err(Error::InvalidEntrySignature {
+                        bad_sig: signature.to_vec(),
+                    });
+                }
+                None => anomalies.push(Anomaly::recovered(
+                    first_attribute_offset,
+                    "No attributes were found in the record".to_string(),
+                )),
+            }
+        }
+
+        Ok(RecordParts {
             header: entry_header,
-            data: buffer,
             valid_fixup,
+            anomalies,
+            original_data,
+            recovered,
         })
     }
 
@@ -199,7 +494,8 @@ impl MftEntry {
     pub fn from_buffer_skip_fixup(buffer: Vec<u8>, entry_number: u64) -> Result<MftEntry> {
         let mut cursor = Cursor::new(&buffer);
         // Get Header
-        let entry_header = EntryHeader::from_reader(&mut cursor, entry_number)?;
+        let entry_header = EntryHeader::from_reader(&mut cursor, entry_number)
+            .map_err(|e| e.in_entry(entry_number))?;
         trace!("Number of sectors: {:#?}", entry_header);
 
         if !entry_header.is_valid() {
@@ -212,9 +508,20 @@ impl MftEntry {
             header: entry_header,
             data: buffer,
             valid_fixup: None,
+            anomalies: vec![],
+            original_data: None,
+            recovered: false,
+            limits: ParseLimits::default(),
+            deserialized_attributes: None,
         })
     }
 
+    /// Sets the limits used when parsing the attributes of the entry.
+    pub fn with_limits(mut self, limits: ParseLimits) -> Self {
+        self.limits = limits;
+        self
+    }
+
     /// Retrieves most human-readable representation of a file path entry.
     /// Will prefer `Win32` file name attributes, and fallback to `Dos` paths.
     pub fn find_best_name_attribute(&self) -> Option<FileNameAttr> {
@@ -238,20 +545,60 @@ impl MftEntry {
         }
     }
 
+    /// The names of the hard links of the entry: every `$FILE_NAME`, except DOS names which are the short names of
+    /// other links. Entries with DOS names only (which are rare) have them as their links.
+    pub fn link_names(&self) -> Vec<FileNameAttr> {
+        let file_name_attributes: Vec<FileNameAttr> = self
+            .iter_attributes_matching(Some(vec![MftAttributeType::FileName]))
+            .filter_map(Result::ok)
+            .filter_map(|a| a.data.into_file_name())
+            .collect();
+
+        if file_name_attributes
+            .iter()
+            .all(|a| a.namespace == FileNamespace::DOS)
+        {
+            return file_name_attributes;
+        }
+
+        file_name_attributes
+            .into_iter()
+            .filter(|a| a.namespace != FileNamespace::DOS)
+            .collect()
+    }
+
     /// Applies the update sequence array fixups.
     /// https://docs.microsoft.com/en-us/windows/desktop/devnotes/multi-sector-header
-    /// **Note**: The fixup will be written at the end of each 512-byte stride,
+    /// **Note**: The fixup will be written at the end of each `fixup.stride` bytes (512 by default),
     /// even if the device has more (or less) than 512 bytes per sector.
-    /// The returned result is true if all fixup blocks had the fixup array value, or
-    /// false if a block's fixup value did not match the array's value.
-    fn apply_fixups(header: &EntryHeader, buffer: &mut [u8]) -> Result<bool> {
-        let mut valid_fixup = true;
-        let number_of_fixups = u32::from(header.usa_size - 1);
-        trace!("Number of fixups: {}", number_of_fixups);
+    /// Returns an anomaly for every block whose fixup value did not match the array's value,
+    /// so an empty result means the fixup is valid, and the original record if it should be preserved.
+    fn apply_fixups(
+        header: &EntryHeader,
+        buffer: &mut [u8],
+        fixup: FixupOptions,
+    ) -> Result<(Vec<Anomaly>, Option<Vec<u8>>)> {
+        let stride = fixup.stride;
+        let mut anomalies = vec![];
 
         // Each fixup is a 2-byte element, and there are `usa_size` of them.
-        let fixups_start_offset = header.usa_offset as usize;
-        let fixups_end_offset = fixups_start_offset + (header.usa_size * 2) as usize;
+        // The first is the update sequence value itself, and each of the rest protects one stride.
+        let fixups_start_offset = usize::from(header.usa_offset);
+        let fixups_end_offset = fixups_start_offset + usize::from(header.usa_size) * 2;
+        let number_of_fixups = usize::from(header.usa_size).saturating_sub(1);
+        trace!("Number of fixups: {}", number_of_fixups);
+
+        if header.usa_size == 0
+            || stride < 2
+            || fixups_end_offset > buffer.len()
+            || number_of_fixups.saturating_mul(stride) > buffer.len()
+        {
+            return Err(Error::InvalidUpdateSequenceArray {
+                usa_offset: header.usa_offset,
+                usa_size: header.usa_size,
+                entry_size: buffer.len(),
+            });
+        }
 
         let fixups = buffer[fixups_start_offset..fixups_end_offset].to_vec();
         let mut fixups = fixups.chunks(2);
@@ -259,34 +606,56 @@ impl MftEntry {
         // There should always be bytes here, but just in case we put zeroes, so it will fail later.
         let update_sequence = fixups.next().unwrap_or(&[0, 0]);
 
-        // We need to compare each last two bytes each 512-bytes stride with the update_sequence,
+        // We need to compare each last two bytes each stride with the update_sequence,
         // And if they match, replace those bytes with the matching bytes from the fixup_sequence.
-        for (stride_number, fixup_bytes) in (0_usize..number_of_fixups as usize).zip(fixups) {
-            let sector_start_offset = stride_number * SEQUENCE_NUMBER_STRIDE;
-
-            let end_of_sector_bytes_end_offset = sector_start_offset + SEQUENCE_NUMBER_STRIDE;
-            let end_of_sector_bytes_start_offset = end_of_sector_bytes_end_offset - 2;
+        // All the strides are checked before any of them is patched, so the original can be kept.
+        let strides: Vec<(usize, &[u8])> = (0_usize..number_of_fixups)
+            .zip(fixups)
+            .map(|(stride_number, fixup_bytes)| ((stride_number + 1) * stride - 2, fixup_bytes))
+            .collect();
 
+        for (stride_number, &(end_of_sector_bytes_start_offset, fixup_bytes)) in
+            strides.iter().enumerate()
+        {
             let end_of_sector_bytes =
-                &mut buffer[end_of_sector_bytes_start_offset..end_of_sector_bytes_end_offset];
+                &buffer[end_of_sector_bytes_start_offset..end_of_sector_bytes_start_offset + 2];
 
             if end_of_sector_bytes != update_sequence {
                 // An item in the block did not match the fixup array value
-                warn!(
-                    "[entry: {}] fixup bytes are not equal to update sequence value - stride_number: {}, end_of_sector_bytes: {:?}, fixup_bytes: {:?}",
-                    header.record_number,
+                let message = format!(
+                    "fixup bytes are not equal to update sequence value - stride_number: {}, end_of_sector_bytes: {:?}, update_sequence: {:?}, fixup_bytes: {:?}",
                     stride_number,
                     end_of_sector_bytes.to_vec(),
+                    update_sequence.to_vec(),
                     fixup_bytes.to_vec()
                 );
+                debug!("[entry: {}] {}", header.record_number, message);
 
-                valid_fixup = false;
+                if fixup.policy == FixupPolicy::Strict {
+                    return Err(Error::FixupMismatch {
+                        offset: end_of_sector_bytes_start_offset as u64,
+                        message,
+                    });
+                }
+
+                anomalies.push(Anomaly::fixup_mismatch(
+                    end_of_sector_bytes_start_offset as u64,
+                    message,
+                ));
             }
+        }
 
-            end_of_sector_bytes.copy_from_slice(fixup_bytes);
+        let original_data = match fixup.policy {
+            FixupPolicy::Preserve if !anomalies.is_empty() => Some(buffer.to_vec()),
+            _ => None,
+        };
+
+        for (end_of_sector_bytes_start_offset, fixup_bytes) in strides {
+            buffer[end_of_sector_bytes_start_offset..end_of_sector_bytes_start_offset + 2]
+                .copy_from_slice(fixup_bytes);
         }
 
-        Ok(valid_fixup)
+        Ok((anomalies, original_data))
     }
 
     pub fn is_allocated(&self) -> bool {
@@ -307,80 +676,554 @@ impl MftEntry {
         &self,
         types: Option<Vec<MftAttributeType>>,
     ) -> impl Iterator<Item = Result<MftAttribute>> + '_ {
-        let mut cursor = Cursor::new(&self.data);
-        let mut offset = u64::from(self.header.first_attribute_record_offset);
-        let mut exhausted = false;
-
-        std::iter::from_fn(move || {
-            // We use a loop here to allow skipping filtered attributes.
-            loop {
-                if exhausted {
-                    return None;
+        iter_entry_attributes(
+            &self.header,
+            &self.data,
+            self.deserialized_attributes.as_deref(),
+            types,
+            self.limits,
+            self.recovered,
+        )
+    }
+
+    /// Parses all the attributes of the entry.
+    /// Attributes which could not be parsed are reported as anomalies,
+    /// following the anomalies found while reading the record.
+    pub fn attributes_with_anomalies(&self) -> (Vec<MftAttribute>, Vec<Anomaly>) {
+        split_attribute_records(self.attribute_records(), &self.anomalies)
+    }
+
+    /// Parses all the attributes of the entry, in record order.
+    /// Attributes which could not be parsed are kept as `AttributeRecord::Error`, with their salvaged bytes.
+    pub fn attribute_records(&self) -> Vec<AttributeRecord> {
+        entry_attribute_records(
+            &self.header,
+            &self.data,
+            self.deserialized_attributes.as_deref(),
+            self.limits,
+            self.recovered,
+        )
+    }
+
+    /// Carves attributes left over from an earlier use of the record from its slack,
+    /// the bytes between `used_entry_size` and the end of the record, which `iter_attributes` never reaches.
+    ///
+    /// Candidates are found by their plausible type codes and record lengths, and are only yielded
+    /// if their header and content parse like a regular attribute.
+    /// They may be partially overwritten, and don't describe the current state of the file.
+    ///
+    /// Deserialized entries have no record (and so no slack), nothing is carved from them.
+    pub fn iter_residual_attributes(&self) -> impl Iterator<Item = ResidualAttribute> + '_ {
+        iter_residual_attributes_in(&self.data, self.header.used_entry_size, self.limits)
+    }
+
+    /// Returns a borrowed view over this entry.
+    ///
+    /// Deserialized entries have no record, their view has no `data` but the attributes which were kept.
+    pub fn as_entry_ref(&self) -> MftEntryRef<'_> {
+        MftEntryRef {
+            header: self.header.clone(),
+            data: &self.data,
+            valid_fixup: self.valid_fixup,
+            anomalies: self.anomalies.clone(),
+            original_data: self.original_data.clone(),
+            recovered: self.recovered,
+            limits: self.limits,
+            deserialized_attributes: self.deserialized_attributes.as_deref(),
+        }
+    }
+}
+
+/// A borrowed MFT entry.
+///
+/// Unlike `MftEntry`, the record bytes are not owned by the entry, but by whoever handed it out
+/// (usually a scratch buffer owned by the parser, see `MftParser::get_entry_ref`).
+/// Attributes are parsed lazily from the same borrowed buffer.
+#[derive(Debug, Clone)]
+pub struct MftEntryRef<'a> {
+    pub header: EntryHeader,
+    pub data: &'a [u8],
+    /// See `MftEntry::valid_fixup`.
+    pub valid_fixup: Option<bool>,
+    /// See `MftEntry::anomalies`.
+    pub anomalies: Vec<Anomaly>,
+    /// See `MftEntry::original_data`.
+    pub original_data: Option<Vec<u8>>,
+    /// See `MftEntry::recovered`.
+    pub recovered: bool,
+    /// See `MftEntry::limits`.
+    pub limits: ParseLimits,
+    /// See `MftEntry::as_entry_ref`.
+    deserialized_attributes: Option<&'a [AttributeRecord]>,
+}
+
+impl<'a> MftEntryRef<'a> {
+    /// Initializes a borrowed MFT entry from a raw record.
+    /// The record is copied into `scratch` (reusing its allocation), and fixups are applied there,
+    /// so the source buffer (for example, a memory mapped file) is never mutated.
+    pub fn from_slice(
+        record: &[u8],
+        scratch: &'a mut Vec<u8>,
+        entry_number: u64,
+    ) -> Result<MftEntryRef<'a>> {
+        Self::from_slice_with_fixup(record, scratch, entry_number, FixupOptions::default())
+    }
+
+    /// Like `from_slice`, with the given fixup policy and stride.
+    pub fn from_slice_with_fixup(
+        record: &[u8],
+        scratch: &'a mut Vec<u8>,
+        entry_number: u64,
+        fixup: FixupOptions,
+    ) -> Result<MftEntryRef<'a>> {
+        scratch.clear();
+        scratch.extend_from_slice(record);
+
+        let parts = MftEntry::read_record(scratch, entry_number, fixup)
+            .map_err(|e| e.in_entry(entry_number))?;
+
+        Ok(Self::from_parts(parts, scratch))
+    }
+
+    /// Like `from_slice`, but recovers damaged records, see `MftEntry::recover`.
+    pub fn recover_slice(
+        record: &[u8],
+        scratch: &'a mut Vec<u8>,
+        entry_number: u64,
+        fixup: FixupOptions,
+    ) -> Result<MftEntryRef<'a>> {
+        scratch.clear();
+        scratch.extend_from_slice(record);
+
+        let parts = MftEntry::recover_record(scratch, entry_number, fixup)
+            .map_err(|e| e.in_entry(entry_number))?;
+
+        Ok(Self::from_parts(parts, scratch))
+    }
+
+    fn from_parts(parts: RecordParts, data: &'a [u8]) -> MftEntryRef<'a> {
+        MftEntryRef {
+            header: parts.header,
+            data,
+            valid_fixup: parts.valid_fixup,
+            anomalies: parts.anomalies,
+            original_data: parts.original_data,
+            recovered: parts.recovered,
+            limits: ParseLimits::default(),
+            deserialized_attributes: None,
+        }
+    }
+
+    /// See `MftEntry::with_limits`.
+    pub fn with_limits(mut self, limits: ParseLimits) -> Self {
+        self.limits = limits;
+        self
+    }
+
+    /// Copies the borrowed record into an owned `MftEntry`.
+    pub fn to_entry(&self) -> MftEntry {
+        MftEntry {
+            header: self.header.clone(),
+            data: self.data.to_vec(),
+            valid_fixup: self.valid_fixup,
+            anomalies: self.anomalies.clone(),
+            original_data: self.original_data.clone(),
+            recovered: self.recovered,
+            limits: self.limits,
+            deserialized_attributes: self
+                .deserialized_attributes
+                .map(<[AttributeRecord]>::to_vec),
+        }
+    }
+
+    pub fn is_allocated(&self) -> bool {
+        self.header.flags.bits() & 0x01 != 0
+    }
+
+    pub fn is_dir(&self) -> bool {
+        self.header.flags.bits() & 0x02 != 0
+    }
+
+    /// Returns an iterator over all the attributes of the entry.
+    pub fn iter_attributes(&self) -> impl Iterator<Item = Result<MftAttribute>> + 'a {
+        self.iter_attributes_matching(None)
+    }
+
+    /// Returns an iterator over the attributes in the list given in `types`, skips other attributes.
+    pub fn iter_attributes_matching(
+        &self,
+        types: Option<Vec<MftAttributeType>>,
+    ) -> impl Iterator<Item = Result<MftAttribute>> + 'a {
+        iter_entry_attributes(
+            &self.header,
+            self.data,
+            self.deserialized_attributes,
+            types,
+            self.limits,
+            self.recovered,
+        )
+    }
+
+    /// See `MftEntry::iter_residual_attributes`.
+    pub fn iter_residual_attributes(&self) -> impl Iterator<Item = ResidualAttribute> + 'a {
+        iter_residual_attributes_in(self.data, self.header.used_entry_size, self.limits)
+    }
+
+    /// See `MftEntry::attributes_with_anomalies`.
+    pub fn attributes_with_anomalies(&self) -> (Vec<MftAttribute>, Vec<Anomaly>) {
+        split_attribute_records(self.attribute_records(), &self.anomalies)
+    }
+
+    /// See `MftEntry::attribute_records`.
+    pub fn attribute_records(&self) -> Vec<AttributeRecord> {
+        entry_attribute_records(
+            &self.header,
+            self.data,
+            self.deserialized_attributes,
+            self.limits,
+            self.recovered,
+        )
+    }
+}
+
+/// The attributes of an entry, parsed from its record, or the ones kept when it was deserialized.
+fn iter_entry_attributes<'a>(
+    header: &EntryHeader,
+    data: &'a [u8],
+    deserialized_attributes: Option<&'a [AttributeRecord]>,
+    types: Option<Vec<MftAttributeType>>,
+    limits: ParseLimits,
+    recovered: bool,
+) -> impl Iterator<Item = Result<MftAttribute>> + 'a {
+    let record_number = header.record_number;
+
+    // Deserialized entries have no record to parse, their attributes were kept.
+    let deserialized = deserialized_attributes.map(|records| {
+        let types = types.clone();
+        records
+            .iter()
+            .filter_map(AttributeRecord::attribute)
+            .filter(move |attribute| {
+                types
+                    .as_ref()
+                    .map_or(true, |types| types.contains(&attribute.header.type_code))
+            })
+            .cloned()
+            .map(Ok)
+    });
+
+    let parsed = deserialized.is_none().then(|| {
+        iter_attributes_in(
+            data,
+            u64::from(header.first_attribute_record_offset),
+            types,
+            limits,
+            recovered,
+        )
+        .map(move |(location, attribute)| {
+            attribute.map_err(|e| {
+                e.in_attribute(record_number, location.offset, location.attribute_type)
+            })
+        })
+    });
+
+    parsed
+        .into_iter()
+        .flatten()
+        .chain(deserialized.into_iter().flatten())
+}
+
+/// See `iter_entry_attributes`.
+fn entry_attribute_records(
+    header: &EntryHeader,
+    data: &[u8],
+    deserialized_attributes: Option<&[AttributeRecord]>,
+    limits: ParseLimits,
+    recovered: bool,
+) -> Vec<AttributeRecord> {
+    match deserialized_attributes {
+        Some(records) => records.to_vec(),
+        None => collect_attribute_records(
+            data,
+            u64::from(header.first_attribute_record_offset),
+            limits,
+            recovered,
+        ),
+    }
+}
+
+/// Checks the size of the records of an MFT, as given by the first header or by the caller.
+/// Records are a power of two in size, which is at least the header and at most `MAX_ENTRY_SIZE`.
+pub(crate) fn check_entry_size(entry_size: u32) -> Result<u32> {
+    if entry_size.is_power_of_two()
+        && (ENTRY_HEADER_SIZE..=MAX_ENTRY_SIZE).contains(&(entry_size as usize))
+    {
+        Ok(entry_size)
+    } else {
+        Err(Error::InvalidEntrySize { entry_size })
+    }
+}
+
+fn collect_attribute_records(
+    data: &[u8],
+    first_attribute_offset: u64,
+    limits: ParseLimits,
+    recovered: bool,
+) -> Vec<AttributeRecord> {
+    iter_attributes_in(data, first_attribute_offset, None, limits, recovered)
+        .map(|(location, attribute)| match attribute {
+            Ok(attribute) => AttributeRecord::Attribute(attribute),
+            Err(e) => AttributeRecord::Error(AttributeError::salvage(
+                data,
+                location.offset,
+                location.instance,
+                &e,
+            )),
+        })
+        .collect()
+}
+
+/// The anomalies of the record, followed by the attributes which could not be parsed.
+pub(crate) fn anomalies_of(
+    record_anomalies: &[Anomaly],
+    records: &[AttributeRecord],
+) -> Vec<Anomaly> {
+    record_anomalies
+        .iter()
+        .cloned()
+        .chain(
+            records
+                .iter()
+                .filter_map(AttributeRecord::error)
+                .map(Anomaly::from),
+        )
+        .collect()
+}
+
+fn split_attribute_records(
+    records: Vec<AttributeRecord>,
+    record_anomalies: &[Anomaly],
+) -> (Vec<MftAttribute>, Vec<Anomaly>) {
+    let anomalies = anomalies_of(record_anomalies, &records);
+    let attributes = records
+        .into_iter()
+        .filter_map(|record| match record {
+            AttributeRecord::Attribute(attribute) => Some(attribute),
+            AttributeRecord::Error(_) => None,
+        })
+        .collect();
+
+    (attributes, anomalies)
+}
+
+/// Where an attribute record was found, and what is known about it even if it failed to parse.
+struct AttributeLocation {
+    offset: u64,
+    instance: Option<u16>,
+    attribute_type: Option<MftAttributeType>,
+}
+
+impl AttributeLocation {
+    /// The location of an attribute whose header could not be parsed.
+    fn unparsed(offset: u64) -> Self {
+        AttributeLocation {
+            offset,
+            instance: None,
+            attribute_type: None,
+        }
+    }
+}
+
+/// Whether `offset` looks like the start of an attribute record (or the end marker),
+/// judging by its type code, form code and record length.
+fn is_plausible_attribute(data: &[u8], offset: u64) -> bool {
+    let offset = match usize::try_from(offset) {
+        Ok(offset) if offset % 8 == 0 && offset >= ENTRY_HEADER_SIZE => offset,
+        _ => return false,
+    };
+
+    let header = match data.get(offset..offset + ATTRIBUTE_HEADER_MIN_LENGTH as usize) {
+        Some(header) => header,
+        None => return false,
+    };
+
+    let type_code = u32::from_le_bytes([header[0], header[1], header[2], header[3]]);
+    if type_code == 0xFFFF_FFFF {
+        return true;
+    }
+
+    if MftAttributeType::from_u32(type_code).is_none() {
+        return false;
+    }
+
+    let record_length = u32::from_le_bytes([header[4], header[5], header[6], header[7]]) as usize;
+    let min_record_length = match header[8] {
+        0 => RESIDENT_ATTRIBUTE_HEADER_LENGTH,
+        1 => NON_RESIDENT_ATTRIBUTE_HEADER_LENGTH,
+        _ => return false,
+    };
+
+    record_length >= min_record_length
+        && record_length % 8 == 0
+        && offset + record_length <= data.len()
+}
+
+/// Finds the first plausible attribute record (not the end marker) at or after `offset`.
+fn find_plausible_attribute(data: &[u8], offset: u64) -> Option<u64> {
+    let start = offset.max(ENTRY_HEADER_SIZE as u64).next_multiple_of(8);
+
+    (start..data.len() as u64).step_by(8).find(|&offset| {
+        is_plausible_attribute(data, offset)
+            && data[offset as usize..offset as usize + 4] != [0xFF; 4]
+    })
+}
+
+/// Walks the attribute records of an entry, starting at `first_attribute_offset`.
+/// Each attribute is yielded with its location in the record.
+///
+/// For recovered records, implausible attribute records are skipped,
+/// and the walk resumes at the next plausible one.
+fn iter_attributes_in(
+    data: &[u8],
+    first_attribute_offset: u64,
+    types: Option<Vec<MftAttributeType>>,
+    limits: ParseLimits,
+    recovered: bool,
+) -> impl Iterator<Item = (AttributeLocation, Result<MftAttribute>)> + '_ {
+    let mut cursor = Cursor::new(data);
+    let mut offset = first_attribute_offset;
+    let mut exhausted = false;
+
+    std::iter::from_fn(move || {
+        // We use a loop here to allow skipping filtered attributes.
+        loop {
+            if exhausted {
+                return None;
+            }
+
+            if recovered && !is_plausible_attribute(data, offset) {
+                match find_plausible_attribute(data, offset) {
+                    Some(next) => {
+                        debug!(
+                            "Skipping implausible attribute at {}, resuming at {}",
+                            offset, next
+                        );
+                        offset = next;
+                    }
+                    None => return None,
                 }
+            }
+
+            if let Err(e) = cursor.seek(SeekFrom::Start(offset)) {
+                exhausted = true;
+                return Some((AttributeLocation::unparsed(offset), Err(e.into())));
+            };
 
-                if let Err(e) = cursor.seek(SeekFrom::Start(offset)) {
+            let header = MftAttributeHeader::from_stream(&mut cursor);
+
+            // Unexpected I/O error, return err and stop iterating
+            let header = match header {
+                Ok(h) => h,
+                Err(e) => {
                     exhausted = true;
-                    return Some(Err(e.into()));
-                };
+                    return Some((AttributeLocation::unparsed(offset), Err(e)));
+                }
+            };
 
-                let header = MftAttributeHeader::from_stream(&mut cursor);
+            let header = match header {
+                Some(attribute_header) => attribute_header,
+                // Header is 0xFFFF_FFFF, we are finished
+                None => return None,
+            };
 
-                // Unexpected I/O error, return err and stop iterating
-                let header = match header {
-                    Ok(h) => h,
-                    Err(e) => {
-                        exhausted = true;
-                        return Some(Err(e));
-                    }
-                };
+            let location = AttributeLocation {
+                offset,
+                instance: Some(header.instance),
+                attribute_type: Some(header.type_code.clone()),
+            };
 
-                let header = match header {
-                    Some(attribute_header) => attribute_header,
-                    // Header is 0xFFFF_FFFF, we are finished
-                    None => return None,
-                };
+            // A record must at least hold its own header, otherwise we would never advance.
+            if header.record_length < ATTRIBUTE_HEADER_MIN_LENGTH {
+                exhausted = true;
+                return Some((
+                    location,
+                    Err(Error::InvalidRecordLength {
+                        record_length: header.record_length,
+                    }),
+                ));
+            }
 
-                // Increment offset before moving header.
-                offset += u64::from(header.record_length);
+            // Increment offset before moving header.
+            offset += u64::from(header.record_length);
 
-                // Skip attribute if filtered
-                if let Some(filter) = &types {
-                    if !filter.contains(&header.type_code) {
-                        continue;
-                    }
+            // Skip attribute if filtered
+            if let Some(filter) = &types {
+                if !filter.contains(&header.type_code) {
+                    continue;
                 }
+            }
 
-                // Check if the header is resident, and if it is, read the attribute content.
-                let attribute_content = match header.residential_header {
-                    ResidentialHeader::Resident(ref resident) => {
-                        match MftAttributeContent::from_stream_resident(
-                            &mut cursor,
-                            &header,
-                            resident,
-                        ) {
-                            Ok(content) => content,
-                            Err(e) => return Some(Err(e)),
-                        }
-                    }
-                    ResidentialHeader::NonResident(ref resident) => {
-                        match MftAttributeContent::from_stream_non_resident(
-                            &mut cursor,
-                            &header,
-                            resident,
-                        ) {
-                            Ok(content) => content,
-                            Err(e) => return Some(Err(e)),
-                        }
-                    }
-                };
+            return Some((location, read_attribute(&mut cursor, header, &limits)));
+        }
+    })
+}
+
+/// Reads the content of the attribute whose header was just read from `cursor`.
+fn read_attribute(
+    cursor: &mut Cursor<&[u8]>,
+    header: MftAttributeHeader,
+    limits: &ParseLimits,
+) -> Result<MftAttribute> {
+    // Check if the header is resident, and if it is, read the attribute content.
+    let attribute_content = match header.residential_header {
+        ResidentialHeader::Resident(ref resident) => {
+            MftAttributeContent::from_stream_resident(cursor, &header, resident, limits)?
+        }
+        ResidentialHeader::NonResident(ref resident) => {
+            MftAttributeContent::from_stream_non_resident(cursor, &header, resident, limits)?
+        }
+    };
+
+    Ok(MftAttribute {
+        header,
+        data: attribute_content,
+    })
+}
+
+/// Carves attributes from the slack of an entry, between its used size and the end of the record.
+fn iter_residual_attributes_in(
+    data: &[u8],
+    used_entry_size: u32,
+    limits: ParseLimits,
+) -> impl Iterator<Item = ResidualAttribute> + '_ {
+    let mut cursor = Cursor::new(data);
+    let mut offset = u64::from(used_entry_size);
 
-                return Some(Ok(MftAttribute {
-                    header,
-                    data: attribute_content,
-                }));
+    std::iter::from_fn(move || loop {
+        offset = find_plausible_attribute(data, offset)?;
+
+        let carved = cursor
+            .seek(SeekFrom::Start(offset))
+            .map_err(Error::from)
+            .and_then(|_| MftAttributeHeader::from_stream(&mut cursor));
+
+        if let Ok(Some(header)) = carved {
+            let record_length = u64::from(header.record_length);
+
+            if let Ok(attribute) = read_attribute(&mut cursor, header, &limits) {
+                trace!("Carved a residual attribute at {}", offset);
+                let slack_offset = offset - u64::from(used_entry_size);
+                offset += record_length;
+                return Some(ResidualAttribute {
+                    slack_offset,
+                    attribute,
+                });
             }
-        })
-    }
+        }
+
+        // Not an attribute after all (or a partially overwritten one), keep looking.
+        offset += 8;
+    })
 }
 
 #[cfg(test)]
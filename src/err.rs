@@ -1,3 +1,6 @@
+use crate::attribute::MftAttributeType;
+
+use serde::{Deserialize, Serialize};
 use std::path::{Path, PathBuf};
 use thiserror::Error;
 
@@ -5,7 +8,7 @@ pub type Result<T> = ::std::result::Result<T, Error>;
 
 #[derive(Debug, Error)]
 pub enum Error {
-    #[error("An I/O error has occurred")]
+    #[error("An I/O error has occurred: {}", source)]
     IoError {
         #[from]
         source: std::io::Error,
@@ -15,6 +18,29 @@ pub enum Error {
         path: PathBuf,
         source: std::io::Error,
     },
+    #[error("Failed to read archive {}: {}", path.display(), detail)]
+    FailedToReadArchive { path: PathBuf, detail: String },
+    #[error("Invalid {} image: {}", format, detail)]
+    InvalidImage {
+        format: &'static str,
+        detail: String,
+    },
+    #[error("Invalid NTFS boot sector: {}", detail)]
+    InvalidBootSector { detail: String },
+    #[error("Invalid partition table: {}", detail)]
+    InvalidPartitionTable { detail: String },
+    #[error("Partition {} does not exist, or is not an NTFS volume", index)]
+    NoSuchNtfsPartition { index: usize },
+    #[error(
+        "Expected a single NTFS partition, found {} (indices: {:?}), select one by index",
+        indices.len(),
+        indices
+    )]
+    AmbiguousNtfsPartition { indices: Vec<usize> },
+    #[error("Invalid time zone `{}`: {}", name, detail)]
+    InvalidTimeZone { name: String, detail: String },
+    #[error("Invalid field selection: {}", detail)]
+    InvalidFieldSelection { detail: String },
     #[error("Error while decoding name in filename attribute")]
     InvalidFilename,
     #[error(
@@ -22,6 +48,29 @@ pub enum Error {
         bad_sig
     )]
     InvalidEntrySignature { bad_sig: Vec<u8> },
+    #[error("Invalid entry size: {}", entry_size)]
+    InvalidEntrySize { entry_size: u32 },
+    #[error(
+        "Update sequence array (offset: {}, size: {}) does not fit in an entry of {} bytes",
+        usa_offset,
+        usa_size,
+        entry_size
+    )]
+    InvalidUpdateSequenceArray {
+        usa_offset: u16,
+        usa_size: u16,
+        entry_size: usize,
+    },
+    #[error("Fixup mismatch at offset {}: {}", offset, message)]
+    FixupMismatch { offset: u64, message: String },
+    #[error("Invalid record length: {}", record_length)]
+    InvalidRecordLength { record_length: u32 },
+    #[error("Too many {} ({}, the limit is {})", limit, value, max)]
+    LimitExceeded {
+        limit: &'static str,
+        value: usize,
+        max: usize,
+    },
     #[error("Unknown `AttributeType`: {:04X}", attribute_type)]
     UnknownAttributeType { attribute_type: u32 },
     #[error("Unknown collation type {}", collation_type)]
@@ -51,6 +100,40 @@ pub enum Error {
     FailedToDecodeDataRuns { bad_data_runs: Vec<u8> },
     #[error("An unexpected error has occurred: {}", detail)]
     Any { detail: String },
+    #[error(
+        "[entry: {}{}] {}",
+        record_number,
+        attribute_location(attribute_offset, attribute_type),
+        source
+    )]
+    InEntry {
+        record_number: u64,
+        /// Offset of the attribute (`MftAttributeHeader::start_offset`), if the error is in an attribute.
+        attribute_offset: Option<u64>,
+        /// Type of the attribute, if its header could be parsed.
+        attribute_type: Option<MftAttributeType>,
+        source: Box<Error>,
+    },
+}
+
+fn attribute_location(offset: &Option<u64>, attribute_type: &Option<MftAttributeType>) -> String {
+    match (offset, attribute_type) {
+        (Some(offset), Some(attribute_type)) => {
+            format!(", attribute: {:?} at offset {}", attribute_type, offset)
+        }
+        (Some(offset), None) => format!(", attribute at offset {}", offset),
+        _ => String::new(),
+    }
+}
+
+/// A serializable description of an error, and where it occurred.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct ErrorReport {
+    pub record_number: Option<u64>,
+    pub attribute_offset: Option<u64>,
+    pub attribute_type: Option<MftAttributeType>,
+    /// The message of the underlying error, without the location.
+    pub message: String,
 }
 
 impl Error {
@@ -72,4 +155,79 @@ impl Error {
             source,
         }
     }
+
+    /// Attaches the number of the entry the error occurred in.
+    /// Errors which already carry a location are returned as-is.
+    pub fn in_entry(self, record_number: u64) -> Error {
+        match self {
+            Error::InEntry { .. } => self,
+            _ => Error::InEntry {
+                record_number,
+                attribute_offset: None,
+                attribute_type: None,
+                source: Box::new(self),
+            },
+        }
+    }
+
+    /// Attaches the entry and attribute the error occurred in.
+    pub fn in_attribute(
+        self,
+        record_number: u64,
+        attribute_offset: u64,
+        attribute_type: Option<MftAttributeType>,
+    ) -> Error {
+        match self {
+            Error::InEntry { .. } => self,
+            _ => Error::InEntry {
+                record_number,
+                attribute_offset: Some(attribute_offset),
+                attribute_type,
+                source: Box::new(self),
+            },
+        }
+    }
+
+    /// The number of the entry the error occurred in, if known.
+    pub fn record_number(&self) -> Option<u64> {
+        match self {
+            Error::InEntry { record_number, .. } => Some(*record_number),
+            _ => None,
+        }
+    }
+
+    /// The offset of the attribute the error occurred in, if known.
+    pub fn attribute_offset(&self) -> Option<u64> {
+        match self {
+            Error::InEntry {
+                attribute_offset, ..
+            } => *attribute_offset,
+            _ => None,
+        }
+    }
+
+    /// The type of the attribute the error occurred in, if known.
+    pub fn attribute_type(&self) -> Option<&MftAttributeType> {
+        match self {
+            Error::InEntry { attribute_type, .. } => attribute_type.as_ref(),
+            _ => None,
+        }
+    }
+
+    /// The error, without the location it occurred in.
+    pub fn without_location(&self) -> &Error {
+        match self {
+            Error::InEntry { source, .. } => source,
+            _ => self,
+        }
+    }
+
+    pub fn report(&self) -> ErrorReport {
+        ErrorReport {
+            record_number: self.record_number(),
+            attribute_offset: self.attribute_offset(),
+            attribute_type: self.attribute_type().cloned(),
+            message: self.without_location().to_string(),
+        }
+    }
 }
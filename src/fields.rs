@@ -0,0 +1,180 @@
+//! The catalog of fields of an entry, which selects and orders the columns of the flat outputs.
+//!
+//! Every field is a column of `FlatMftEntryWithName`. A `FieldSelection` projects rows to the chosen fields,
+//! in the chosen order, both as CSV records and as JSON objects (keyed by the field names).
+use crate::csv::FlatMftEntryWithName;
+use crate::err::{Error, Result};
+
+use serde::ser::SerializeMap;
+use serde::{Serialize, Serializer};
+use serde_json::Value;
+
+/// A field of the catalog.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Field {
+    /// The name used to select the field, and its key in projected rows.
+    pub name: &'static str,
+    /// The column of `FlatMftEntryWithName` in the CSV output.
+    pub column: &'static str,
+    pub description: &'static str,
+}
+
+const fn field(name: &'static str, column: &'static str, description: &'static str) -> Field {
+    Field {
+        name,
+        column,
+        description,
+    }
+}
+
+/// Every field, in the order of the CSV output.
+#[rustfmt::skip]
+pub const FIELDS: &[Field] = &[
+    field("signature", "Signature", "`FILE`, or `BAAD` for records which failed a multi-sector transfer"),
+    field("entry_id", "EntryId", "The record number"),
+    field("sequence", "Sequence", "The sequence number of the record"),
+    field("base_entry_id", "BaseEntryId", "For extension records, the record number of the base record"),
+    field("base_entry_sequence", "BaseEntrySequence", "For extension records, the sequence number of the base record"),
+    field("hard_link_count", "HardLinkCount", "The number of hard links"),
+    field("flags", "Flags", "The flags of the record, such as `ALLOCATED` and `INDEX_PRESENT`"),
+    field("used_entry_size", "UsedEntrySize", "The used size of the record, in bytes"),
+    field("total_entry_size", "TotalEntrySize", "The allocated size of the record, in bytes"),
+    field("file_size", "FileSize", "The size of the first $DATA stream, 0 without one"),
+    field("is_a_directory", "IsADirectory", "Whether the record is a directory"),
+    field("is_deleted", "IsDeleted", "Whether the record is not `ALLOCATED`"),
+    field("has_alternate_data_streams", "HasAlternateDataStreams", "Whether the record has named $DATA streams"),
+    field("data_streams", "DataStreams", "Every $DATA stream, as `name:size`, separated by `; `"),
+    field("si_flags", "StandardInfoFlags", "The file attributes of $STANDARD_INFORMATION"),
+    field("si_modified", "StandardInfoLastModified", "The modified time of $STANDARD_INFORMATION"),
+    field("si_accessed", "StandardInfoLastAccess", "The accessed time of $STANDARD_INFORMATION"),
+    field("si_created", "StandardInfoCreated", "The created time of $STANDARD_INFORMATION"),
+    field("si_mft_modified", "StandardInfoMftModified", "The $MFT modified time of $STANDARD_INFORMATION"),
+    field("si_usn", "StandardInfoUsn", "The update sequence number of the last change to the file"),
+    field("si_security_id", "StandardInfoSecurityId", "The security ID (in $Secure) of the file"),
+    field("si_owner_id", "StandardInfoOwnerId", "The owner ID (for quotas) of the file"),
+    field("fn_name", "FileName", "The best name of the file (Win32 if there is one)"),
+    field("fn_namespace", "FileNameNamespace", "The namespace of the best name"),
+    field("fn_flags", "FileNameFlags", "The file attributes of the best name"),
+    field("fn_modified", "FileNameLastModified", "The modified time of the best name"),
+    field("fn_accessed", "FileNameLastAccess", "The accessed time of the best name"),
+    field("fn_created", "FileNameCreated", "The created time of the best name"),
+    field("fn_mft_modified", "FileNameMftModified", "The $MFT modified time of the best name"),
+    field("fn_logical_size", "FileNameLogicalSize", "The logical size of the file, as of the last update of the best name"),
+    field("fn_physical_size", "FileNamePhysicalSize", "The allocated size of the file, as of the last update of the best name"),
+    field("parent_entry_id", "ParentEntryId", "The record number of the parent directory of the best name"),
+    field("parent_entry_sequence", "ParentEntrySequence", "The sequence number of the parent directory of the best name"),
+    field("sfn_name", "ShortFileName", "The separate DOS (8.3) name, if any"),
+    field("sfn_modified", "ShortFileNameLastModified", "The modified time of the DOS name"),
+    field("sfn_accessed", "ShortFileNameLastAccess", "The accessed time of the DOS name"),
+    field("sfn_created", "ShortFileNameCreated", "The created time of the DOS name"),
+    field("sfn_mft_modified", "ShortFileNameMftModified", "The $MFT modified time of the DOS name"),
+    field("file_names", "FileNames", "Every $FILE_NAME, as `name (namespace, parent entry-sequence)`, separated by `; `"),
+    field("full_path", "FullPath", "The full path of the file"),
+    field("anomaly_count", "AnomalyCount", "The number of anomalies found in the record"),
+    field("attribute_errors", "AttributeErrors", "The attributes which failed to parse, separated by `; `"),
+    field("si_created_before_fn_created", "SiCreatedBeforeFnCreated", "Timestomping: $STANDARD_INFORMATION was created before a $FILE_NAME"),
+    field("si_zero_fraction", "SiZeroFraction", "Timestomping: a $STANDARD_INFORMATION time has no 100ns fraction"),
+    field("si_modified_before_created", "SiModifiedBeforeCreated", "Timestomping: $STANDARD_INFORMATION was modified before it was created"),
+    field("outside_volume_lifetime", "OutsideVolumeLifetime", "Timestomping: a time is outside of the lifetime of the volume"),
+    field("carved_offset", "CarvedOffset", "For carved records, the offset of the record in the source"),
+];
+
+/// Finds a field by its name.
+pub fn find_field(name: &str) -> Option<&'static Field> {
+    FIELDS.iter().find(|field| field.name == name)
+}
+
+/// The fields to output, in order.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FieldSelection(Vec<&'static Field>);
+
+impl FieldSelection {
+    /// Parses a comma separated list of field names, such as `entry_id,full_path,si_created`.
+    pub fn parse(s: &str) -> Result<Self> {
+        let fields = s
+            .split(',')
+            .map(str::trim)
+            .filter(|name| !name.is_empty())
+            .map(|name| {
+                find_field(name).ok_or_else(|| Error::InvalidFieldSelection {
+                    detail: format!("unknown field `{}`", name),
+                })
+            })
+            .collect::<Result<Vec<_>>>()?;
+
+        if fields.is_empty() {
+            return Err(Error::InvalidFieldSelection {
+                detail: "no fields were given".to_string(),
+            });
+        }
+
+        Ok(FieldSelection(fields))
+    }
+
+    pub fn fields(&self) -> &[&'static Field] {
+        &self.0
+    }
+
+    /// The names of the fields, which make the header of projected CSV output.
+    pub fn header(&self) -> Vec<&'static str> {
+        self.0.iter().map(|field| field.name).collect()
+    }
+
+    pub fn project(&self, row: &FlatMftEntryWithName) -> ProjectedRow {
+        let columns = match serde_json::to_value(row) {
+            Ok(Value::Object(columns)) => columns,
+            _ => unreachable!("a row is serialized as a map"),
+        };
+
+        ProjectedRow(
+            self.0
+                .iter()
+                .map(|field| {
+                    (
+                        field.name,
+                        columns.get(field.column).cloned().unwrap_or(Value::Null),
+                    )
+                })
+                .collect(),
+        )
+    }
+}
+
+/// The selected fields of a row, in order.
+/// Serialized as a map from the field names to their values.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ProjectedRow(Vec<(&'static str, Value)>);
+
+impl ProjectedRow {
+    pub fn get(&self, name: &str) -> Option<&Value> {
+        self.0
+            .iter()
+            .find(|(field, _)| *field == name)
+            .map(|(_, value)| value)
+    }
+
+    /// The values as a CSV record. Missing values are empty.
+    pub fn to_record(&self) -> Vec<String> {
+        self.0
+            .iter()
+            .map(|(_, value)| match value {
+                Value::Null => String::new(),
+                Value::String(s) => s.clone(),
+                value => value.to_string(),
+            })
+            .collect()
+    }
+}
+
+impl Serialize for ProjectedRow {
+    fn serialize<S>(&self, serializer: S) -> ::std::result::Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        let mut map = serializer.serialize_map(Some(self.0.len()))?;
+        for (name, value) in &self.0 {
+            map.serialize_entry(name, value)?;
+        }
+        map.end()
+    }
+}
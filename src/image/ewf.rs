@@ -0,0 +1,372 @@
+//! Expert Witness Format (EWF, `.E01`) images, as produced by EnCase and FTK Imager.
+//!
+//! The image is stored as a sequence of chunks (usually 32KB), each of which may be zlib compressed.
+//! Chunk offsets are found in the `table` sections of each segment file.
+//! <https://github.com/libyal/libewf/blob/main/documentation/Expert%20Witness%20Compression%20Format%20(EWF).asciidoc>
+use crate::err::{Error, Result};
+use crate::utils::seek_position;
+
+use byteorder::{LittleEndian, ReadBytesExt};
+use flate2::read::ZlibDecoder;
+use log::{debug, trace};
+use lru::LruCache;
+
+use std::fs::File;
+use std::io::{self, BufReader, Read, Seek, SeekFrom};
+use std::num::NonZeroUsize;
+use std::path::{Path, PathBuf};
+
+pub const EWF_SIGNATURE: &[u8; 8] = b"EVF\x09\x0d\x0a\xff\x00";
+
+const FILE_HEADER_SIZE: u64 = 13;
+const SECTION_DESCRIPTOR_SIZE: u64 = 76;
+const TABLE_HEADER_SIZE: u64 = 24;
+
+/// The most significant bit of a table entry marks a compressed chunk.
+const COMPRESSED_CHUNK_FLAG: u32 = 0x8000_0000;
+
+/// Uncompressed chunks are followed by an adler32 checksum.
+const CHUNK_CHECKSUM_SIZE: u64 = 4;
+
+/// Images are usually created with 64 sectors per chunk, anything much larger is likely corrupt.
+const MAX_CHUNK_SIZE: u64 = 16 * 1024 * 1024;
+
+const CHUNK_CACHE_SIZE: usize = 16;
+
+/// A chunk of the image, inside one of the segment files.
+#[derive(Debug, Clone)]
+struct Chunk {
+    segment: usize,
+    offset: u64,
+    /// The size of the stored chunk, including the checksum for uncompressed chunks.
+    stored_size: u64,
+    compressed: bool,
+}
+
+/// The media information from the `volume` (or `disk`) section.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct EwfMedia {
+    pub chunk_count: u32,
+    pub sectors_per_chunk: u32,
+    pub bytes_per_sector: u32,
+    pub sector_count: u64,
+}
+
+impl EwfMedia {
+    pub fn chunk_size(&self) -> u64 {
+        u64::from(self.sectors_per_chunk) * u64::from(self.bytes_per_sector)
+    }
+
+    /// The size of the imaged media, in bytes.
+    pub fn media_size(&self) -> u64 {
+        self.sector_count
+            .saturating_mul(u64::from(self.bytes_per_sector))
+    }
+}
+
+/// Presents the media stored in an EWF image as a contiguous stream.
+pub struct EwfReader<R: Read + Seek = BufReader<File>> {
+    segments: Vec<R>,
+    media: EwfMedia,
+    chunks: Vec<Chunk>,
+    chunk_cache: LruCache<usize, Vec<u8>>,
+    position: u64,
+}
+
+impl EwfReader<BufReader<File>> {
+    /// Opens an image from the path of its first segment (`.E01`).
+    /// The following segments (`.E02` ... `.E99`, `.EAA` ...) are found next to it.
+    pub fn open(first_segment: impl AsRef<Path>) -> Result<Self> {
+        let segments = segment_paths(first_segment.as_ref())
+            .iter()
+            .map(|path| {
+                File::open(path)
+                    .map(BufReader::new)
+                    .map_err(|e| Error::failed_to_open_file(path, e))
+            })
+            .collect::<Result<Vec<_>>>()?;
+
+        debug!("Found {} EWF segments", segments.len());
+
+        Self::new(segments)
+    }
+}
+
+impl<R: Read + Seek> EwfReader<R> {
+    /// Reads the image from its segment files, in order.
+    pub fn new(mut segments: Vec<R>) -> Result<Self> {
+        let mut media = None;
+        let mut chunks = vec![];
+
+        for (index, segment) in segments.iter_mut().enumerate() {
+            read_segment(segment, index, &mut media, &mut chunks)?;
+        }
+
+        let media = media.ok_or_else(|| invalid("missing volume section".to_string()))?;
+
+        let chunk_size = media.chunk_size();
+        if chunk_size == 0 || chunk_size > MAX_CHUNK_SIZE {
+            return Err(invalid(format!("bad chunk size {}", chunk_size)));
+        }
+
+        let expected_chunks = media.media_size().div_ceil(chunk_size);
+        if (chunks.len() as u64) < expected_chunks {
+            return Err(invalid(format!(
+                "image has {} chunks, but the media needs {} (missing segments?)",
+                chunks.len(),
+                expected_chunks
+            )));
+        }
+
+        Ok(EwfReader {
+            segments,
+            media,
+            chunks,
+            chunk_cache: LruCache::new(NonZeroUsize::new(CHUNK_CACHE_SIZE).expect("16 > 0")),
+            position: 0,
+        })
+    }
+
+    pub fn media(&self) -> &EwfMedia {
+        &self.media
+    }
+
+    /// The size of the imaged media, in bytes.
+    pub fn len(&self) -> u64 {
+        self.media.media_size()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    fn read_chunk(&mut self, index: usize) -> io::Result<&[u8]> {
+        if !self.chunk_cache.contains(&index) {
+            let chunk = self.chunks[index].clone();
+            let chunk_size = self.media.chunk_size() as usize;
+            trace!("Reading EWF chunk {} {:?}", index, chunk);
+
+            let segment = &mut self.segments[chunk.segment];
+            segment.seek(SeekFrom::Start(chunk.offset))?;
+
+            let mut data = Vec::with_capacity(chunk_size);
+            if chunk.compressed {
+                ZlibDecoder::new(segment.take(chunk.stored_size))
+                    .take(chunk_size as u64)
+                    .read_to_end(&mut data)?;
+            } else {
+                let size = chunk.stored_size.saturating_sub(CHUNK_CHECKSUM_SIZE);
+                segment
+                    .take(size.min(chunk_size as u64))
+                    .read_to_end(&mut data)?;
+            }
+
+            // Short chunks only occur at the end of the media, the rest reads as zeros.
+            data.resize(chunk_size, 0);
+            self.chunk_cache.put(index, data);
+        }
+
+        Ok(self
+            .chunk_cache
+            .get(&index)
+            .expect("Chunk was inserted above")
+            .as_slice())
+    }
+}
+
+impl<R: Read + Seek> Read for EwfReader<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let size = self.len();
+        if self.position >= size || buf.is_empty() {
+            return Ok(0);
+        }
+
+        let chunk_size = self.media.chunk_size();
+        let index = (self.position / chunk_size) as usize;
+        let offset_in_chunk = (self.position % chunk_size) as usize;
+        let available = (size - self.position).min(chunk_size - offset_in_chunk as u64) as usize;
+        let to_read = buf.len().min(available);
+
+        let chunk = self.read_chunk(index)?;
+        buf[..to_read].copy_from_slice(&chunk[offset_in_chunk..offset_in_chunk + to_read]);
+
+        self.position += to_read as u64;
+        Ok(to_read)
+    }
+}
+
+impl<R: Read + Seek> Seek for EwfReader<R> {
+    fn seek(&mut self, pos: SeekFrom) -> io::Result<u64> {
+        self.position = seek_position(self.position, self.len(), pos)?;
+        Ok(self.position)
+    }
+}
+
+fn invalid(detail: String) -> Error {
+    Error::InvalidImage {
+        format: "EWF",
+        detail,
+    }
+}
+
+/// Walks the sections of a single segment file, collecting media information and chunks.
+fn read_segment<R: Read + Seek>(
+    segment: &mut R,
+    segment_index: usize,
+    media: &mut Option<EwfMedia>,
+    chunks: &mut Vec<Chunk>,
+) -> Result<()> {
+    let segment_size = segment.seek(SeekFrom::End(0))?;
+    segment.rewind()?;
+
+    let mut signature = [0; 8];
+    segment.read_exact(&mut signature)?;
+    if &signature != EWF_SIGNATURE {
+        return Err(invalid(format!(
+            "segment {} has a bad signature {:x?}",
+            segment_index + 1,
+            signature
+        )));
+    }
+
+    // The end of the last `sectors` section, which is also the end of the last chunk of a table.
+    let mut sectors_end = None;
+    let mut offset = FILE_HEADER_SIZE;
+
+    while offset + SECTION_DESCRIPTOR_SIZE <= segment_size {
+        segment.seek(SeekFrom::Start(offset))?;
+
+        let mut section_type = [0; 16];
+        segment.read_exact(&mut section_type)?;
+        let next_offset = segment.read_u64::<LittleEndian>()?;
+        let section_size = segment.read_u64::<LittleEndian>()?;
+
+        let section_type = section_type.split(|&b| b == 0).next().unwrap_or_default();
+        let data_offset = offset + SECTION_DESCRIPTOR_SIZE;
+        trace!(
+            "EWF section `{}` at {}, size {}",
+            String::from_utf8_lossy(section_type),
+            offset,
+            section_size
+        );
+
+        segment.seek(SeekFrom::Start(data_offset))?;
+        match section_type {
+            b"volume" | b"disk" => {
+                let _media_type = segment.read_u32::<LittleEndian>()?;
+                media.get_or_insert(EwfMedia {
+                    chunk_count: segment.read_u32::<LittleEndian>()?,
+                    sectors_per_chunk: segment.read_u32::<LittleEndian>()?,
+                    bytes_per_sector: segment.read_u32::<LittleEndian>()?,
+                    sector_count: segment.read_u64::<LittleEndian>()?,
+                });
+            }
+            b"sectors" => sectors_end = Some(offset.saturating_add(section_size)),
+            b"table" => {
+                let table_end = sectors_end.unwrap_or(offset);
+                read_table(segment, segment_index, table_end, chunks)?;
+            }
+            b"next" | b"done" => break,
+            // `table2` is a copy of `table`, and the rest hold metadata we don't need.
+            _ => {}
+        }
+
+        // The last section points to itself.
+        if next_offset <= offset {
+            break;
+        }
+
+        offset = next_offset;
+    }
+
+    Ok(())
+}
+
+/// Reads the chunk offsets of a `table` section.
+/// The stream should be positioned at the start of the section data.
+fn read_table<R: Read + Seek>(
+    segment: &mut R,
+    segment_index: usize,
+    table_end: u64,
+    chunks: &mut Vec<Chunk>,
+) -> Result<()> {
+    let entry_count = segment.read_u32::<LittleEndian>()?;
+    let _padding = segment.read_u32::<LittleEndian>()?;
+    let base_offset = segment.read_u64::<LittleEndian>()?;
+    segment.seek(SeekFrom::Current(TABLE_HEADER_SIZE as i64 - 16))?;
+
+    let mut entries = Vec::with_capacity((entry_count as usize).min(65536));
+    for _ in 0..entry_count {
+        let entry = segment.read_u32::<LittleEndian>()?;
+        let offset = base_offset
+            .checked_add(u64::from(entry & !COMPRESSED_CHUNK_FLAG))
+            .ok_or_else(|| invalid(format!("chunk offset past base offset {}", base_offset)))?;
+        entries.push((offset, entry & COMPRESSED_CHUNK_FLAG != 0));
+    }
+
+    for (i, &(offset, compressed)) in entries.iter().enumerate() {
+        let end = match entries.get(i + 1) {
+            Some(&(next_offset, _)) => next_offset,
+            None => table_end,
+        };
+
+        chunks.push(Chunk {
+            segment: segment_index,
+            offset,
+            stored_size: end.saturating_sub(offset),
+            compressed,
+        });
+    }
+
+    Ok(())
+}
+
+/// Returns the extension of the nth segment, starting from 1 (`E01`).
+/// After `E99` the extensions continue with `EAA`, `EAB`, ... `EZZ`, `FAA` ...
+pub fn segment_extension(number: u32, first_letter: u8) -> String {
+    if number < 100 {
+        return format!("{}{:02}", first_letter as char, number);
+    }
+
+    let index = number - 100;
+    let letters = [
+        first_letter + (index / (26 * 26)) as u8,
+        b'A' + (index / 26 % 26) as u8,
+        b'A' + (index % 26) as u8,
+    ];
+
+    String::from_utf8_lossy(&letters).to_string()
+}
+
+fn segment_paths(first_segment: &Path) -> Vec<PathBuf> {
+    let extension = first_segment
+        .extension()
+        .and_then(|extension| extension.to_str())
+        .unwrap_or_default();
+
+    // Only images following the `.E01` naming scheme can be followed to the next segment.
+    let first_letter = match extension.as_bytes() {
+        [letter, b'0', b'1'] if letter.is_ascii_alphabetic() => letter.to_ascii_uppercase(),
+        _ => return vec![first_segment.to_path_buf()],
+    };
+    let lowercase = extension.as_bytes()[0].is_ascii_lowercase();
+
+    let mut paths = vec![first_segment.to_path_buf()];
+
+    // Segment names run out at `ZZZ`.
+    for number in 2..(100 + (b'Z' - first_letter + 1) as u32 * 26 * 26) {
+        let mut extension = segment_extension(number, first_letter);
+        if lowercase {
+            extension.make_ascii_lowercase();
+        }
+
+        let path = first_segment.with_extension(extension);
+        if !path.is_file() {
+            break;
+        }
+
+        paths.push(path);
+    }
+
+    paths
+}
@@ -0,0 +1,90 @@
+//! Readers for forensic image formats, which present the imaged media as a single `Read + Seek`.
+//!
+//! The media may be a whole disk, a single volume, or a loose MFT file.
+//! `MftParser::from_image_path` takes care of locating the MFT inside it.
+use crate::err::Result;
+use crate::ReadSeek;
+
+use log::debug;
+
+use std::fs::File;
+use std::io::{BufReader, Read};
+use std::path::Path;
+
+pub mod ewf;
+pub mod split;
+pub mod vhd;
+pub mod vhdx;
+
+pub use ewf::EwfReader;
+pub use split::SplitRawReader;
+pub use vhd::VhdReader;
+pub use vhdx::VhdxReader;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ImageFormat {
+    /// Expert Witness Format (`.E01`).
+    Ewf,
+    /// Virtual PC / Hyper-V virtual disk (`.vhd`).
+    Vhd,
+    /// Hyper-V virtual disk (`.vhdx`).
+    Vhdx,
+    /// A raw image split into numbered segments (`.001`, `.002`, ...).
+    SplitRaw,
+    /// Anything else is read as-is (after decompression, with the `compression` feature).
+    Raw,
+}
+
+impl ImageFormat {
+    /// Detects the image format from the path of the first segment, and its first bytes.
+    pub fn detect(path: &Path, magic: &[u8]) -> Self {
+        // Fixed VHDs only have a footer, so they can only be told apart by their extension.
+        let is_vhd = path
+            .extension()
+            .is_some_and(|extension| extension.eq_ignore_ascii_case("vhd"));
+
+        if magic.starts_with(ewf::EWF_SIGNATURE) {
+            ImageFormat::Ewf
+        } else if magic.starts_with(vhdx::VHDX_SIGNATURE) {
+            ImageFormat::Vhdx
+        } else if magic.starts_with(vhd::VHD_FOOTER_COOKIE) || is_vhd {
+            ImageFormat::Vhd
+        } else if split::is_split_segment(path) {
+            ImageFormat::SplitRaw
+        } else {
+            ImageFormat::Raw
+        }
+    }
+}
+
+/// Opens an image (or a loose MFT file), detecting its format.
+pub fn open(path: impl AsRef<Path>) -> Result<Box<dyn ReadSeek>> {
+    let path = path.as_ref();
+
+    let mut magic = Vec::with_capacity(8);
+    // Paths to archive members don't exist, and are handled by `container::open`.
+    if let Ok(file) = File::open(path) {
+        file.take(8).read_to_end(&mut magic)?;
+    }
+
+    let format = ImageFormat::detect(path, &magic);
+    debug!("Opening {} as {:?} image", path.display(), format);
+
+    Ok(match format {
+        ImageFormat::Ewf => Box::new(EwfReader::open(path)?),
+        ImageFormat::Vhd => Box::new(VhdReader::open(path)?),
+        ImageFormat::Vhdx => Box::new(VhdxReader::open(path)?),
+        ImageFormat::SplitRaw => Box::new(SplitRawReader::open(path)?),
+        ImageFormat::Raw => Box::new(BufReader::with_capacity(4096, open_raw(path)?)),
+    })
+}
+
+#[cfg(feature = "compression")]
+fn open_raw(path: &Path) -> Result<File> {
+    crate::container::open(path)
+}
+
+#[cfg(not(feature = "compression"))]
+fn open_raw(path: &Path) -> Result<File> {
+    File::open(path).map_err(|e| crate::err::Error::failed_to_open_file(path, e))
+}
@@ -0,0 +1,156 @@
+//! Raw (`dd`) images which were split into numbered segments, such as `disk.001`, `disk.002`, ...
+use crate::err::{Error, Result};
+use crate::utils::seek_position;
+
+use log::debug;
+
+use std::fs::File;
+use std::io::{self, Read, Seek, SeekFrom};
+use std::path::{Path, PathBuf};
+
+/// A single segment of the image, and its position in the image.
+struct Segment<R> {
+    data: R,
+    start: u64,
+    length: u64,
+}
+
+/// Presents the segments of a split raw image as a single contiguous stream.
+pub struct SplitRawReader<R: Read + Seek = File> {
+    segments: Vec<Segment<R>>,
+    size: u64,
+    position: u64,
+}
+
+impl SplitRawReader<File> {
+    /// Opens a split image from the path of its first segment.
+    /// The following segments are found by incrementing the numeric extension (`.001` -> `.002`),
+    /// until a segment is missing.
+    pub fn open(first_segment: impl AsRef<Path>) -> Result<Self> {
+        let paths = segment_paths(first_segment.as_ref())?;
+
+        let segments = paths
+            .iter()
+            .map(|path| File::open(path).map_err(|e| Error::failed_to_open_file(path, e)))
+            .collect::<Result<Vec<File>>>()?;
+
+        debug!("Found {} raw image segments", segments.len());
+
+        Ok(Self::new(segments)?)
+    }
+}
+
+impl<R: Read + Seek> SplitRawReader<R> {
+    /// Concatenates the given segments, in order.
+    pub fn new(segments: Vec<R>) -> io::Result<Self> {
+        let mut start = 0;
+        let mut mapped = Vec::with_capacity(segments.len());
+
+        for mut data in segments {
+            let length = data.seek(SeekFrom::End(0))?;
+
+            mapped.push(Segment {
+                data,
+                start,
+                length,
+            });
+            start += length;
+        }
+
+        Ok(SplitRawReader {
+            segments: mapped,
+            size: start,
+            position: 0,
+        })
+    }
+
+    /// The total size of the image, in bytes.
+    pub fn len(&self) -> u64 {
+        self.size
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.size == 0
+    }
+}
+
+impl<R: Read + Seek> Read for SplitRawReader<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        if self.position >= self.size || buf.is_empty() {
+            return Ok(0);
+        }
+
+        let position = self.position;
+        // Empty segments are skipped, since they can never contain the position.
+        let index = self
+            .segments
+            .partition_point(|segment| segment.start + segment.length <= position);
+
+        let segment = match self.segments.get_mut(index) {
+            Some(segment) => segment,
+            None => return Ok(0),
+        };
+
+        let offset_in_segment = position - segment.start;
+        let to_read = (buf.len() as u64).min(segment.length - offset_in_segment) as usize;
+
+        segment.data.seek(SeekFrom::Start(offset_in_segment))?;
+        let read = segment.data.read(&mut buf[..to_read])?;
+
+        self.position += read as u64;
+        Ok(read)
+    }
+}
+
+impl<R: Read + Seek> Seek for SplitRawReader<R> {
+    fn seek(&mut self, pos: SeekFrom) -> io::Result<u64> {
+        self.position = seek_position(self.position, self.size, pos)?;
+        Ok(self.position)
+    }
+}
+
+/// Returns true if the path looks like a segment of a split image (a numeric extension).
+pub fn is_split_segment(path: &Path) -> bool {
+    path.extension()
+        .and_then(|extension| extension.to_str())
+        .is_some_and(|extension| {
+            extension.len() >= 3 && extension.bytes().all(|b| b.is_ascii_digit())
+        })
+}
+
+fn segment_paths(first_segment: &Path) -> Result<Vec<PathBuf>> {
+    let invalid = |detail: String| Error::InvalidImage {
+        format: "split raw",
+        detail,
+    };
+
+    let extension = first_segment
+        .extension()
+        .and_then(|extension| extension.to_str())
+        .filter(|_| is_split_segment(first_segment))
+        .ok_or_else(|| {
+            invalid(format!(
+                "`{}` does not have a numeric extension",
+                first_segment.display()
+            ))
+        })?;
+
+    let width = extension.len();
+    let first: u64 = extension
+        .parse()
+        .map_err(|_| invalid(format!("bad segment number `{}`", extension)))?;
+
+    let mut paths = vec![first_segment.to_path_buf()];
+
+    for number in first + 1.. {
+        let path = first_segment.with_extension(format!("{:0width$}", number, width = width));
+
+        if !path.is_file() {
+            break;
+        }
+
+        paths.push(path);
+    }
+
+    Ok(paths)
+}
@@ -0,0 +1,293 @@
+//! Virtual PC / Hyper-V virtual hard disks (`.vhd`), in their fixed and dynamic variants.
+//! <https://learn.microsoft.com/en-us/windows/win32/vstor/about-vhd>
+use crate::err::{Error, Result};
+use crate::utils::seek_position;
+
+use byteorder::{BigEndian, ReadBytesExt};
+use log::debug;
+
+use std::fs::File;
+use std::io::{self, BufReader, Read, Seek, SeekFrom};
+use std::path::Path;
+
+pub const VHD_FOOTER_COOKIE: &[u8; 8] = b"conectix";
+const DYNAMIC_HEADER_COOKIE: &[u8; 8] = b"cxsparse";
+
+const FOOTER_SIZE: u64 = 512;
+const SECTOR_SIZE: u64 = 512;
+/// Unallocated blocks read as zeros.
+const UNALLOCATED_BLOCK: u32 = 0xFFFF_FFFF;
+
+/// Dynamic disks default to 2MB blocks, anything much larger is likely corrupt.
+const MAX_BLOCK_SIZE: u32 = 256 * 1024 * 1024;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum VhdDiskType {
+    Fixed,
+    Dynamic,
+    Differencing,
+}
+
+#[derive(Debug)]
+enum Layout {
+    Fixed,
+    Dynamic {
+        block_size: u64,
+        /// Sector offsets of each block, in the file.
+        block_allocation_table: Vec<u32>,
+        /// Size of the sector bitmap which precedes each block, in bytes (padded to a sector).
+        bitmap_size: u64,
+        /// The sector bitmap of the last block which was read.
+        cached_bitmap: Option<(u64, Vec<u8>)>,
+    },
+}
+
+/// Presents the disk stored in a VHD file as a contiguous stream.
+///
+/// Differencing disks depend on their parent, and are not supported.
+pub struct VhdReader<R: Read + Seek = BufReader<File>> {
+    data: R,
+    disk_type: VhdDiskType,
+    layout: Layout,
+    size: u64,
+    position: u64,
+}
+
+impl VhdReader<BufReader<File>> {
+    pub fn open(path: impl AsRef<Path>) -> Result<Self> {
+        let path = path.as_ref();
+        let file = File::open(path).map_err(|e| Error::failed_to_open_file(path, e))?;
+
+        Self::new(BufReader::new(file))
+    }
+}
+
+impl<R: Read + Seek> VhdReader<R> {
+    pub fn new(mut data: R) -> Result<Self> {
+        let file_size = data.seek(SeekFrom::End(0))?;
+        if file_size < FOOTER_SIZE {
+            return Err(invalid("file is too small to hold a footer".to_string()));
+        }
+
+        // The footer is at the end of the file, dynamic disks keep a copy at the start.
+        let mut footer = [0; FOOTER_SIZE as usize];
+        data.seek(SeekFrom::Start(file_size - FOOTER_SIZE))?;
+        data.read_exact(&mut footer)?;
+
+        if &footer[..8] != VHD_FOOTER_COOKIE {
+            data.rewind()?;
+            data.read_exact(&mut footer)?;
+
+            if &footer[..8] != VHD_FOOTER_COOKIE {
+                return Err(invalid("missing `conectix` footer".to_string()));
+            }
+        }
+
+        let mut reader = &footer[16..];
+        let data_offset = reader.read_u64::<BigEndian>()?;
+        let mut reader = &footer[48..];
+        let size = reader.read_u64::<BigEndian>()?;
+        let mut reader = &footer[60..];
+        let disk_type = match reader.read_u32::<BigEndian>()? {
+            2 => VhdDiskType::Fixed,
+            3 => VhdDiskType::Dynamic,
+            4 => VhdDiskType::Differencing,
+            other => return Err(invalid(format!("unknown disk type {}", other))),
+        };
+
+        debug!("VHD {:?} disk of size {}", disk_type, size);
+
+        let layout = match disk_type {
+            VhdDiskType::Fixed => {
+                if size > file_size - FOOTER_SIZE {
+                    return Err(invalid(format!(
+                        "fixed disk of size {} is larger than the file",
+                        size
+                    )));
+                }
+
+                Layout::Fixed
+            }
+            VhdDiskType::Dynamic => {
+                Self::read_dynamic_layout(&mut data, data_offset, size, file_size)?
+            }
+            VhdDiskType::Differencing => {
+                return Err(invalid(
+                    "differencing disks require their parent, which is not supported".to_string(),
+                ))
+            }
+        };
+
+        Ok(VhdReader {
+            data,
+            disk_type,
+            layout,
+            size,
+            position: 0,
+        })
+    }
+
+    fn read_dynamic_layout(
+        data: &mut R,
+        header_offset: u64,
+        size: u64,
+        file_size: u64,
+    ) -> Result<Layout> {
+        let mut header = [0; 1024];
+        data.seek(SeekFrom::Start(header_offset))?;
+        data.read_exact(&mut header)?;
+
+        if &header[..8] != DYNAMIC_HEADER_COOKIE {
+            return Err(invalid(
+                "missing `cxsparse` dynamic disk header".to_string(),
+            ));
+        }
+
+        let mut reader = &header[16..];
+        let table_offset = reader.read_u64::<BigEndian>()?;
+        let _header_version = reader.read_u32::<BigEndian>()?;
+        let max_table_entries = reader.read_u32::<BigEndian>()?;
+        let block_size = reader.read_u32::<BigEndian>()?;
+
+        if block_size == 0
+            || block_size > MAX_BLOCK_SIZE
+            || u64::from(block_size) % SECTOR_SIZE != 0
+        {
+            return Err(invalid(format!("bad block size {}", block_size)));
+        }
+
+        let block_size = u64::from(block_size);
+        let needed_entries = size.div_ceil(block_size);
+        if u64::from(max_table_entries) < needed_entries {
+            return Err(invalid(format!(
+                "block allocation table has {} entries, but the disk needs {}",
+                max_table_entries, needed_entries
+            )));
+        }
+
+        // The table is allocated up front, so it must fit in the file.
+        let table_fits = needed_entries
+            .checked_mul(4)
+            .and_then(|table_size| table_offset.checked_add(table_size))
+            .is_some_and(|table_end| table_end <= file_size);
+        if !table_fits {
+            return Err(invalid(format!(
+                "block allocation table of {} entries at offset {} is outside of the file",
+                needed_entries, table_offset
+            )));
+        }
+
+        data.seek(SeekFrom::Start(table_offset))?;
+        let mut block_allocation_table = Vec::with_capacity(needed_entries as usize);
+        for _ in 0..needed_entries {
+            block_allocation_table.push(data.read_u32::<BigEndian>()?);
+        }
+
+        // One bit per sector, padded to a whole sector.
+        let bitmap_bytes = (block_size / SECTOR_SIZE).div_ceil(8);
+        let bitmap_size = bitmap_bytes.div_ceil(SECTOR_SIZE) * SECTOR_SIZE;
+
+        Ok(Layout::Dynamic {
+            block_size,
+            block_allocation_table,
+            bitmap_size,
+            cached_bitmap: None,
+        })
+    }
+
+    pub fn disk_type(&self) -> VhdDiskType {
+        self.disk_type
+    }
+
+    /// The size of the virtual disk, in bytes.
+    pub fn len(&self) -> u64 {
+        self.size
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.size == 0
+    }
+
+    fn advance(&mut self, read: usize) -> io::Result<usize> {
+        self.position += read as u64;
+        Ok(read)
+    }
+}
+
+impl<R: Read + Seek> Read for VhdReader<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        if self.position >= self.size || buf.is_empty() {
+            return Ok(0);
+        }
+
+        let remaining = ((self.size - self.position) as usize).min(buf.len());
+        let buf = &mut buf[..remaining];
+
+        let read = match &mut self.layout {
+            Layout::Fixed => {
+                self.data.seek(SeekFrom::Start(self.position))?;
+                self.data.read(buf)?
+            }
+            Layout::Dynamic {
+                block_size,
+                block_allocation_table,
+                bitmap_size,
+                cached_bitmap,
+            } => {
+                let block = self.position / *block_size;
+                let offset_in_block = self.position % *block_size;
+                let block_sector = block_allocation_table[block as usize];
+
+                let to_read = (buf.len() as u64).min(*block_size - offset_in_block) as usize;
+                let buf = &mut buf[..to_read];
+
+                if block_sector == UNALLOCATED_BLOCK {
+                    buf.fill(0);
+                    return self.advance(to_read);
+                }
+
+                let block_start = u64::from(block_sector) * SECTOR_SIZE;
+
+                if cached_bitmap.as_ref().map(|(cached, _)| *cached) != Some(block) {
+                    let mut bitmap = vec![0; *bitmap_size as usize];
+                    self.data.seek(SeekFrom::Start(block_start))?;
+                    self.data.read_exact(&mut bitmap)?;
+                    *cached_bitmap = Some((block, bitmap));
+                }
+                let (_, bitmap) = cached_bitmap.as_ref().expect("Bitmap was cached above");
+
+                // Sectors which are not marked in the bitmap were never written, and read as zeros.
+                let sector = (offset_in_block / SECTOR_SIZE) as usize;
+                let is_present = bitmap[sector / 8] & (0x80 >> (sector % 8)) != 0;
+                let to_read = to_read.min((SECTOR_SIZE - offset_in_block % SECTOR_SIZE) as usize);
+                let buf = &mut buf[..to_read];
+
+                if is_present {
+                    self.data.seek(SeekFrom::Start(
+                        block_start + *bitmap_size + offset_in_block,
+                    ))?;
+                    self.data.read(buf)?
+                } else {
+                    buf.fill(0);
+                    to_read
+                }
+            }
+        };
+
+        self.advance(read)
+    }
+}
+
+impl<R: Read + Seek> Seek for VhdReader<R> {
+    fn seek(&mut self, pos: SeekFrom) -> io::Result<u64> {
+        self.position = seek_position(self.position, self.size, pos)?;
+        Ok(self.position)
+    }
+}
+
+fn invalid(detail: String) -> Error {
+    Error::InvalidImage {
+        format: "VHD",
+        detail,
+    }
+}
@@ -0,0 +1,371 @@
+//! Hyper-V virtual hard disks (`.vhdx`).
+//! <https://learn.microsoft.com/en-us/openspecs/windows_protocols/ms-vhdx>
+use crate::err::{Error, Result};
+use crate::utils::seek_position;
+
+use byteorder::{LittleEndian, ReadBytesExt};
+use log::{debug, warn};
+
+use std::fs::File;
+use std::io::{self, BufReader, Read, Seek, SeekFrom};
+use std::path::Path;
+
+pub const VHDX_SIGNATURE: &[u8; 8] = b"vhdxfile";
+
+const HEADER_OFFSETS: [u64; 2] = [64 * 1024, 128 * 1024];
+const REGION_TABLE_OFFSET: u64 = 192 * 1024;
+const MAX_REGION_ENTRIES: u32 = 2047;
+const MAX_METADATA_ENTRIES: u16 = 2047;
+
+// Region and metadata item GUIDs, as stored on disk.
+/// `2DC27766-F623-4200-9D64-115E9BFD4A08`
+const BAT_REGION: [u8; 16] = [
+    0x66, 0x77, 0xC2, 0x2D, 0x23, 0xF6, 0x00, 0x42, 0x9D, 0x64, 0x11, 0x5E, 0x9B, 0xFD, 0x4A, 0x08,
+];
+/// `8B7CA206-4790-4B9A-B8FE-575F050F886E`
+const METADATA_REGION: [u8; 16] = [
+    0x06, 0xA2, 0x7C, 0x8B, 0x90, 0x47, 0x9A, 0x4B, 0xB8, 0xFE, 0x57, 0x5F, 0x05, 0x0F, 0x88, 0x6E,
+];
+/// `CAA16737-FA36-4D43-B3B6-33F0AA44E76B`
+const FILE_PARAMETERS: [u8; 16] = [
+    0x37, 0x67, 0xA1, 0xCA, 0x36, 0xFA, 0x43, 0x4D, 0xB3, 0xB6, 0x33, 0xF0, 0xAA, 0x44, 0xE7, 0x6B,
+];
+/// `2FA54224-CD1B-4876-B211-5DBED83BF4B8`
+const VIRTUAL_DISK_SIZE: [u8; 16] = [
+    0x24, 0x42, 0xA5, 0x2F, 0x1B, 0xCD, 0x76, 0x48, 0xB2, 0x11, 0x5D, 0xBE, 0xD8, 0x3B, 0xF4, 0xB8,
+];
+/// `8141BF1D-A96F-4709-BA47-F233A8FAAB5F`
+const LOGICAL_SECTOR_SIZE: [u8; 16] = [
+    0x1D, 0xBF, 0x41, 0x81, 0x6F, 0xA9, 0x09, 0x47, 0xBA, 0x47, 0xF2, 0x33, 0xA8, 0xFA, 0xAB, 0x5F,
+];
+
+const HAS_PARENT_FLAG: u32 = 0x2;
+
+/// Payload blocks in these states hold data, the rest read as zeros.
+const PAYLOAD_BLOCK_FULLY_PRESENT: u64 = 6;
+const PAYLOAD_BLOCK_PARTIALLY_PRESENT: u64 = 7;
+const BAT_STATE_MASK: u64 = 0x7;
+/// File offsets in the BAT are in units of 1MB, stored in the top 44 bits.
+const BAT_OFFSET_SHIFT: u64 = 20;
+
+/// The size of the area covered by each sector bitmap block, used to find the BAT interleaving.
+const SECTOR_BITMAP_COVERAGE: u64 = 1 << 23;
+
+const MIN_BLOCK_SIZE: u32 = 1024 * 1024;
+const MAX_BLOCK_SIZE: u32 = 256 * 1024 * 1024;
+
+/// Presents the disk stored in a VHDX file as a contiguous stream.
+///
+/// Differencing disks depend on their parent, and are not supported.
+/// Checksums are not verified, and the log is not replayed.
+pub struct VhdxReader<R: Read + Seek = BufReader<File>> {
+    data: R,
+    block_size: u64,
+    logical_sector_size: u32,
+    /// The payload block entries of the BAT (without the interleaved sector bitmap entries).
+    payload_blocks: Vec<u64>,
+    size: u64,
+    position: u64,
+}
+
+#[derive(Debug, Clone, Copy)]
+struct Region {
+    offset: u64,
+    length: u32,
+}
+
+impl VhdxReader<BufReader<File>> {
+    pub fn open(path: impl AsRef<Path>) -> Result<Self> {
+        let path = path.as_ref();
+        let file = File::open(path).map_err(|e| Error::failed_to_open_file(path, e))?;
+
+        Self::new(BufReader::new(file))
+    }
+}
+
+impl<R: Read + Seek> VhdxReader<R> {
+    pub fn new(mut data: R) -> Result<Self> {
+        let mut signature = [0; 8];
+        data.rewind()?;
+        data.read_exact(&mut signature)?;
+
+        if &signature != VHDX_SIGNATURE {
+            return Err(invalid("missing `vhdxfile` signature".to_string()));
+        }
+
+        Self::check_log(&mut data)?;
+
+        let (bat_region, metadata_region) = Self::read_region_table(&mut data)?;
+        let (block_size, has_parent, size, logical_sector_size) =
+            Self::read_metadata(&mut data, metadata_region)?;
+
+        if has_parent {
+            return Err(invalid(
+                "differencing disks require their parent, which is not supported".to_string(),
+            ));
+        }
+
+        debug!(
+            "VHDX disk of size {}, block size {}, sector size {}",
+            size, block_size, logical_sector_size
+        );
+
+        // A sector bitmap entry follows every `chunk_ratio` payload entries.
+        let block_size = u64::from(block_size);
+        let chunk_ratio = (SECTOR_BITMAP_COVERAGE * u64::from(logical_sector_size)) / block_size;
+        let payload_block_count = size.div_ceil(block_size);
+
+        if chunk_ratio == 0 {
+            return Err(invalid(format!(
+                "bad block size {} for sector size {}",
+                block_size, logical_sector_size
+            )));
+        }
+
+        let bat_entries = payload_block_count + payload_block_count.saturating_sub(1) / chunk_ratio;
+        if bat_entries * 8 > u64::from(bat_region.length) {
+            return Err(invalid(format!(
+                "BAT region of size {} cannot hold {} entries",
+                bat_region.length, bat_entries
+            )));
+        }
+
+        // The table is allocated up front, so it must fit in the file.
+        let file_size = data.seek(SeekFrom::End(0))?;
+        let table_fits = bat_region
+            .offset
+            .checked_add(bat_entries * 8)
+            .is_some_and(|table_end| table_end <= file_size);
+        if !table_fits {
+            return Err(invalid(format!(
+                "BAT of {} entries at offset {} is outside of the file",
+                bat_entries, bat_region.offset
+            )));
+        }
+
+        data.seek(SeekFrom::Start(bat_region.offset))?;
+        let mut payload_blocks = Vec::with_capacity(payload_block_count as usize);
+        for i in 0..bat_entries {
+            let entry = data.read_u64::<LittleEndian>()?;
+
+            if (i + 1) % (chunk_ratio + 1) != 0 {
+                payload_blocks.push(entry);
+            }
+        }
+
+        Ok(VhdxReader {
+            data,
+            block_size,
+            logical_sector_size,
+            payload_blocks,
+            size,
+            position: 0,
+        })
+    }
+
+    /// The size of the virtual disk, in bytes.
+    pub fn len(&self) -> u64 {
+        self.size
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.size == 0
+    }
+
+    pub fn logical_sector_size(&self) -> u32 {
+        self.logical_sector_size
+    }
+
+    /// Warns if the current header has a log, which may hold writes which were not flushed to the disk.
+    fn check_log(data: &mut R) -> Result<()> {
+        let mut current: Option<(u64, [u8; 16])> = None;
+
+        for offset in HEADER_OFFSETS {
+            let mut header = [0; 64];
+            data.seek(SeekFrom::Start(offset))?;
+            data.read_exact(&mut header)?;
+
+            if &header[..4] != b"head" {
+                continue;
+            }
+
+            let sequence_number = (&header[8..16]).read_u64::<LittleEndian>()?;
+            let mut log_guid = [0; 16];
+            log_guid.copy_from_slice(&header[48..64]);
+
+            let newer = current.map_or(true, |(sequence, _)| sequence_number > sequence);
+            if newer {
+                current = Some((sequence_number, log_guid));
+            }
+        }
+
+        match current {
+            None => Err(invalid("no valid header".to_string())),
+            Some((_, log_guid)) => {
+                if log_guid != [0; 16] {
+                    warn!("VHDX has a pending log which was not replayed, recent writes may be missing");
+                }
+                Ok(())
+            }
+        }
+    }
+
+    fn read_region_table(data: &mut R) -> Result<(Region, Region)> {
+        let mut header = [0; 16];
+        data.seek(SeekFrom::Start(REGION_TABLE_OFFSET))?;
+        data.read_exact(&mut header)?;
+
+        if &header[..4] != b"regi" {
+            return Err(invalid("missing region table".to_string()));
+        }
+
+        let entry_count = (&header[8..12]).read_u32::<LittleEndian>()?;
+        if entry_count > MAX_REGION_ENTRIES {
+            return Err(invalid(format!("region table has {} entries", entry_count)));
+        }
+
+        let mut bat = None;
+        let mut metadata = None;
+
+        for _ in 0..entry_count {
+            let mut guid = [0; 16];
+            data.read_exact(&mut guid)?;
+            let region = Region {
+                offset: data.read_u64::<LittleEndian>()?,
+                length: data.read_u32::<LittleEndian>()?,
+            };
+            let _required = data.read_u32::<LittleEndian>()?;
+
+            match guid {
+                BAT_REGION => bat = Some(region),
+                METADATA_REGION => metadata = Some(region),
+                _ => {}
+            }
+        }
+
+        match (bat, metadata) {
+            (Some(bat), Some(metadata)) => Ok((bat, metadata)),
+            _ => Err(invalid("missing BAT or metadata region".to_string())),
+        }
+    }
+
+    /// Returns the block size, whether the disk has a parent, the disk size and the logical sector size.
+    fn read_metadata(data: &mut R, region: Region) -> Result<(u32, bool, u64, u32)> {
+        let mut header = [0; 32];
+        data.seek(SeekFrom::Start(region.offset))?;
+        data.read_exact(&mut header)?;
+
+        if &header[..8] != b"metadata" {
+            return Err(invalid("missing metadata table".to_string()));
+        }
+
+        let entry_count = (&header[10..12]).read_u16::<LittleEndian>()?;
+        if entry_count > MAX_METADATA_ENTRIES {
+            return Err(invalid(format!(
+                "metadata table has {} entries",
+                entry_count
+            )));
+        }
+
+        let mut items = vec![];
+        for _ in 0..entry_count {
+            let mut item_id = [0; 16];
+            data.read_exact(&mut item_id)?;
+            let offset = data.read_u32::<LittleEndian>()?;
+            let _length = data.read_u32::<LittleEndian>()?;
+            let _flags = data.read_u32::<LittleEndian>()?;
+            let _reserved = data.read_u32::<LittleEndian>()?;
+
+            let item_offset = region
+                .offset
+                .checked_add(u64::from(offset))
+                .ok_or_else(|| invalid(format!("metadata item at offset {} overflows", offset)))?;
+            items.push((item_id, item_offset));
+        }
+
+        let item_offset = |id: [u8; 16], name: &str| -> Result<SeekFrom> {
+            items
+                .iter()
+                .find(|(item_id, _)| *item_id == id)
+                .map(|(_, offset)| SeekFrom::Start(*offset))
+                .ok_or_else(|| invalid(format!("missing {} metadata item", name)))
+        };
+
+        data.seek(item_offset(FILE_PARAMETERS, "file parameters")?)?;
+        let block_size = data.read_u32::<LittleEndian>()?;
+        let flags = data.read_u32::<LittleEndian>()?;
+
+        data.seek(item_offset(VIRTUAL_DISK_SIZE, "virtual disk size")?)?;
+        let size = data.read_u64::<LittleEndian>()?;
+
+        data.seek(item_offset(LOGICAL_SECTOR_SIZE, "logical sector size")?)?;
+        let logical_sector_size = data.read_u32::<LittleEndian>()?;
+
+        if !(MIN_BLOCK_SIZE..=MAX_BLOCK_SIZE).contains(&block_size) || !block_size.is_power_of_two()
+        {
+            return Err(invalid(format!("bad block size {}", block_size)));
+        }
+
+        if logical_sector_size != 512 && logical_sector_size != 4096 {
+            return Err(invalid(format!(
+                "bad logical sector size {}",
+                logical_sector_size
+            )));
+        }
+
+        Ok((
+            block_size,
+            flags & HAS_PARENT_FLAG != 0,
+            size,
+            logical_sector_size,
+        ))
+    }
+}
+
+impl<R: Read + Seek> Read for VhdxReader<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        if self.position >= self.size || buf.is_empty() {
+            return Ok(0);
+        }
+
+        let block = (self.position / self.block_size) as usize;
+        let offset_in_block = self.position % self.block_size;
+        let to_read = (buf.len() as u64)
+            .min(self.block_size - offset_in_block)
+            .min(self.size - self.position) as usize;
+        let buf = &mut buf[..to_read];
+
+        let entry = self.payload_blocks[block];
+
+        let read = match entry & BAT_STATE_MASK {
+            PAYLOAD_BLOCK_FULLY_PRESENT | PAYLOAD_BLOCK_PARTIALLY_PRESENT => {
+                let block_offset = (entry >> BAT_OFFSET_SHIFT) << BAT_OFFSET_SHIFT;
+                self.data
+                    .seek(SeekFrom::Start(block_offset + offset_in_block))?;
+                self.data.read(buf)?
+            }
+            // Not present, undefined, zero and unmapped blocks.
+            _ => {
+                buf.fill(0);
+                to_read
+            }
+        };
+
+        self.position += read as u64;
+        Ok(read)
+    }
+}
+
+impl<R: Read + Seek> Seek for VhdxReader<R> {
+    fn seek(&mut self, pos: SeekFrom) -> io::Result<u64> {
+        self.position = seek_position(self.position, self.size, pos)?;
+        Ok(self.position)
+    }
+}
+
+fn invalid(detail: String) -> Error {
+    Error::InvalidImage {
+        format: "VHDX",
+        detail,
+    }
+}
@@ -10,13 +10,41 @@ pub use attribute::x30::FileNameAttr;
 pub use attribute::MftAttribute;
 
 pub use crate::mft::MftParser;
-pub use entry::{EntryHeader, MftEntry};
+pub use crate::stream::MftStreamParser;
+pub use entry::{EntryHeader, FixupOptions, FixupPolicy, MftEntry, MftEntryRef};
+pub use limits::ParseLimits;
+pub use timestamp::FileTime;
 
+use std::io::{Read, Seek};
+
+/// A trait object friendly `Read + Seek`, for sources which are only known at runtime.
+pub trait ReadSeek: Read + Seek {}
+
+impl<T: Read + Seek> ReadSeek for T {}
+
+pub mod analysis;
+pub mod anomaly;
 pub mod attribute;
+pub mod bodyfile;
+pub mod carve;
+#[cfg(feature = "compression")]
+pub mod container;
 pub mod csv;
+pub mod derived;
 pub mod entry;
 pub mod err;
+pub mod fields;
+#[cfg(feature = "images")]
+pub mod image;
+pub mod limits;
 pub mod mft;
+pub mod partition;
+pub mod schema;
+pub mod stream;
+pub mod timeline;
+pub mod timestamp;
+pub mod timezone;
+pub mod volume;
 
 pub(crate) mod macros;
 pub(crate) mod utils;
@@ -0,0 +1,60 @@
+//! Bounds on the resources a single (possibly corrupted) record may consume while being parsed.
+use crate::err::{Error, Result};
+
+/// Upper bounds on what the parsers will allocate or walk for a single record.
+///
+/// Records which exceed a limit fail with `Error::LimitExceeded`, like any other malformed record,
+/// so a corrupted record cannot exhaust memory. The defaults are far above what NTFS itself produces.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ParseLimits {
+    /// The maximum size of a resident attribute's content, or of a non-resident attribute's data runs, in bytes.
+    pub max_attribute_size: u32,
+    /// The maximum number of entries in an index node.
+    pub max_index_entries: usize,
+    /// The maximum number of entries in an attribute list.
+    pub max_attribute_list_entries: usize,
+    /// The maximum number of parent directories which are followed when resolving a path.
+    /// Deeper paths (usually cycles) are resolved as `[Unknown]`.
+    pub max_path_depth: usize,
+}
+
+impl Default for ParseLimits {
+    fn default() -> Self {
+        ParseLimits {
+            max_attribute_size: 1024 * 1024,
+            max_index_entries: 4096,
+            max_attribute_list_entries: 4096,
+            max_path_depth: 1024,
+        }
+    }
+}
+
+impl ParseLimits {
+    pub(crate) fn check_attribute_size(&self, size: u32) -> Result<()> {
+        check(
+            "attribute size",
+            size as usize,
+            self.max_attribute_size as usize,
+        )
+    }
+
+    pub(crate) fn check_index_entries(&self, count: usize) -> Result<()> {
+        check("index entries", count, self.max_index_entries)
+    }
+
+    pub(crate) fn check_attribute_list_entries(&self, count: usize) -> Result<()> {
+        check(
+            "attribute list entries",
+            count,
+            self.max_attribute_list_entries,
+        )
+    }
+}
+
+fn check(limit: &'static str, value: usize, max: usize) -> Result<()> {
+    if value > max {
+        return Err(Error::LimitExceeded { limit, value, max });
+    }
+
+    Ok(())
+}
@@ -6,7 +6,54 @@ macro_rules! impl_serialize_for_bitflags {
             where
                 S: serde::ser::Serializer,
             {
-                serializer.serialize_str(&format!("{:?}", &self))
+                use serde::ser::SerializeStruct;
+
+                // The names of the flags which are set, and their value.
+                // Records are read with `from_bits_truncate`, so bits without a name are not in it.
+                let mut state = serializer.serialize_struct(stringify!($flags), 2)?;
+                state.serialize_field(
+                    "names",
+                    &$crate::utils::flag_names(&format!("{:?}", &self)),
+                )?;
+                state.serialize_field("raw", &self.bits())?;
+                state.end()
+            }
+        }
+    };
+}
+
+#[macro_export]
+macro_rules! impl_deserialize_for_bitflags {
+    ($flags: ident) => {
+        impl<'de> serde::de::Deserialize<'de> for $flags {
+            fn deserialize<D>(deserializer: D) -> ::std::result::Result<Self, D::Error>
+            where
+                D: serde::de::Deserializer<'de>,
+            {
+                use serde::de::Error;
+
+                // The names are only there for readers, the value has every flag.
+                #[derive(serde::Deserialize)]
+                struct Flags {
+                    raw: u64,
+                }
+
+                let flags = Flags::deserialize(deserializer)?;
+                let bits = ::std::convert::TryFrom::try_from(flags.raw).map_err(|_| {
+                    D::Error::custom(format!(
+                        "{:#x} is too large for {}",
+                        flags.raw,
+                        stringify!($flags)
+                    ))
+                })?;
+                // Bits without a name are never serialized, they are not silently dropped here either.
+                $flags::from_bits(bits).ok_or_else(|| {
+                    D::Error::custom(format!(
+                        "{:#x} has bits which are not {}",
+                        flags.raw,
+                        stringify!($flags)
+                    ))
+                })
             }
         }
     };
@@ -1,12 +1,24 @@
-use crate::entry::MftEntry;
+use crate::attribute::x30::FileNameAttr;
+use crate::entry::{check_entry_size, FixupOptions, MftEntry, MftEntryRef};
 use crate::err::{Error, Result};
+use crate::limits::ParseLimits;
+use crate::partition::{Partition, PartitionReader};
+use crate::volume::VolumeMftReader;
+#[cfg(feature = "images")]
+use crate::{
+    entry::{BAAD_HEADER, FILE_HEADER},
+    partition::{list_partitions, ntfs_partitions, select_ntfs_partition},
+    partition::{PartitionScheme, PartitionType},
+    volume::BootSector,
+    ReadSeek,
+};
 
 use crate::EntryHeader;
 use log::{debug, trace};
 
 use lru::LruCache;
-use std::fs::{self, File};
-use std::io::{BufReader, Cursor, Read, Seek, SeekFrom};
+use std::fs::File;
+use std::io::{self, BufReader, Cursor, Read, Seek, SeekFrom};
 use std::num::NonZeroUsize;
 use std::path::{Path, PathBuf};
 
@@ -17,16 +29,28 @@ pub struct MftParser<T: Read + Seek> {
     entry_size: u32,
     size: u64,
     entries_cache: LruCache<u64, PathBuf>,
+    /// Reusable buffer for borrowed entries (see `get_entry_ref`).
+    scratch: Vec<u8>,
+    limits: ParseLimits,
+    fixup: FixupOptions,
+    recover: bool,
 }
 
 impl MftParser<BufReader<File>> {
     /// Instantiates an instance of the parser from a file path.
     /// Does not mutate the file contents in any way.
+    ///
+    /// With the `compression` feature, gzip, zstd and zip files are unpacked transparently
+    /// (see `container::open`).
     pub fn from_path(filename: impl AsRef<Path>) -> Result<Self> {
         let f = filename.as_ref();
 
+        #[cfg(feature = "compression")]
+        let mft_fh = crate::container::open(f)?;
+        #[cfg(not(feature = "compression"))]
         let mft_fh = File::open(f).map_err(|e| Error::failed_to_open_file(f, e))?;
-        let size = fs::metadata(f)?.len();
+
+        let size = mft_fh.metadata()?.len();
 
         Self::from_read_seek(BufReader::with_capacity(4096, mft_fh), Some(size))
     }
@@ -43,10 +67,192 @@ impl MftParser<Cursor<Vec<u8>>> {
     }
 }
 
+#[cfg(feature = "mmap")]
+impl MftParser<Cursor<memmap2::Mmap>> {
+    /// Instantiates an instance of the parser from a memory mapped file.
+    /// Does not mutate the file contents in any way.
+    ///
+    /// Combined with `get_entry_ref`, entries can be read without any per-entry allocation.
+    pub fn from_path_mmap(filename: impl AsRef<Path>) -> Result<Self> {
+        let f = filename.as_ref();
+
+        let mft_fh = File::open(f).map_err(|e| Error::failed_to_open_file(f, e))?;
+
+        // SAFETY: The mapping is read-only. The caller must make sure the file is not truncated
+        // or modified by another process while the parser is alive.
+        let mmap =
+            unsafe { memmap2::Mmap::map(&mft_fh) }.map_err(|e| Error::failed_to_open_file(f, e))?;
+        let size = mmap.len() as u64;
+
+        Self::from_read_seek(Cursor::new(mmap), Some(size))
+    }
+}
+
+#[cfg(feature = "images")]
+impl MftParser<Box<dyn ReadSeek>> {
+    /// Instantiates an instance of the parser from an image (see `image::open`) or an MFT file.
+    ///
+    /// If the image contains an NTFS volume, the MFT is located from its `$Boot` sector.
+    /// Whole-disk images must contain a single NTFS partition, see `from_image_path_partition`.
+    /// Otherwise, the image is assumed to be an MFT.
+    pub fn from_image_path(filename: impl AsRef<Path>) -> Result<Self> {
+        Self::from_image_path_partition(filename, None)
+    }
+
+    /// Like `from_image_path`, but selects the NTFS partition with the given (1-based) index
+    /// from the partition table of a whole-disk image.
+    pub fn from_image_path_partition(
+        filename: impl AsRef<Path>,
+        partition: Option<usize>,
+    ) -> Result<Self> {
+        let mut image = crate::image::open(filename)?;
+
+        if partition.is_none() && BootSector::is_ntfs_volume(&mut image)? {
+            return Self::from_read_seek(Box::new(VolumeMftReader::new(image)?), None);
+        }
+
+        // The first record may end with 0x55AA, which would pass for an MBR.
+        if starts_with_mft_record(&mut image)? {
+            return match partition {
+                Some(index) => Err(Error::NoSuchNtfsPartition { index }),
+                None => Self::from_read_seek(image, None),
+            };
+        }
+
+        let partitions = list_partitions(&mut image)?;
+        image.rewind()?;
+
+        if partitions.is_empty() {
+            return match partition {
+                Some(index) => Err(Error::NoSuchNtfsPartition { index }),
+                None => Self::from_read_seek(image, None),
+            };
+        }
+
+        let partition = select_ntfs_partition(&partitions, partition)?;
+        debug!("Using partition {:?}", partition);
+
+        let volume = PartitionReader::from_partition(image, &partition);
+        Self::from_read_seek(Box::new(VolumeMftReader::new(volume)?), None)
+    }
+
+    /// Instantiates a parser for each NTFS volume in an image.
+    /// The image is opened separately for each volume.
+    pub fn from_image_path_volumes(filename: impl AsRef<Path>) -> Result<Vec<(Partition, Self)>> {
+        let filename = filename.as_ref();
+        let mut image = crate::image::open(filename)?;
+
+        if BootSector::is_ntfs_volume(&mut image)? {
+            let size = image.seek(SeekFrom::End(0))?;
+            image.rewind()?;
+
+            let volume = Partition {
+                index: 1,
+                scheme: PartitionScheme::None,
+                partition_type: PartitionType::None,
+                offset: 0,
+                size,
+                name: None,
+                is_ntfs: true,
+            };
+
+            let parser = Self::from_read_seek(Box::new(VolumeMftReader::new(image)?), None)?;
+            return Ok(vec![(volume, parser)]);
+        }
+
+        if starts_with_mft_record(&mut image)? {
+            return Ok(vec![]);
+        }
+
+        ntfs_partitions(&mut image)?
+            .into_iter()
+            .map(|partition| {
+                let volume =
+                    PartitionReader::from_partition(crate::image::open(filename)?, &partition);
+                let parser = Self::from_read_seek(Box::new(VolumeMftReader::new(volume)?), None)?;
+
+                Ok((partition, parser))
+            })
+            .collect()
+    }
+}
+
+/// Whether the stream starts with a `FILE` or `BAAD` record, rather than a boot sector or partition table.
+#[cfg(feature = "images")]
+fn starts_with_mft_record<R: Read + Seek>(reader: &mut R) -> io::Result<bool> {
+    let start = reader.stream_position()?;
+
+    let mut signature = [0; 4];
+    let is_record = match reader.read_exact(&mut signature) {
+        Ok(()) => &signature == FILE_HEADER || &signature == BAAD_HEADER,
+        Err(e) if e.kind() == io::ErrorKind::UnexpectedEof => false,
+        Err(e) => return Err(e),
+    };
+
+    reader.seek(SeekFrom::Start(start))?;
+
+    Ok(is_record)
+}
+
+impl<D: Read + Seek> MftParser<VolumeMftReader<PartitionReader<D>>> {
+    /// Instantiates an instance of the parser from an NTFS partition of a disk
+    /// (see `partition::list_partitions`).
+    pub fn from_partition(disk: D, partition: &Partition) -> Result<Self> {
+        Self::from_volume(PartitionReader::from_partition(disk, partition))
+    }
+}
+
+impl<V: Read + Seek> MftParser<VolumeMftReader<V>> {
+    /// Instantiates an instance of the parser from an NTFS volume (for example a partition image),
+    /// which should start at the current position of the stream.
+    ///
+    /// The MFT is located using the `$Boot` sector, and read by following its data runs.
+    pub fn from_volume(volume: V) -> Result<Self> {
+        let reader = VolumeMftReader::new(volume)?;
+        let size = reader.len();
+
+        Self::from_read_seek(reader, Some(size))
+    }
+}
+
+impl<B: AsRef<[u8]>> MftParser<Cursor<B>> {
+    /// Reads an entry from the MFT by entry number, without allocating.
+    ///
+    /// The record is copied from the underlying buffer into a scratch buffer owned by the parser,
+    /// where fixups are applied. The returned entry (and its attributes) borrow that buffer,
+    /// so it is only valid until the next call to `get_entry_ref`.
+    /// Use `MftEntryRef::to_entry` or `get_entry` when an owned entry is needed.
+    pub fn get_entry_ref(&mut self, entry_number: u64) -> Result<MftEntryRef<'_>> {
+        debug!("Reading entry {}", entry_number);
+
+        let buffer = self.data.get_ref().as_ref();
+        let entry_size = u64::from(self.entry_size);
+
+        let record = entry_number
+            .checked_mul(entry_size)
+            .and_then(|start| {
+                let start = usize::try_from(start).ok()?;
+                buffer.get(start..start.checked_add(entry_size as usize)?)
+            })
+            .ok_or_else(|| {
+                Error::from(io::Error::from(io::ErrorKind::UnexpectedEof)).in_entry(entry_number)
+            })?;
+
+        let entry = if self.recover {
+            MftEntryRef::recover_slice(record, &mut self.scratch, entry_number, self.fixup)?
+        } else {
+            MftEntryRef::from_slice_with_fixup(record, &mut self.scratch, entry_number, self.fixup)?
+        };
+
+        Ok(entry.with_limits(self.limits))
+    }
+}
+
 impl<T: Read + Seek> MftParser<T> {
     pub fn from_read_seek(mut data: T, size: Option<u64>) -> Result<Self> {
         // We use the first entry to guess the entry size for all the other records.
         let first_entry = EntryHeader::from_reader(&mut data, 0)?;
+        let entry_size = check_entry_size(first_entry.total_entry_size)?;
 
         let size = match size {
             Some(sz) => sz,
@@ -57,12 +263,39 @@ impl<T: Read + Seek> MftParser<T> {
 
         Ok(Self {
             data,
-            entry_size: first_entry.total_entry_size,
+            entry_size,
             size,
             entries_cache: LruCache::new(NonZeroUsize::new(1000).expect("1000 > 0")),
+            scratch: Vec::with_capacity(entry_size as usize),
+            limits: ParseLimits::default(),
+            fixup: FixupOptions::default(),
+            recover: false,
         })
     }
 
+    /// Sets the limits used when parsing entries and resolving paths.
+    pub fn with_limits(mut self, limits: ParseLimits) -> Self {
+        self.limits = limits;
+        self
+    }
+
+    pub fn limits(&self) -> &ParseLimits {
+        &self.limits
+    }
+
+    /// Sets the fixup policy and stride used when reading entries.
+    pub fn with_fixup_options(mut self, fixup: FixupOptions) -> Self {
+        self.fixup = fixup;
+        self
+    }
+
+    /// When set, damaged records (such as `BAAD` records) are parsed on a best-effort basis,
+    /// see `MftEntry::recover`.
+    pub fn with_recovery(mut self, recover: bool) -> Self {
+        self.recover = recover;
+        self
+    }
+
     pub fn get_entry_count(&self) -> u64 {
         self.size / u64::from(self.entry_size)
     }
@@ -71,13 +304,24 @@ impl<T: Read + Seek> MftParser<T> {
     pub fn get_entry(&mut self, entry_number: u64) -> Result<MftEntry> {
         debug!("Reading entry {}", entry_number);
 
-        self.data
-            .seek(SeekFrom::Start(entry_number * u64::from(self.entry_size)))?;
         let mut entry_buffer = vec![0; self.entry_size as usize];
 
-        self.data.read_exact(&mut entry_buffer)?;
+        let offset = entry_number
+            .checked_mul(u64::from(self.entry_size))
+            .ok_or_else(|| io::Error::from(io::ErrorKind::UnexpectedEof));
 
-        MftEntry::from_buffer(entry_buffer, entry_number)
+        offset
+            .and_then(|offset| self.data.seek(SeekFrom::Start(offset)))
+            .and_then(|_| self.data.read_exact(&mut entry_buffer))
+            .map_err(|e| Error::from(e).in_entry(entry_number))?;
+
+        let entry = if self.recover {
+            MftEntry::recover(entry_buffer, entry_number, self.fixup)?
+        } else {
+            MftEntry::from_buffer_with_fixup(entry_buffer, entry_number, self.fixup)?
+        };
+
+        Ok(entry.with_limits(self.limits))
     }
 
     /// Iterates over all the entries in the MFT.
@@ -87,79 +331,138 @@ impl<T: Read + Seek> MftParser<T> {
         (0..total_entries).map(move |i| self.get_entry(i))
     }
 
-    fn inner_get_entry(&mut self, parent_entry_id: u64, entry_name: Option<&str>) -> PathBuf {
-        let cached_entry = self.entries_cache.get(&parent_entry_id);
+    /// Resolves the path of a parent directory, walking up to the root.
+    /// Caches the path of every directory on the way, unless the walk was cut off.
+    fn parent_path(&mut self, parent_entry_id: u64) -> PathBuf {
+        // The entries walked so far, and the name each adds to the path of the next one.
+        let mut walked: Vec<(u64, Option<String>)> = vec![];
+        let mut current = parent_entry_id;
+        let mut cut_off = false;
+
+        let prefix = loop {
+            // Parent chains this long are assumed to be cyclic.
+            if walked.len() >= self.limits.max_path_depth {
+                cut_off = true;
+                break PathBuf::from("[Unknown]");
+            }
 
-        // If my parent path is known, then my path is parent's full path + my name.
-        // Else, retrieve and cache my parent's path.
-        if let Some(cached_parent_path) = cached_entry {
-            match entry_name {
-                Some(name) => cached_parent_path.clone().join(name),
-                None => cached_parent_path.clone(),
+            if let Some(cached_path) = self.entries_cache.get(&current) {
+                break cached_path.clone();
             }
-        } else {
-            let path = match self.get_entry(parent_entry_id).ok() {
-                Some(parent) => match self.get_full_path_for_entry(&parent) {
-                    Ok(Some(path)) if parent.is_dir() => path,
-                    Ok(Some(_)) => PathBuf::from("[Unknown]"),
-                    // I have a parent, which doesn't have a filename attribute.
-                    // Default to root.
-                    _ => PathBuf::new(),
-                },
+
+            let path = match self.get_entry(current).ok() {
+                Some(parent) => {
+                    let entry_id = parent.header.record_number;
+
+                    match parent.find_best_name_attribute() {
+                        Some(_) if !parent.is_dir() => PathBuf::from("[Unknown]"),
+                        // MFT entry 5 is the root path.
+                        Some(filename_header) if filename_header.parent.entry == 5 => {
+                            PathBuf::from(&filename_header.name)
+                        }
+                        Some(filename_header)
+                            if filename_header.parent.entry == entry_id
+                                || filename_header.parent.entry == 0 =>
+                        {
+                            PathBuf::from("[Orphaned]").join(&filename_header.name)
+                        }
+                        Some(filename_header) => {
+                            walked.push((current, Some(filename_header.name)));
+                            current = filename_header.parent.entry;
+                            continue;
+                        }
+                        None => match parent.header.base_reference.entry {
+                            // I have a parent, which doesn't have a filename attribute.
+                            // Default to root.
+                            0 => PathBuf::new(),
+                            _ if !parent.is_dir() => PathBuf::from("[Unknown]"),
+                            base_entry_id => {
+                                walked.push((current, None));
+                                current = base_entry_id;
+                                continue;
+                            }
+                        },
+                    }
+                }
                 // Parent is maybe corrupted or incomplete, use a sentinel instead.
                 None => PathBuf::from("[Unknown]"),
             };
 
-            self.entries_cache.put(parent_entry_id, path.clone());
-            match entry_name {
-                Some(name) => path.join(name),
-                None => path,
-            }
-        }
+            self.entries_cache.put(current, path.clone());
+            break path;
+        };
+
+        walked
+            .into_iter()
+            .rev()
+            .fold(prefix, |path, (entry_id, name)| {
+                let path = match name {
+                    Some(name) => path.join(name),
+                    None => path,
+                };
+
+                // A cut off path would stick to the entries, even when resolved from a closer start.
+                if !cut_off {
+                    self.entries_cache.put(entry_id, path.clone());
+                }
+
+                path
+            })
     }
 
     /// Gets the full path for an entry.
     /// Caches computations.
     pub fn get_full_path_for_entry(&mut self, entry: &MftEntry) -> Result<Option<PathBuf>> {
-        let entry_id = entry.header.record_number;
         match entry.find_best_name_attribute() {
-            Some(filename_header) => {
-                let parent_entry_id = filename_header.parent.entry;
+            Some(filename_header) => Ok(Some(self.path_for_name(entry, &filename_header))),
+            None => match entry.header.base_reference.entry {
+                // I don't have a parent reference, and no X30 attribute. Though luck.
+                0 => Ok(None),
+                parent_entry_id => Ok(Some(self.parent_path(parent_entry_id))),
+            },
+        }
+    }
 
-                // MFT entry 5 is the root path.
-                if parent_entry_id == 5 {
-                    return Ok(Some(PathBuf::from(filename_header.name)));
-                }
+    /// Gets the paths of every hard link of an entry (see `MftEntry::link_names`),
+    /// the first of which is usually the one `get_full_path_for_entry` returns.
+    pub fn get_hard_link_paths_for_entry(&mut self, entry: &MftEntry) -> Result<Vec<PathBuf>> {
+        Ok(entry
+            .link_names()
+            .iter()
+            .map(|filename_header| self.path_for_name(entry, filename_header))
+            .collect())
+    }
 
-                if parent_entry_id == entry_id {
-                    trace!(
-                        "Found self-referential file path, for entry ID {}",
-                        entry_id
-                    );
-                    return Ok(Some(PathBuf::from("[Orphaned]").join(filename_header.name)));
-                }
+    /// The path of the entry under one of its names.
+    fn path_for_name(&mut self, entry: &MftEntry, filename_header: &FileNameAttr) -> PathBuf {
+        let entry_id = entry.header.record_number;
+        let parent_entry_id = filename_header.parent.entry;
 
-                if parent_entry_id > 0 {
-                    Ok(Some(self.inner_get_entry(
-                        parent_entry_id,
-                        Some(&filename_header.name),
-                    )))
-                } else {
-                    trace!("Found orphaned entry ID {}", entry_id);
+        // MFT entry 5 is the root path.
+        if parent_entry_id == 5 {
+            return PathBuf::from(&filename_header.name);
+        }
 
-                    let orphan = PathBuf::from("[Orphaned]").join(filename_header.name);
+        if parent_entry_id == entry_id {
+            trace!(
+                "Found self-referential file path, for entry ID {}",
+                entry_id
+            );
+            return PathBuf::from("[Orphaned]").join(&filename_header.name);
+        }
 
-                    self.entries_cache
-                        .put(entry.header.record_number, orphan.clone());
+        if parent_entry_id > 0 {
+            self.parent_path(parent_entry_id)
+                .join(&filename_header.name)
+        } else {
+            trace!("Found orphaned entry ID {}", entry_id);
 
-                    Ok(Some(orphan))
-                }
-            }
-            None => match entry.header.base_reference.entry {
-                // I don't have a parent reference, and no X30 attribute. Though luck.
-                0 => Ok(None),
-                parent_entry_id => Ok(Some(self.inner_get_entry(parent_entry_id, None))),
-            },
+            let orphan = PathBuf::from("[Orphaned]").join(&filename_header.name);
+
+            self.entries_cache
+                .put(entry.header.record_number, orphan.clone());
+
+            orphan
         }
     }
 }
@@ -0,0 +1,356 @@
+//! Partition tables (MBR and GPT), for locating NTFS volumes inside whole-disk images.
+use crate::err::{Error, Result};
+use crate::utils::{read_utf16_string, seek_position};
+use crate::volume::BootSector;
+
+use byteorder::{LittleEndian, ReadBytesExt};
+use log::{debug, trace};
+use serde::Serialize;
+use winstructs::guid::Guid;
+
+use std::fmt::{self, Display};
+use std::io::{self, Cursor, Read, Seek, SeekFrom};
+
+const MBR_SIGNATURE: [u8; 2] = [0x55, 0xAA];
+const MBR_PARTITION_TABLE_OFFSET: usize = 0x1BE;
+const MBR_PROTECTIVE_TYPE: u8 = 0xEE;
+const MBR_EXTENDED_TYPES: [u8; 3] = [0x05, 0x0F, 0x85];
+
+/// Logical partitions are chained, a corrupt chain could otherwise loop forever.
+const MAX_LOGICAL_PARTITIONS: usize = 128;
+
+const GPT_SIGNATURE: &[u8; 8] = b"EFI PART";
+/// GPT disks may use 512 or 4096 byte logical sectors, the header is always at LBA 1.
+const GPT_SECTOR_SIZES: [u64; 2] = [512, 4096];
+const MAX_GPT_ENTRIES: u32 = 1024;
+
+const SECTOR_SIZE: u64 = 512;
+
+#[derive(Serialize, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PartitionScheme {
+    Mbr,
+    Gpt,
+    /// The image is a single volume, without a partition table.
+    None,
+}
+
+#[derive(Serialize, Debug, Clone, PartialEq)]
+pub enum PartitionType {
+    /// The MBR system ID, for example `0x07` for NTFS/exFAT.
+    Mbr(u8),
+    /// The GPT partition type GUID.
+    Gpt(Guid),
+    None,
+}
+
+impl Display for PartitionType {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            PartitionType::Mbr(system_id) => write!(f, "0x{:02X}", system_id),
+            PartitionType::Gpt(guid) => write!(f, "{}", guid),
+            PartitionType::None => write!(f, "-"),
+        }
+    }
+}
+
+#[derive(Serialize, Debug, Clone, PartialEq)]
+pub struct Partition {
+    /// The 1-based index of the partition, in table order.
+    /// For MBR disks, logical partitions follow the 4 primary entries.
+    pub index: usize,
+    pub scheme: PartitionScheme,
+    pub partition_type: PartitionType,
+    /// Offset of the partition from the start of the disk, in bytes.
+    pub offset: u64,
+    /// Size of the partition, in bytes.
+    pub size: u64,
+    /// GPT partition name, if any.
+    pub name: Option<String>,
+    /// Whether the partition starts with an NTFS boot sector.
+    pub is_ntfs: bool,
+}
+
+/// Reads the partition table of a disk.
+///
+/// GPT is preferred when a protective MBR is found, otherwise the MBR (including logical partitions) is used.
+/// Returns an empty list for disks without a partition table (for example, volume images).
+pub fn list_partitions<R: Read + Seek>(disk: &mut R) -> Result<Vec<Partition>> {
+    let disk_size = disk.seek(SeekFrom::End(0))?;
+
+    let mut mbr = [0; 512];
+    disk.rewind()?;
+    if let Err(e) = disk.read_exact(&mut mbr) {
+        return match e.kind() {
+            io::ErrorKind::UnexpectedEof => Ok(vec![]),
+            _ => Err(e.into()),
+        };
+    }
+
+    // Volume boot sectors share the MBR signature, but have code where the partition table would be.
+    if mbr[510..512] != MBR_SIGNATURE || BootSector::from_bytes(&mbr).is_ok() {
+        return Ok(vec![]);
+    }
+
+    let entries = mbr_entries(&mbr);
+
+    let mut partitions = if entries
+        .iter()
+        .any(|entry| entry.system_id == MBR_PROTECTIVE_TYPE)
+    {
+        gpt_partitions(disk)?
+    } else {
+        mbr_partitions(disk, &entries, disk_size)?
+    };
+
+    // Entries pointing outside of the disk are either garbage, or the image is truncated.
+    partitions.retain(|partition| partition.size > 0 && partition.offset < disk_size);
+
+    for partition in partitions.iter_mut() {
+        disk.seek(SeekFrom::Start(partition.offset))?;
+        partition.is_ntfs = BootSector::is_ntfs_volume(disk)?;
+    }
+
+    debug!("Found partitions {:?}", partitions);
+
+    Ok(partitions)
+}
+
+/// Reads the partition table of a disk, and returns only the NTFS partitions.
+pub fn ntfs_partitions<R: Read + Seek>(disk: &mut R) -> Result<Vec<Partition>> {
+    let mut partitions = list_partitions(disk)?;
+    partitions.retain(|partition| partition.is_ntfs);
+
+    Ok(partitions)
+}
+
+/// Selects an NTFS partition by index, or the only NTFS partition if no index is given.
+pub fn select_ntfs_partition(partitions: &[Partition], index: Option<usize>) -> Result<Partition> {
+    let mut candidates = partitions.iter().filter(|partition| partition.is_ntfs);
+
+    match index {
+        Some(index) => candidates
+            .find(|partition| partition.index == index)
+            .cloned()
+            .ok_or(Error::NoSuchNtfsPartition { index }),
+        None => {
+            let candidates: Vec<&Partition> = candidates.collect();
+            match candidates.as_slice() {
+                [only] => Ok((*only).clone()),
+                _ => Err(Error::AmbiguousNtfsPartition {
+                    indices: candidates.iter().map(|partition| partition.index).collect(),
+                }),
+            }
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+struct MbrEntry {
+    system_id: u8,
+    start_lba: u32,
+    sector_count: u32,
+}
+
+fn mbr_entries(sector: &[u8; 512]) -> [MbrEntry; 4] {
+    let entry = |i: usize| {
+        let raw =
+            &sector[MBR_PARTITION_TABLE_OFFSET + i * 16..MBR_PARTITION_TABLE_OFFSET + (i + 1) * 16];
+        MbrEntry {
+            system_id: raw[4],
+            start_lba: u32::from_le_bytes([raw[8], raw[9], raw[10], raw[11]]),
+            sector_count: u32::from_le_bytes([raw[12], raw[13], raw[14], raw[15]]),
+        }
+    };
+
+    [entry(0), entry(1), entry(2), entry(3)]
+}
+
+fn mbr_partition(index: usize, entry: &MbrEntry, base_lba: u64) -> Partition {
+    Partition {
+        index,
+        scheme: PartitionScheme::Mbr,
+        partition_type: PartitionType::Mbr(entry.system_id),
+        offset: (base_lba + u64::from(entry.start_lba)) * SECTOR_SIZE,
+        size: u64::from(entry.sector_count) * SECTOR_SIZE,
+        name: None,
+        is_ntfs: false,
+    }
+}
+
+fn mbr_partitions<R: Read + Seek>(
+    disk: &mut R,
+    entries: &[MbrEntry; 4],
+    disk_size: u64,
+) -> Result<Vec<Partition>> {
+    let mut partitions = vec![];
+    let mut extended = None;
+
+    for (i, entry) in entries.iter().enumerate() {
+        if entry.system_id == 0 {
+            continue;
+        }
+
+        if MBR_EXTENDED_TYPES.contains(&entry.system_id) {
+            extended.get_or_insert(u64::from(entry.start_lba));
+            continue;
+        }
+
+        partitions.push(mbr_partition(i + 1, entry, 0));
+    }
+
+    // Logical partitions are described by a chain of extended boot records.
+    // The first entry of each is relative to the EBR, the second (the next EBR) to the extended partition.
+    if let Some(extended_lba) = extended {
+        let mut ebr_lba = extended_lba;
+
+        for i in 0..MAX_LOGICAL_PARTITIONS {
+            if ebr_lba * SECTOR_SIZE + SECTOR_SIZE > disk_size {
+                break;
+            }
+
+            trace!("Reading EBR at LBA {}", ebr_lba);
+            let mut ebr = [0; 512];
+            disk.seek(SeekFrom::Start(ebr_lba * SECTOR_SIZE))?;
+            disk.read_exact(&mut ebr)?;
+
+            if ebr[510..512] != MBR_SIGNATURE {
+                break;
+            }
+
+            let [logical, next, ..] = mbr_entries(&ebr);
+            if logical.system_id != 0 {
+                partitions.push(mbr_partition(5 + i, &logical, ebr_lba));
+            }
+
+            if next.system_id == 0 || next.start_lba == 0 {
+                break;
+            }
+
+            ebr_lba = extended_lba + u64::from(next.start_lba);
+        }
+    }
+
+    Ok(partitions)
+}
+
+fn gpt_partitions<R: Read + Seek>(disk: &mut R) -> Result<Vec<Partition>> {
+    let invalid = |detail: String| Error::InvalidPartitionTable { detail };
+
+    for sector_size in GPT_SECTOR_SIZES {
+        let mut header = [0; 92];
+        disk.seek(SeekFrom::Start(sector_size))?;
+        if disk.read_exact(&mut header).is_err() || &header[..8] != GPT_SIGNATURE {
+            continue;
+        }
+
+        let mut reader = &header[72..];
+        let entries_lba = reader.read_u64::<LittleEndian>()?;
+        let entry_count = reader.read_u32::<LittleEndian>()?;
+        let entry_size = reader.read_u32::<LittleEndian>()?;
+
+        if entry_count > MAX_GPT_ENTRIES || !(128..=4096).contains(&entry_size) {
+            return Err(invalid(format!(
+                "GPT has {} entries of size {}",
+                entry_count, entry_size
+            )));
+        }
+
+        let mut table = vec![0; (entry_count * entry_size) as usize];
+        disk.seek(SeekFrom::Start(entries_lba.saturating_mul(sector_size)))?;
+        disk.read_exact(&mut table)?;
+
+        let mut partitions = vec![];
+        for (i, raw) in table.chunks(entry_size as usize).enumerate() {
+            // Unused entries have a zeroed type GUID.
+            if raw[..16].iter().all(|&b| b == 0) {
+                continue;
+            }
+
+            let mut reader = Cursor::new(raw);
+            let partition_type =
+                Guid::from_reader(&mut reader).map_err(Error::failed_to_read_guid)?;
+            reader.seek(SeekFrom::Start(32))?;
+            let first_lba = reader.read_u64::<LittleEndian>()?;
+            let last_lba = reader.read_u64::<LittleEndian>()?;
+            reader.seek(SeekFrom::Start(56))?;
+            let name = read_utf16_string(&mut reader, Some(36)).ok();
+
+            partitions.push(Partition {
+                index: i + 1,
+                scheme: PartitionScheme::Gpt,
+                partition_type: PartitionType::Gpt(partition_type),
+                offset: first_lba.saturating_mul(sector_size),
+                size: last_lba
+                    .saturating_sub(first_lba)
+                    .saturating_add(1)
+                    .saturating_mul(sector_size),
+                name: name.filter(|name| !name.is_empty()),
+                is_ntfs: false,
+            });
+        }
+
+        return Ok(partitions);
+    }
+
+    Err(invalid("protective MBR without a GPT header".to_string()))
+}
+
+/// Restricts a disk to a single partition, presenting it as a standalone volume.
+pub struct PartitionReader<R: Read + Seek> {
+    disk: R,
+    offset: u64,
+    size: u64,
+    position: u64,
+}
+
+impl<R: Read + Seek> PartitionReader<R> {
+    pub fn new(disk: R, offset: u64, size: u64) -> Self {
+        PartitionReader {
+            disk,
+            offset,
+            size,
+            position: 0,
+        }
+    }
+
+    pub fn from_partition(disk: R, partition: &Partition) -> Self {
+        Self::new(disk, partition.offset, partition.size)
+    }
+
+    /// The size of the partition, in bytes.
+    pub fn len(&self) -> u64 {
+        self.size
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.size == 0
+    }
+
+    pub fn into_inner(self) -> R {
+        self.disk
+    }
+}
+
+impl<R: Read + Seek> Read for PartitionReader<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        if self.position >= self.size || buf.is_empty() {
+            return Ok(0);
+        }
+
+        let to_read = (buf.len() as u64).min(self.size - self.position) as usize;
+
+        self.disk
+            .seek(SeekFrom::Start(self.offset + self.position))?;
+        let read = self.disk.read(&mut buf[..to_read])?;
+
+        self.position += read as u64;
+        Ok(read)
+    }
+}
+
+impl<R: Read + Seek> Seek for PartitionReader<R> {
+    fn seek(&mut self, pos: SeekFrom) -> io::Result<u64> {
+        self.position = seek_position(self.position, self.size, pos)?;
+        Ok(self.position)
+    }
+}
@@ -0,0 +1,11 @@
+//! The versioned schema of the JSON entries written by `mft_dump`.
+//!
+//! Every entry has a `schema_version`, which is bumped whenever the shape of the output changes
+//! in a way which could break consumers (such as renamed or retyped fields).
+//! The JSON Schema of entries is published in `schema/mft_entry.schema.json`.
+
+/// The version of the schema of JSON entries.
+pub const SCHEMA_VERSION: u32 = 1;
+
+/// The JSON Schema (draft 2020-12) of JSON entries, as written by `mft_dump -o json` and `-o jsonl`.
+pub const ENTRY_JSON_SCHEMA: &str = include_str!("../schema/mft_entry.schema.json");
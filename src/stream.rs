@@ -0,0 +1,361 @@
+use crate::entry::{check_entry_size, FixupOptions, MftEntry, ENTRY_HEADER_SIZE, ZERO_HEADER};
+use crate::err::{Error, Result};
+use crate::limits::ParseLimits;
+use crate::EntryHeader;
+
+use log::{debug, trace};
+use winstructs::ntfs::mft_reference::MftReference;
+
+use std::collections::HashMap;
+use std::io::{self, Cursor, Read};
+use std::path::PathBuf;
+
+/// A forward-only MFT parser, which reads entries sequentially from any `Read`.
+///
+/// Useful for input which cannot be seeked, such as stdin, pipes or decompression streams.
+/// Since entries cannot be revisited, paths are not resolved while iterating,
+/// see `PathTable` for resolving them after (or during) a single pass.
+pub struct MftStreamParser<T: Read> {
+    data: T,
+    entry_size: u32,
+    next_entry_number: u64,
+    /// The first record, which may have already been read to learn the entry size.
+    first_record: Option<Vec<u8>>,
+    exhausted: bool,
+    limits: ParseLimits,
+    fixup: FixupOptions,
+    recover: bool,
+}
+
+impl<T: Read> MftStreamParser<T> {
+    /// Instantiates a streaming parser.
+    ///
+    /// If `entry_size` is not given, it will be taken from the header of the first entry.
+    pub fn from_read(mut data: T, entry_size: Option<u32>) -> Result<Self> {
+        let (entry_size, first_record) = match entry_size {
+            Some(entry_size) => (entry_size, None),
+            None => {
+                let mut first_record = vec![0; ENTRY_HEADER_SIZE];
+                data.read_exact(&mut first_record)?;
+
+                // We use the first entry to guess the entry size for all the other records.
+                let first_entry = EntryHeader::from_reader(&mut Cursor::new(&first_record), 0)?;
+                let entry_size = check_entry_size(first_entry.total_entry_size)?;
+
+                first_record.resize(entry_size as usize, 0);
+                data.read_exact(&mut first_record[ENTRY_HEADER_SIZE..])?;
+
+                (entry_size, Some(first_record))
+            }
+        };
+
+        let entry_size = check_entry_size(entry_size)?;
+
+        Ok(Self {
+            data,
+            entry_size,
+            next_entry_number: 0,
+            first_record,
+            exhausted: false,
+            limits: ParseLimits::default(),
+            fixup: FixupOptions::default(),
+            recover: false,
+        })
+    }
+
+    /// Sets the limits used when parsing entries.
+    pub fn with_limits(mut self, limits: ParseLimits) -> Self {
+        self.limits = limits;
+        self
+    }
+
+    /// Sets the fixup policy and stride used when reading entries.
+    pub fn with_fixup_options(mut self, fixup: FixupOptions) -> Self {
+        self.fixup = fixup;
+        self
+    }
+
+    /// When set, damaged records are parsed on a best-effort basis, see `MftEntry::recover`.
+    pub fn with_recovery(mut self, recover: bool) -> Self {
+        self.recover = recover;
+        self
+    }
+
+    pub fn entry_size(&self) -> u32 {
+        self.entry_size
+    }
+
+    /// Reads the next entry from the stream.
+    /// Returns `None` when the stream ends on an entry boundary.
+    pub fn next_entry(&mut self) -> Option<Result<MftEntry>> {
+        if self.exhausted {
+            return None;
+        }
+
+        let entry_number = self.next_entry_number;
+        debug!("Reading entry {}", entry_number);
+
+        let entry_buffer = match self.first_record.take() {
+            Some(record) => record,
+            None => match self.read_record() {
+                Ok(Some(record)) => record,
+                Ok(None) => {
+                    self.exhausted = true;
+                    return None;
+                }
+                Err(e) => {
+                    // A truncated record cannot be followed by anything meaningful.
+                    self.exhausted = true;
+                    return Some(Err(Error::from(e).in_entry(entry_number)));
+                }
+            },
+        };
+
+        self.next_entry_number += 1;
+
+        let entry = if self.recover {
+            MftEntry::recover(entry_buffer, entry_number, self.fixup)
+        } else {
+            MftEntry::from_buffer_with_fixup(entry_buffer, entry_number, self.fixup)
+        };
+
+        Some(entry.map(|entry| entry.with_limits(self.limits)))
+    }
+
+    /// Iterates over all the remaining entries in the stream.
+    pub fn iter_entries(&mut self) -> impl Iterator<Item = Result<MftEntry>> + '_ {
+        std::iter::from_fn(move || self.next_entry())
+    }
+
+    /// Reads a whole record, returns `None` if the stream is exhausted before the first byte.
+    fn read_record(&mut self) -> io::Result<Option<Vec<u8>>> {
+        let mut record = vec![0; self.entry_size as usize];
+        let mut filled = 0;
+
+        while filled < record.len() {
+            match self.data.read(&mut record[filled..]) {
+                Ok(0) => break,
+                Ok(n) => filled += n,
+                Err(e) if e.kind() == io::ErrorKind::Interrupted => continue,
+                Err(e) => return Err(e),
+            }
+        }
+
+        match filled {
+            0 => Ok(None),
+            n if n == record.len() => Ok(Some(record)),
+            n => {
+                trace!("Stream ended after {} bytes of a record", n);
+                Err(io::Error::from(io::ErrorKind::UnexpectedEof))
+            }
+        }
+    }
+}
+
+impl<T: Read> Iterator for MftStreamParser<T> {
+    type Item = Result<MftEntry>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.next_entry()
+    }
+}
+
+/// The minimal information about an entry needed to resolve paths.
+#[derive(Debug, Clone)]
+struct PathTableEntry {
+    parent: MftReference,
+    name: Option<String>,
+    is_dir: bool,
+    sequence: u16,
+    allocated: bool,
+}
+
+/// A table of entry names and parents, spooled while streaming over an MFT.
+///
+/// Once all the entries were added, paths can be resolved for any of them,
+/// producing the same results as `MftParser::get_full_path_for_entry`
+/// (except for parent chains deeper than the limit, which the parser may have partly cached).
+#[derive(Debug, Clone, Default)]
+pub struct PathTable {
+    entries: HashMap<u64, PathTableEntry>,
+    limits: ParseLimits,
+    match_sequences: bool,
+}
+
+impl PathTable {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Sets the limits used when resolving paths, parent chains longer than
+    /// `limits.max_path_depth` are assumed to be cyclic.
+    pub fn with_limits(mut self, limits: ParseLimits) -> Self {
+        self.limits = limits;
+        self
+    }
+
+    /// Only resolves references through entries with a matching sequence number
+    /// (or the one before it, for entries which are not in use).
+    ///
+    /// Useful for carved entries, which may come from several MFTs or several uses of a record:
+    /// only the last entry added for each record number is kept, and paths which go through
+    /// another use of a record are left unresolved rather than resolved against the wrong parent.
+    pub fn with_sequence_matching(mut self, match_sequences: bool) -> Self {
+        self.match_sequences = match_sequences;
+        self
+    }
+
+    /// Records the name and parent of the entry.
+    pub fn add_entry(&mut self, entry: &MftEntry) {
+        // Zeroed entries don't carry their entry number, and have nothing to resolve.
+        if entry.header.signature == *ZERO_HEADER {
+            return;
+        }
+
+        let table_entry = match entry.find_best_name_attribute() {
+            Some(filename) => PathTableEntry {
+                parent: filename.parent,
+                name: Some(filename.name),
+                is_dir: entry.is_dir(),
+                sequence: entry.header.sequence,
+                allocated: entry.is_allocated(),
+            },
+            // Extension records don't have names of their own, we use their base entry instead.
+            None => PathTableEntry {
+                parent: entry.header.base_reference,
+                name: None,
+                is_dir: entry.is_dir(),
+                sequence: entry.header.sequence,
+                allocated: entry.is_allocated(),
+            },
+        };
+
+        self.entries.insert(entry.header.record_number, table_entry);
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    /// Gets the full path for an entry, by entry number.
+    /// Returns `None` for entries which were not added, or have no name and no base entry.
+    pub fn get_full_path(&self, entry_id: u64) -> Option<PathBuf> {
+        let entry = self.entries.get(&entry_id)?;
+        self.full_path_of(entry_id, entry)
+    }
+
+    /// Like `get_full_path`, but with sequence matching (see `with_sequence_matching`),
+    /// also returns `None` when the entry added for the record number has another sequence number.
+    pub fn get_full_path_for_reference(&self, reference: MftReference) -> Option<PathBuf> {
+        let entry = self.lookup(reference)?;
+        self.full_path_of(reference.entry, entry)
+    }
+
+    /// See `get_full_path_for_reference`.
+    pub fn get_full_path_for_entry(&self, entry: &MftEntry) -> Option<PathBuf> {
+        self.get_full_path_for_reference(MftReference::new(
+            entry.header.record_number,
+            entry.header.sequence,
+        ))
+    }
+
+    fn full_path_of(&self, entry_id: u64, entry: &PathTableEntry) -> Option<PathBuf> {
+        match &entry.name {
+            Some(name) => Some(self.path_with_name(entry_id, entry.parent, name)),
+            None => match entry.parent.entry {
+                // I don't have a parent reference, and no X30 attribute. Though luck.
+                0 => None,
+                _ => Some(self.parent_path(entry.parent)),
+            },
+        }
+    }
+
+    /// The entry added for the record number of the reference, if it is the same use of the record.
+    fn lookup(&self, reference: MftReference) -> Option<&PathTableEntry> {
+        let entry = self.entries.get(&reference.entry)?;
+
+        // Records which are not in use have their sequence number incremented.
+        let matches = !self.match_sequences
+            || entry.sequence == reference.sequence
+            || (!entry.allocated && entry.sequence == reference.sequence.wrapping_add(1));
+
+        matches.then_some(entry)
+    }
+
+    /// Gets the paths of every hard link of an entry (see `MftEntry::link_names`),
+    /// resolving their parents from the entries which were added.
+    pub fn get_hard_link_paths(&self, entry: &MftEntry) -> Vec<PathBuf> {
+        entry
+            .link_names()
+            .iter()
+            .map(|name| self.path_with_name(entry.header.record_number, name.parent, &name.name))
+            .collect()
+    }
+
+    fn path_with_name(&self, entry_id: u64, parent: MftReference, name: &str) -> PathBuf {
+        // MFT entry 5 is the root path.
+        if parent.entry == 5 {
+            return PathBuf::from(name);
+        }
+
+        if parent.entry == entry_id || parent.entry == 0 {
+            trace!("Found orphaned entry ID {}", entry_id);
+            return PathBuf::from("[Orphaned]").join(name);
+        }
+
+        self.parent_path(parent).join(name)
+    }
+
+    /// Resolves the path of a parent directory, walking up to the root.
+    fn parent_path(&self, parent: MftReference) -> PathBuf {
+        let mut components = vec![];
+        let mut current = parent;
+        let mut depth = 0;
+
+        let prefix = loop {
+            // Parent chains this long are assumed to be cyclic.
+            if depth >= self.limits.max_path_depth {
+                break PathBuf::from("[Unknown]");
+            }
+            depth += 1;
+
+            let entry = match self.lookup(current) {
+                Some(entry) => entry,
+                // Parent is maybe corrupted or incomplete, use a sentinel instead.
+                None => break PathBuf::from("[Unknown]"),
+            };
+
+            match &entry.name {
+                Some(_) if !entry.is_dir => break PathBuf::from("[Unknown]"),
+                Some(name) => {
+                    components.push(name.as_str());
+
+                    match entry.parent.entry {
+                        5 => break PathBuf::new(),
+                        parent if parent == current.entry || parent == 0 => {
+                            break PathBuf::from("[Orphaned]")
+                        }
+                        _ => current = entry.parent,
+                    }
+                }
+                // An extension record, its path is the one of its base entry.
+                None => match entry.parent.entry {
+                    // I have a parent, which doesn't have a filename attribute.
+                    // Default to root.
+                    0 => break PathBuf::new(),
+                    _ if !entry.is_dir => break PathBuf::from("[Unknown]"),
+                    _ => current = entry.parent,
+                },
+            }
+        };
+
+        components
+            .iter()
+            .rev()
+            .fold(prefix, |path, component| path.join(component))
+    }
+}
@@ -0,0 +1,433 @@
+//! A super-timeline of all the timestamps in the MFT, as individual MACB events.
+//!
+//! `TimelineEvent::iter_from_entry` explodes an entry into its events, and `TimelineSorter` sorts them by time,
+//! spilling sorted runs to temporary files when there are more events than it should keep in memory.
+use crate::attribute::x90::IndexRootAttr;
+use crate::attribute::{FileAttributeFlags, MftAttribute, MftAttributeContent, MftAttributeType};
+use crate::err::{Error, Result};
+use crate::{FileNameAttr, FileTime, MftEntry};
+
+use byteorder::{LittleEndian, ReadBytesExt, WriteBytesExt};
+use log::debug;
+use serde::Serialize;
+
+use std::cmp::Reverse;
+use std::collections::BinaryHeap;
+use std::fs::File;
+use std::io::{self, BufReader, BufWriter, Read, Seek, SeekFrom, Write};
+use std::path::PathBuf;
+
+/// Which of the four NTFS timestamps an event is for.
+#[derive(Serialize, Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum TimestampKind {
+    Modified = 0,
+    Accessed = 1,
+    /// The MFT entry itself was modified.
+    MftModified = 2,
+    /// Birth.
+    Created = 3,
+}
+
+impl TimestampKind {
+    /// The letter of the timestamp in MACB notation.
+    pub fn macb(self) -> char {
+        match self {
+            TimestampKind::Modified => 'M',
+            TimestampKind::Accessed => 'A',
+            TimestampKind::MftModified => 'C',
+            TimestampKind::Created => 'B',
+        }
+    }
+
+    fn from_u8(value: u8) -> Option<Self> {
+        match value {
+            0 => Some(TimestampKind::Modified),
+            1 => Some(TimestampKind::Accessed),
+            2 => Some(TimestampKind::MftModified),
+            3 => Some(TimestampKind::Created),
+            _ => None,
+        }
+    }
+}
+
+/// Where the timestamp of an event was found.
+#[derive(Serialize, Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum TimelineSource {
+    StandardInformation = 0,
+    FileName = 1,
+    /// An entry of a `$I30` index root, describing a file in the directory.
+    IndexEntry = 2,
+}
+
+impl TimelineSource {
+    fn from_u8(value: u8) -> Option<Self> {
+        match value {
+            0 => Some(TimelineSource::StandardInformation),
+            1 => Some(TimelineSource::FileName),
+            2 => Some(TimelineSource::IndexEntry),
+            _ => None,
+        }
+    }
+}
+
+/// A single timestamp of an entry.
+///
+/// Events are ordered by time first (then by entry, source and kind), which is the order of a timeline.
+#[derive(Serialize, Debug, Clone, PartialEq, Eq, PartialOrd, Ord)]
+pub struct TimelineEvent {
+    #[serde(serialize_with = "crate::csv::time_as_string")]
+    pub timestamp: FileTime,
+    /// The entry the attribute was found in (for index entries, the directory).
+    pub entry_id: u64,
+    pub sequence: u16,
+    pub source: TimelineSource,
+    pub kind: TimestampKind,
+    /// The full path of the entry.
+    pub full_path: PathBuf,
+    /// The name in the `$FILE_NAME` attribute or index entry, `None` for `$STANDARD_INFORMATION` events.
+    pub name: Option<String>,
+    /// For index entries, the entry of the file they describe.
+    pub referenced_entry_id: Option<u64>,
+    pub referenced_sequence: Option<u16>,
+    #[serde(serialize_with = "crate::csv::flags_as_string")]
+    pub flags: FileAttributeFlags,
+}
+
+impl TimelineEvent {
+    /// Explodes the entry into an event per timestamp of its `$STANDARD_INFORMATION`, `$FILE_NAME`
+    /// and `$I30` index root entries. Events are not sorted.
+    pub fn iter_from_entry(
+        entry: &MftEntry,
+        full_path: PathBuf,
+    ) -> impl Iterator<Item = TimelineEvent> + '_ {
+        entry
+            .iter_attributes()
+            .filter_map(Result::ok)
+            .flat_map(timestamps_of)
+            .flat_map(move |timestamps| {
+                let full_path = full_path.clone();
+                timestamps
+                    .times
+                    .into_iter()
+                    .map(move |(kind, timestamp)| TimelineEvent {
+                        timestamp,
+                        entry_id: entry.header.record_number,
+                        sequence: entry.header.sequence,
+                        source: timestamps.source,
+                        kind,
+                        full_path: full_path.clone(),
+                        name: timestamps.name.clone(),
+                        referenced_entry_id: timestamps.reference.map(|r| r.0),
+                        referenced_sequence: timestamps.reference.map(|r| r.1),
+                        flags: timestamps.flags,
+                    })
+            })
+    }
+
+    fn write_to<W: Write>(&self, writer: &mut W) -> io::Result<()> {
+        writer.write_u64::<LittleEndian>(self.timestamp.raw())?;
+        writer.write_u64::<LittleEndian>(self.entry_id)?;
+        writer.write_u16::<LittleEndian>(self.sequence)?;
+        writer.write_u8(self.source as u8)?;
+        writer.write_u8(self.kind as u8)?;
+        write_string(writer, Some(&self.full_path.to_string_lossy()))?;
+        write_string(writer, self.name.as_deref())?;
+        writer.write_u64::<LittleEndian>(self.referenced_entry_id.unwrap_or(u64::MAX))?;
+        writer.write_u16::<LittleEndian>(self.referenced_sequence.unwrap_or(0))?;
+        writer.write_u32::<LittleEndian>(self.flags.bits())
+    }
+
+    /// Reads an event written with `write_to`, or `None` at the end of the run.
+    fn read_from<R: Read>(reader: &mut R) -> io::Result<Option<TimelineEvent>> {
+        let timestamp = match reader.read_u64::<LittleEndian>() {
+            Ok(raw) => FileTime::from_raw(raw),
+            Err(e) if e.kind() == io::ErrorKind::UnexpectedEof => return Ok(None),
+            Err(e) => return Err(e),
+        };
+        let entry_id = reader.read_u64::<LittleEndian>()?;
+        let sequence = reader.read_u16::<LittleEndian>()?;
+        let source = TimelineSource::from_u8(reader.read_u8()?).ok_or_else(corrupted_run)?;
+        let kind = TimestampKind::from_u8(reader.read_u8()?).ok_or_else(corrupted_run)?;
+        let full_path = PathBuf::from(read_string(reader)?.unwrap_or_default());
+        let name = read_string(reader)?;
+        let referenced_entry_id = reader.read_u64::<LittleEndian>()?;
+        let referenced_sequence = reader.read_u16::<LittleEndian>()?;
+        let flags = FileAttributeFlags::from_bits_truncate(reader.read_u32::<LittleEndian>()?);
+
+        let reference = (referenced_entry_id != u64::MAX).then_some(referenced_entry_id);
+
+        Ok(Some(TimelineEvent {
+            timestamp,
+            entry_id,
+            sequence,
+            source,
+            kind,
+            full_path,
+            name,
+            referenced_entry_id: reference,
+            referenced_sequence: reference.map(|_| referenced_sequence),
+            flags,
+        }))
+    }
+}
+
+/// The four timestamps of an attribute (or index entry).
+struct Timestamps {
+    source: TimelineSource,
+    name: Option<String>,
+    reference: Option<(u64, u16)>,
+    flags: FileAttributeFlags,
+    times: [(TimestampKind, FileTime); 4],
+}
+
+fn timestamps_of(attribute: MftAttribute) -> Vec<Timestamps> {
+    match attribute.data {
+        MftAttributeContent::AttrX10(info) => vec![Timestamps {
+            source: TimelineSource::StandardInformation,
+            name: None,
+            reference: None,
+            flags: info.file_flags,
+            times: [
+                (TimestampKind::Modified, info.modified),
+                (TimestampKind::Accessed, info.accessed),
+                (TimestampKind::MftModified, info.mft_modified),
+                (TimestampKind::Created, info.created),
+            ],
+        }],
+        MftAttributeContent::AttrX30(file_name) => vec![Timestamps {
+            source: TimelineSource::FileName,
+            flags: file_name.flags,
+            reference: None,
+            times: file_name_times(&file_name),
+            name: Some(file_name.name),
+        }],
+        MftAttributeContent::AttrX90(index_root) if is_file_name_index(&index_root) => index_root
+            .index_entries
+            .index_entries
+            .into_iter()
+            .map(|index_entry| {
+                let file_name = index_entry.fname_info;
+                Timestamps {
+                    source: TimelineSource::IndexEntry,
+                    flags: file_name.flags,
+                    reference: Some((
+                        index_entry.mft_reference.entry,
+                        index_entry.mft_reference.sequence,
+                    )),
+                    times: file_name_times(&file_name),
+                    name: Some(file_name.name),
+                }
+            })
+            .collect(),
+        _ => vec![],
+    }
+}
+
+fn file_name_times(file_name: &FileNameAttr) -> [(TimestampKind, FileTime); 4] {
+    [
+        (TimestampKind::Modified, file_name.modified),
+        (TimestampKind::Accessed, file_name.accessed),
+        (TimestampKind::MftModified, file_name.mft_modified),
+        (TimestampKind::Created, file_name.created),
+    ]
+}
+
+fn is_file_name_index(index_root: &IndexRootAttr) -> bool {
+    index_root.attribute_type == MftAttributeType::FileName as u32
+}
+
+fn write_string<W: Write>(writer: &mut W, s: Option<&str>) -> io::Result<()> {
+    match s {
+        Some(s) => {
+            writer.write_u32::<LittleEndian>(s.len() as u32)?;
+            writer.write_all(s.as_bytes())
+        }
+        None => writer.write_u32::<LittleEndian>(u32::MAX),
+    }
+}
+
+fn read_string<R: Read>(reader: &mut R) -> io::Result<Option<String>> {
+    let length = reader.read_u32::<LittleEndian>()?;
+    if length == u32::MAX {
+        return Ok(None);
+    }
+
+    let mut bytes = vec![];
+    reader.take(u64::from(length)).read_to_end(&mut bytes)?;
+    if bytes.len() != length as usize {
+        return Err(io::ErrorKind::UnexpectedEof.into());
+    }
+
+    String::from_utf8(bytes)
+        .map(Some)
+        .map_err(|_| corrupted_run())
+}
+
+fn corrupted_run() -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, "Corrupted timeline run")
+}
+
+/// Sorts any number of events by time.
+///
+/// Up to `max_in_memory` events are buffered, and once there are more, each sorted buffer is spilled to a
+/// temporary file. `into_sorted` then merges the runs, so memory use stays bounded for huge MFTs.
+pub struct TimelineSorter {
+    max_in_memory: usize,
+    buffer: Vec<TimelineEvent>,
+    runs: Vec<File>,
+}
+
+impl Default for TimelineSorter {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl TimelineSorter {
+    pub fn new() -> Self {
+        TimelineSorter {
+            max_in_memory: 1_000_000,
+            buffer: vec![],
+            runs: vec![],
+        }
+    }
+
+    /// Sets how many events are kept in memory before a sorted run is spilled to disk, 1,000,000 by default.
+    pub fn with_max_in_memory(mut self, max_in_memory: usize) -> Self {
+        self.max_in_memory = max_in_memory.max(1);
+        self
+    }
+
+    pub fn push(&mut self, event: TimelineEvent) -> Result<()> {
+        self.buffer.push(event);
+
+        if self.buffer.len() >= self.max_in_memory {
+            self.spill()?;
+        }
+
+        Ok(())
+    }
+
+    /// The number of runs spilled to disk so far.
+    pub fn spilled_runs(&self) -> usize {
+        self.runs.len()
+    }
+
+    /// Sorts the events pushed so far, merging the runs spilled to disk with the ones still in memory.
+    pub fn into_sorted(mut self) -> Result<SortedTimeline> {
+        self.buffer.sort_unstable();
+
+        let mut runs = vec![Run::Memory(self.buffer.into_iter())];
+        for mut file in self.runs {
+            file.seek(SeekFrom::Start(0))?;
+            runs.push(Run::File(BufReader::new(file)));
+        }
+
+        let mut heap = BinaryHeap::with_capacity(runs.len());
+        for (index, run) in runs.iter_mut().enumerate() {
+            if let Some(event) = run.next_event()? {
+                heap.push(Reverse((event, index)));
+            }
+        }
+
+        Ok(SortedTimeline {
+            runs,
+            heap,
+            error: None,
+        })
+    }
+
+    fn spill(&mut self) -> Result<()> {
+        self.buffer.sort_unstable();
+        debug!("Spilling a run of {} timeline events", self.buffer.len());
+
+        let mut writer = BufWriter::new(tempfile::tempfile()?);
+        for event in self.buffer.drain(..) {
+            event.write_to(&mut writer)?;
+        }
+
+        let file = writer.into_inner().map_err(|e| e.into_error())?;
+        self.runs.push(file);
+
+        Ok(())
+    }
+}
+
+enum Run {
+    Memory(std::vec::IntoIter<TimelineEvent>),
+    File(BufReader<File>),
+}
+
+impl Run {
+    fn next_event(&mut self) -> Result<Option<TimelineEvent>> {
+        match self {
+            Run::Memory(events) => Ok(events.next()),
+            Run::File(reader) => Ok(TimelineEvent::read_from(reader)?),
+        }
+    }
+}
+
+/// The events of a `TimelineSorter`, in order.
+///
+/// If a spilled run cannot be read, the error is returned (after the event before it), and ends the timeline.
+pub struct SortedTimeline {
+    runs: Vec<Run>,
+    heap: BinaryHeap<Reverse<(TimelineEvent, usize)>>,
+    error: Option<Error>,
+}
+
+impl Iterator for SortedTimeline {
+    type Item = Result<TimelineEvent>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if let Some(e) = self.error.take() {
+            return Some(Err(e));
+        }
+
+        let Reverse((event, index)) = self.heap.pop()?;
+
+        match self.runs[index].next_event() {
+            Ok(Some(next)) => self.heap.push(Reverse((next, index))),
+            Ok(None) => {}
+            Err(e) => {
+                self.heap.clear();
+                self.error = Some(e);
+            }
+        }
+
+        Some(Ok(event))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_sorted_timeline_returns_the_event_before_a_read_error() {
+        let entry =
+            MftEntry::from_buffer(include_bytes!("../samples/entry_single_file").to_vec(), 0)
+                .unwrap();
+        let mut events: Vec<TimelineEvent> =
+            TimelineEvent::iter_from_entry(&entry, PathBuf::new()).collect();
+        events.sort();
+
+        let mut sorter = TimelineSorter::new().with_max_in_memory(events.len());
+        for event in events.iter().cloned() {
+            sorter.push(event).unwrap();
+        }
+        assert_eq!(sorter.spilled_runs(), 1);
+
+        // Cut the run in the middle of its second event.
+        let mut first_event = vec![];
+        events[0].write_to(&mut first_event).unwrap();
+        sorter.runs[0]
+            .set_len(first_event.len() as u64 + 12)
+            .unwrap();
+
+        let mut timeline = sorter.into_sorted().unwrap();
+        assert_eq!(timeline.next().unwrap().unwrap(), events[0]);
+        assert!(timeline.next().unwrap().is_err());
+        assert!(timeline.next().is_none());
+    }
+}
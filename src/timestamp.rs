@@ -0,0 +1,386 @@
+//! NTFS timestamps, which keep their raw FILETIME value.
+use crate::err::{Error, Result};
+use crate::timezone::OutputTimeZone;
+
+use byteorder::{LittleEndian, ReadBytesExt};
+use chrono::{DateTime, Datelike, TimeZone, Utc};
+use serde::de::{self, Deserialize, Deserializer, Unexpected, Visitor};
+use serde::ser::SerializeMap;
+use serde::{Serialize, Serializer};
+
+use std::cell::RefCell;
+use std::fmt;
+use std::io::Read;
+use std::str::FromStr;
+
+/// 100ns intervals in a second.
+const INTERVALS_PER_SECOND: u64 = 10_000_000;
+/// Seconds between 1601-01-01 (the FILETIME epoch) and the Unix epoch.
+const EPOCH_DIFFERENCE: i64 = 11_644_473_600;
+
+/// A FILETIME: the number of 100ns intervals since 1601-01-01 (UTC).
+///
+/// The raw value is kept, so no precision is lost and zeroed or corrupted timestamps can be told apart
+/// from real ones (see `FileTime::value`).
+/// Times are serialized in RFC 3339 with all seven fractional digits, such as `2013-08-12T03:31:30.1234567Z`,
+/// unless other `TimestampOptions` are in effect.
+/// Timestamps which were never set are serialized as `null`, and out of range ones as `{"out_of_range": <raw>}`,
+/// so they can't be mistaken for a time in any format.
+///
+/// `FileTime`s are ordered by their raw value, which is chronological for times in range.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Default)]
+pub struct FileTime(u64);
+
+/// What a `FileTime` holds.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FileTimeValue {
+    /// The FILETIME is 0.
+    NotSet,
+    /// The FILETIME is negative (when read as the signed value Windows uses), or after the year 9999.
+    OutOfRange,
+    Time(DateTime<Utc>),
+}
+
+impl FileTime {
+    pub fn from_raw(raw: u64) -> Self {
+        FileTime(raw)
+    }
+
+    pub fn from_reader<R: Read>(reader: &mut R) -> Result<Self> {
+        let raw = reader
+            .read_u64::<LittleEndian>()
+            .map_err(|e| Error::failed_to_read_windows_time(e.into()))?;
+
+        Ok(FileTime(raw))
+    }
+
+    /// The FILETIME, as it is stored in the record.
+    pub fn raw(&self) -> u64 {
+        self.0
+    }
+
+    pub fn value(&self) -> FileTimeValue {
+        if self.0 == 0 {
+            return FileTimeValue::NotSet;
+        }
+
+        if self.0 > i64::MAX as u64 {
+            return FileTimeValue::OutOfRange;
+        }
+
+        let seconds = (self.0 / INTERVALS_PER_SECOND) as i64 - EPOCH_DIFFERENCE;
+        let nanoseconds = (self.0 % INTERVALS_PER_SECOND) as u32 * 100;
+
+        match Utc.timestamp_opt(seconds, nanoseconds).single() {
+            Some(time) if time.year() <= 9999 => FileTimeValue::Time(time),
+            _ => FileTimeValue::OutOfRange,
+        }
+    }
+
+    /// The time, if the timestamp is set and in range.
+    pub fn to_datetime(&self) -> Option<DateTime<Utc>> {
+        match self.value() {
+            FileTimeValue::Time(time) => Some(time),
+            _ => None,
+        }
+    }
+
+    pub fn is_set(&self) -> bool {
+        self.value() != FileTimeValue::NotSet
+    }
+
+    /// The 100ns intervals past the second.
+    pub fn subsecond_intervals(&self) -> u32 {
+        (self.0 % INTERVALS_PER_SECOND) as u32
+    }
+}
+
+impl From<DateTime<Utc>> for FileTime {
+    /// Times before 1601 are clamped to it, times after 30828 saturate.
+    fn from(time: DateTime<Utc>) -> Self {
+        let seconds = (time.timestamp() + EPOCH_DIFFERENCE).max(0) as u64;
+        let intervals = u64::from(time.timestamp_subsec_nanos() / 100);
+
+        FileTime(
+            seconds
+                .saturating_mul(INTERVALS_PER_SECOND)
+                .saturating_add(intervals),
+        )
+    }
+}
+
+impl fmt::Display for FileTime {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self.value() {
+            FileTimeValue::NotSet => write!(f, "Not set"),
+            FileTimeValue::OutOfRange => write!(f, "Out of range ({:#x})", self.0),
+            FileTimeValue::Time(time) => write!(
+                f,
+                "{}.{:07}Z",
+                time.format("%Y-%m-%dT%H:%M:%S"),
+                self.subsecond_intervals()
+            ),
+        }
+    }
+}
+
+impl Serialize for FileTime {
+    fn serialize<S>(&self, serializer: S) -> ::std::result::Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        let time = match self.value() {
+            FileTimeValue::NotSet => return serializer.serialize_none(),
+            FileTimeValue::OutOfRange => {
+                let mut map = serializer.serialize_map(Some(1))?;
+                map.serialize_entry("out_of_range", &self.0)?;
+                return map.end();
+            }
+            FileTimeValue::Time(time) => time,
+        };
+
+        OPTIONS.with(|options| {
+            let options = options.borrow();
+            match options.format {
+                TimestampFormat::Iso8601 => {
+                    serializer.collect_str(&self.iso8601(&time, &options.time_zone))
+                }
+                TimestampFormat::EpochSeconds => serializer.serialize_i64(self.epoch_seconds()),
+                TimestampFormat::EpochFraction => serializer.collect_str(&self.epoch_fraction()),
+                TimestampFormat::Filetime => serializer.serialize_u64(self.0),
+            }
+        })
+    }
+}
+
+/// `FileTime`s are read back with the `TimestampOptions` in effect, like they are serialized:
+/// integers are epoch seconds with `TimestampFormat::EpochSeconds`, and raw FILETIMEs otherwise.
+/// ISO 8601 (in any offset) and epoch fraction strings are both accepted, `null` is an unset time,
+/// and `{"out_of_range": <raw>}` an out of range one.
+///
+/// Times read back are the ones which were serialized, but for `TimestampFormat::EpochSeconds`
+/// which drops the fraction of the second.
+impl<'de> Deserialize<'de> for FileTime {
+    fn deserialize<D>(deserializer: D) -> ::std::result::Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        deserializer.deserialize_any(FileTimeVisitor)
+    }
+}
+
+struct FileTimeVisitor;
+
+impl<'de> Visitor<'de> for FileTimeVisitor {
+    type Value = FileTime;
+
+    fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "a timestamp, a number, an out of range time or null")
+    }
+
+    fn visit_unit<E: de::Error>(self) -> ::std::result::Result<FileTime, E> {
+        Ok(FileTime(0))
+    }
+
+    fn visit_none<E: de::Error>(self) -> ::std::result::Result<FileTime, E> {
+        Ok(FileTime(0))
+    }
+
+    fn visit_some<D>(self, deserializer: D) -> ::std::result::Result<FileTime, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        FileTime::deserialize(deserializer)
+    }
+
+    fn visit_u64<E: de::Error>(self, v: u64) -> ::std::result::Result<FileTime, E> {
+        let epoch_seconds =
+            OPTIONS.with(|options| options.borrow().format) == TimestampFormat::EpochSeconds;
+
+        if epoch_seconds {
+            return FileTime::from_epoch_intervals(
+                i128::from(v) * i128::from(INTERVALS_PER_SECOND),
+            )
+            .ok_or_else(|| E::invalid_value(Unexpected::Unsigned(v), &self));
+        }
+
+        Ok(FileTime(v))
+    }
+
+    fn visit_i64<E: de::Error>(self, v: i64) -> ::std::result::Result<FileTime, E> {
+        match u64::try_from(v) {
+            Ok(v) => self.visit_u64(v),
+            // Only epoch seconds are negative.
+            Err(_) => {
+                FileTime::from_epoch_intervals(i128::from(v) * i128::from(INTERVALS_PER_SECOND))
+                    .ok_or_else(|| E::invalid_value(Unexpected::Signed(v), &self))
+            }
+        }
+    }
+
+    fn visit_map<A>(self, mut map: A) -> ::std::result::Result<FileTime, A::Error>
+    where
+        A: de::MapAccess<'de>,
+    {
+        let raw = match map.next_key::<String>()?.as_deref() {
+            Some("out_of_range") => map.next_value::<u64>()?,
+            Some(key) => return Err(de::Error::unknown_field(key, &["out_of_range"])),
+            None => return Err(de::Error::missing_field("out_of_range")),
+        };
+
+        if let Some(key) = map.next_key::<String>()? {
+            return Err(de::Error::unknown_field(&key, &["out_of_range"]));
+        }
+
+        Ok(FileTime(raw))
+    }
+
+    fn visit_str<E: de::Error>(self, v: &str) -> ::std::result::Result<FileTime, E> {
+        let time = if v.contains('T') {
+            DateTime::parse_from_rfc3339(v)
+                .ok()
+                .map(|time| FileTime::from(time.with_timezone(&Utc)))
+        } else {
+            FileTime::parse_epoch_fraction(v)
+        };
+
+        time.ok_or_else(|| E::invalid_value(Unexpected::Str(v), &self))
+    }
+}
+
+impl FileTime {
+    /// The `FileTime` of the given 100ns intervals since the Unix epoch, if it can be represented.
+    fn from_epoch_intervals(intervals: i128) -> Option<FileTime> {
+        let raw = intervals + i128::from(EPOCH_DIFFERENCE) * i128::from(INTERVALS_PER_SECOND);
+        u64::try_from(raw).ok().map(FileTime)
+    }
+
+    /// Parses seconds since the Unix epoch with up to seven fractional digits, such as `-1.5` or `1204258356.0000000`.
+    fn parse_epoch_fraction(s: &str) -> Option<FileTime> {
+        let (negative, unsigned) = match s.strip_prefix('-') {
+            Some(unsigned) => (true, unsigned),
+            None => (false, s),
+        };
+        let (seconds, fraction) = unsigned.split_once('.').unwrap_or((unsigned, ""));
+
+        let is_number = |digits: &str| digits.bytes().all(|b| b.is_ascii_digit());
+        if seconds.is_empty() || fraction.len() > 7 || !is_number(seconds) || !is_number(fraction) {
+            return None;
+        }
+
+        let seconds: i128 = seconds.parse().ok()?;
+        let fraction: i128 = format!("{:0<7}", fraction).parse().ok()?;
+        let intervals = seconds.checked_mul(i128::from(INTERVALS_PER_SECOND))? + fraction;
+
+        FileTime::from_epoch_intervals(if negative { -intervals } else { intervals })
+    }
+
+    /// 100ns intervals since the Unix epoch.
+    fn epoch_intervals(&self) -> i128 {
+        i128::from(self.0) - i128::from(EPOCH_DIFFERENCE) * i128::from(INTERVALS_PER_SECOND)
+    }
+
+    fn epoch_seconds(&self) -> i64 {
+        self.epoch_intervals()
+            .div_euclid(i128::from(INTERVALS_PER_SECOND)) as i64
+    }
+
+    fn epoch_fraction(&self) -> String {
+        let intervals = self.epoch_intervals();
+        let sign = if intervals < 0 { "-" } else { "" };
+        let intervals = intervals.unsigned_abs();
+
+        format!(
+            "{}{}.{:07}",
+            sign,
+            intervals / u128::from(INTERVALS_PER_SECOND),
+            intervals % u128::from(INTERVALS_PER_SECOND)
+        )
+    }
+
+    fn iso8601(&self, time: &DateTime<Utc>, time_zone: &OutputTimeZone) -> String {
+        if *time_zone == OutputTimeZone::Utc {
+            return self.to_string();
+        }
+
+        let local = time.with_timezone(&time_zone.offset_at(time));
+        format!(
+            "{}.{:07}{}",
+            local.format("%Y-%m-%dT%H:%M:%S"),
+            self.subsecond_intervals(),
+            local.format("%:z")
+        )
+    }
+}
+
+thread_local! {
+    static OPTIONS: RefCell<TimestampOptions> = RefCell::new(TimestampOptions::default());
+}
+
+/// How `FileTime`s are serialized.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum TimestampFormat {
+    /// RFC 3339 (ISO 8601) with seven fractional digits, in the time zone of the `TimestampOptions`.
+    #[default]
+    Iso8601,
+    /// Whole seconds since the Unix epoch.
+    EpochSeconds,
+    /// Seconds since the Unix epoch with seven fractional digits, as a string so no precision is lost.
+    EpochFraction,
+    /// The raw FILETIME.
+    Filetime,
+}
+
+impl FromStr for TimestampFormat {
+    type Err = String;
+
+    fn from_str(s: &str) -> ::std::result::Result<Self, Self::Err> {
+        match s {
+            "iso8601" => Ok(TimestampFormat::Iso8601),
+            "epoch" => Ok(TimestampFormat::EpochSeconds),
+            "epoch-fraction" => Ok(TimestampFormat::EpochFraction),
+            "filetime" => Ok(TimestampFormat::Filetime),
+            _ => Err(format!("unknown timestamp format `{}`", s)),
+        }
+    }
+}
+
+/// The format and time zone `FileTime`s are serialized in.
+///
+/// The time zone only applies to `TimestampFormat::Iso8601`.
+/// Unset and out of range timestamps are serialized the same way whatever the options.
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct TimestampOptions {
+    pub format: TimestampFormat,
+    pub time_zone: OutputTimeZone,
+}
+
+impl TimestampOptions {
+    pub fn with_format(mut self, format: TimestampFormat) -> Self {
+        self.format = format;
+        self
+    }
+
+    pub fn with_time_zone(mut self, time_zone: OutputTimeZone) -> Self {
+        self.time_zone = time_zone;
+        self
+    }
+
+    /// Runs `f` with these options in effect for serializing (and deserializing) `FileTime`s on the current thread.
+    pub fn scope<R>(&self, f: impl FnOnce() -> R) -> R {
+        struct Restore(Option<TimestampOptions>);
+
+        impl Drop for Restore {
+            fn drop(&mut self) {
+                if let Some(previous) = self.0.take() {
+                    OPTIONS.with(|options| *options.borrow_mut() = previous);
+                }
+            }
+        }
+
+        let previous = OPTIONS.with(|options| options.replace(self.clone()));
+        let _restore = Restore(Some(previous));
+
+        f()
+    }
+}
@@ -0,0 +1,77 @@
+//! Time zones for rendering timestamps: UTC, fixed offsets, and IANA time zones
+//! (from the tz database built into `chrono-tz`).
+use crate::err::{Error, Result};
+
+use chrono::{DateTime, FixedOffset, Offset, TimeZone, Utc};
+use chrono_tz::Tz;
+
+/// The time zone timestamps are rendered in.
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub enum OutputTimeZone {
+    #[default]
+    Utc,
+    Fixed(FixedOffset),
+    /// An IANA time zone, such as `Europe/Berlin`.
+    Named(Tz),
+}
+
+impl OutputTimeZone {
+    /// Parses `UTC`, a fixed offset (`+02:00`, `-0530`, `+3`), or the name of an IANA time zone.
+    pub fn parse(s: &str) -> Result<Self> {
+        if s.eq_ignore_ascii_case("utc") || s.eq_ignore_ascii_case("z") {
+            return Ok(OutputTimeZone::Utc);
+        }
+
+        if s.starts_with('+') || s.starts_with('-') {
+            let offset = parse_fixed_offset(s).ok_or_else(|| Error::InvalidTimeZone {
+                name: s.to_string(),
+                detail: "expected an offset such as `+02:00`".to_string(),
+            })?;
+            return Ok(OutputTimeZone::Fixed(offset));
+        }
+
+        let zone = s.parse::<Tz>().map_err(|_| Error::InvalidTimeZone {
+            name: s.to_string(),
+            detail: "not the name of a time zone".to_string(),
+        })?;
+
+        Ok(OutputTimeZone::Named(zone))
+    }
+
+    /// The offset from UTC at the given time.
+    pub fn offset_at(&self, time: &DateTime<Utc>) -> FixedOffset {
+        match self {
+            OutputTimeZone::Utc => Utc.fix(),
+            OutputTimeZone::Fixed(offset) => *offset,
+            OutputTimeZone::Named(zone) => zone.offset_from_utc_datetime(&time.naive_utc()).fix(),
+        }
+    }
+}
+
+/// `+02:00`, `-0530`, `+3` and so on, in seconds east of UTC.
+fn parse_fixed_offset(s: &str) -> Option<FixedOffset> {
+    let (sign, rest) = match s.split_at(1) {
+        ("+", rest) => (1, rest),
+        ("-", rest) => (-1, rest),
+        _ => return None,
+    };
+
+    // Only digits, which also keeps `split_at` on a character boundary.
+    if !rest.chars().all(|c| c.is_ascii_digit() || c == ':') {
+        return None;
+    }
+
+    let (hours, minutes) = match rest.split_once(':') {
+        Some((hours, minutes)) => (hours, minutes),
+        None if rest.len() == 4 => rest.split_at(2),
+        None => (rest, "0"),
+    };
+    let hours: i32 = hours.parse().ok()?;
+    let minutes: i32 = minutes.parse().ok()?;
+
+    if !(0..=23).contains(&hours) || !(0..=59).contains(&minutes) {
+        return None;
+    }
+
+    FixedOffset::east_opt(sign * (hours * 3600 + minutes * 60))
+}
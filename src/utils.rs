@@ -1,7 +1,11 @@
 use byteorder::ReadBytesExt;
+use serde::de::{self, Deserializer, Unexpected};
+use serde::Deserialize;
 use std::char::decode_utf16;
 use std::fmt::Write;
-use std::io::{self, Read, Seek};
+use std::io::{self, Read, Seek, SeekFrom};
+use winstructs::guid::Guid;
+use winstructs::ntfs::mft_reference::MftReference;
 
 pub fn to_hex_string(bytes: &[u8]) -> String {
     let len = bytes.len();
@@ -15,6 +19,100 @@ pub fn to_hex_string(bytes: &[u8]) -> String {
     s
 }
 
+/// The bytes of a hex string, as written by `to_hex_string`.
+/// Returns `None` if the string has an odd length or non-hex digits.
+pub fn from_hex_string(s: &str) -> Option<Vec<u8>> {
+    if s.len() % 2 != 0 {
+        return None;
+    }
+
+    (0..s.len())
+        .step_by(2)
+        .map(|i| {
+            s.get(i..i + 2)
+                .filter(|byte| byte.bytes().all(|b| b.is_ascii_hexdigit()))
+                .and_then(|byte| u8::from_str_radix(byte, 16).ok())
+        })
+        .collect()
+}
+
+/// Deserializes bytes from a hex string, the counterpart of `attribute::raw::data_as_hex`.
+pub(crate) fn data_from_hex<'de, D>(deserializer: D) -> Result<Vec<u8>, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    let s = String::deserialize(deserializer)?;
+    from_hex_string(&s)
+        .ok_or_else(|| de::Error::invalid_value(Unexpected::Str(&s), &"a hex string"))
+}
+
+/// `MftReference` (of winstructs) is only `Serialize`, this reads it back from `{"entry": .., "sequence": ..}`.
+pub(crate) fn deserialize_mft_reference<'de, D>(deserializer: D) -> Result<MftReference, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    #[derive(Deserialize)]
+    struct Reference {
+        entry: u64,
+        sequence: u16,
+    }
+
+    let reference = Reference::deserialize(deserializer)?;
+    Ok(MftReference::new(reference.entry, reference.sequence))
+}
+
+/// Parses a GUID in its display form, such as `54849625-5478-4994-A5BA-3E3B0328C30D`.
+pub fn parse_guid(s: &str) -> Option<Guid> {
+    let groups: Vec<&str> = s.split('-').collect();
+    if groups.len() != 5
+        || groups.iter().map(|g| g.len()).ne([8, 4, 4, 4, 12])
+        || !groups
+            .iter()
+            .all(|g| g.chars().all(|c| c.is_ascii_hexdigit()))
+    {
+        return None;
+    }
+
+    let data4 = from_hex_string(&format!("{}{}", groups[3], groups[4]))?;
+
+    Some(Guid::new(
+        u32::from_str_radix(groups[0], 16).ok()?,
+        u16::from_str_radix(groups[1], 16).ok()?,
+        u16::from_str_radix(groups[2], 16).ok()?,
+        data4.try_into().ok()?,
+    ))
+}
+
+/// `Guid` (of winstructs) is only `Serialize`, this reads it back from its display form.
+pub(crate) fn deserialize_guid<'de, D>(deserializer: D) -> Result<Guid, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    let s = String::deserialize(deserializer)?;
+    parse_guid(&s).ok_or_else(|| de::Error::invalid_value(Unexpected::Str(&s), &"a GUID"))
+}
+
+pub(crate) fn deserialize_optional_guid<'de, D>(deserializer: D) -> Result<Option<Guid>, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    match Option::<String>::deserialize(deserializer)? {
+        Some(s) => parse_guid(&s)
+            .map(Some)
+            .ok_or_else(|| de::Error::invalid_value(Unexpected::Str(&s), &"a GUID")),
+        None => Ok(None),
+    }
+}
+
+/// The names of the flags in the `Debug` output of bitflags, such as `ALLOCATED | INDEX_PRESENT`.
+/// Unknown bits (written in hex) and `(empty)` are left out.
+pub(crate) fn flag_names(debug: &str) -> Vec<&str> {
+    debug
+        .split(" | ")
+        .filter(|name| !name.starts_with("0x") && *name != "(empty)")
+        .collect()
+}
+
 /// Reads a utf16 string from the given stream.
 /// If `len` is given, exactly `len` u16 values are read from the stream.
 /// If `len` is None, the string is assumed to be null terminated and the stream will be read to the first null (0).
@@ -47,3 +145,32 @@ pub fn read_utf16_string<T: Read + Seek>(stream: &mut T, len: Option<usize>) ->
         .map(|r| r.map_err(|_e| io::Error::from(io::ErrorKind::InvalidData)))
         .collect()
 }
+
+/// Reads exactly `len` bytes from the stream.
+/// The buffer only grows with the data which was actually read, so a corrupted `len` cannot exhaust memory.
+pub(crate) fn read_bytes<R: Read>(reader: &mut R, len: u64) -> io::Result<Vec<u8>> {
+    let mut data = Vec::new();
+    reader.by_ref().take(len).read_to_end(&mut data)?;
+
+    if (data.len() as u64) < len {
+        return Err(io::Error::from(io::ErrorKind::UnexpectedEof));
+    }
+
+    Ok(data)
+}
+
+/// Resolves a `SeekFrom` for readers which track their own position over a stream of known size.
+pub(crate) fn seek_position(current: u64, size: u64, pos: SeekFrom) -> io::Result<u64> {
+    let new_position = match pos {
+        SeekFrom::Start(offset) => Some(offset),
+        SeekFrom::End(offset) => size.checked_add_signed(offset),
+        SeekFrom::Current(offset) => current.checked_add_signed(offset),
+    };
+
+    new_position.ok_or_else(|| {
+        io::Error::new(
+            io::ErrorKind::InvalidInput,
+            "invalid seek to a negative or overflowing position",
+        )
+    })
+}
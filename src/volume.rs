@@ -0,0 +1,295 @@
+//! Support for reading the MFT directly from an NTFS volume.
+//!
+//! The `$Boot` sector at the start of the volume tells us where the MFT starts,
+//! and the `$DATA` attribute of the first MFT entry (`$MFT` itself) maps the rest of it.
+use crate::attribute::data_run::RunType;
+use crate::attribute::header::ResidentialHeader;
+use crate::attribute::MftAttributeType;
+use crate::entry::MftEntry;
+use crate::err::{Error, Result};
+use crate::utils::seek_position;
+
+use byteorder::{LittleEndian, ReadBytesExt};
+use log::{debug, warn};
+use serde::Serialize;
+
+use std::io::{self, Read, Seek, SeekFrom};
+
+pub const NTFS_OEM_ID: &[u8; 8] = b"NTFS    ";
+
+/// The parts of the NTFS `$Boot` sector needed to locate the MFT.
+/// <https://github.com/libyal/libfsntfs/blob/main/documentation/New%20Technologies%20File%20System%20(NTFS).asciidoc#the-volume-header>
+#[derive(Serialize, Debug, Clone, PartialEq, Eq)]
+pub struct BootSector {
+    pub oem_id: [u8; 8],
+    pub bytes_per_sector: u16,
+    /// The raw value, see `BootSector::cluster_size`.
+    pub sectors_per_cluster: u8,
+    pub total_sectors: u64,
+    /// The cluster number of the start of the MFT.
+    pub mft_lcn: u64,
+    /// The cluster number of the start of the MFT mirror.
+    pub mft_mirror_lcn: u64,
+    /// Positive values are a number of clusters, negative values are a power of 2 in bytes.
+    pub clusters_per_mft_record: i8,
+    pub clusters_per_index_record: i8,
+    pub volume_serial_number: u64,
+}
+
+impl BootSector {
+    /// Parses the boot sector from the start of the volume.
+    /// Will error if the volume is not NTFS.
+    pub fn from_reader<R: Read>(reader: &mut R) -> Result<BootSector> {
+        let mut sector = [0; 512];
+        reader.read_exact(&mut sector)?;
+
+        Self::from_bytes(&sector)
+    }
+
+    pub fn from_bytes(sector: &[u8; 512]) -> Result<BootSector> {
+        let mut oem_id = [0; 8];
+        oem_id.copy_from_slice(&sector[3..11]);
+
+        if &oem_id != NTFS_OEM_ID {
+            return Err(Error::InvalidBootSector {
+                detail: format!("OEM ID is {:x?}, expected `NTFS    `", oem_id),
+            });
+        }
+
+        let mut reader = &sector[11..];
+        let bytes_per_sector = reader.read_u16::<LittleEndian>()?;
+        let sectors_per_cluster = reader.read_u8()?;
+
+        let mut reader = &sector[0x28..];
+        let total_sectors = reader.read_u64::<LittleEndian>()?;
+        let mft_lcn = reader.read_u64::<LittleEndian>()?;
+        let mft_mirror_lcn = reader.read_u64::<LittleEndian>()?;
+        let clusters_per_mft_record = reader.read_i8()?;
+
+        let mut reader = &sector[0x44..];
+        let clusters_per_index_record = reader.read_i8()?;
+
+        let mut reader = &sector[0x48..];
+        let volume_serial_number = reader.read_u64::<LittleEndian>()?;
+
+        let boot_sector = BootSector {
+            oem_id,
+            bytes_per_sector,
+            sectors_per_cluster,
+            total_sectors,
+            mft_lcn,
+            mft_mirror_lcn,
+            clusters_per_mft_record,
+            clusters_per_index_record,
+            volume_serial_number,
+        };
+
+        if !bytes_per_sector.is_power_of_two() || bytes_per_sector < 256 {
+            return Err(Error::InvalidBootSector {
+                detail: format!("bad sector size {}", bytes_per_sector),
+            });
+        }
+
+        let record_size = boot_sector.mft_record_size();
+        if !(256..=65536).contains(&record_size) {
+            return Err(Error::InvalidBootSector {
+                detail: format!("bad MFT record size {}", record_size),
+            });
+        }
+
+        Ok(boot_sector)
+    }
+
+    /// Checks for the NTFS OEM ID, without consuming the stream.
+    pub fn is_ntfs_volume<R: Read + Seek>(reader: &mut R) -> io::Result<bool> {
+        let start = reader.stream_position()?;
+
+        let mut header = [0; 11];
+        let is_ntfs = match reader.read_exact(&mut header) {
+            Ok(()) => &header[3..11] == NTFS_OEM_ID,
+            Err(e) if e.kind() == io::ErrorKind::UnexpectedEof => false,
+            Err(e) => return Err(e),
+        };
+
+        reader.seek(SeekFrom::Start(start))?;
+
+        Ok(is_ntfs)
+    }
+
+    pub fn cluster_size(&self) -> u64 {
+        // Since Windows 10 1709, values above 0x80 are a negative power of 2 (to allow 2MB clusters).
+        let sectors_per_cluster = match self.sectors_per_cluster {
+            spc if spc > 0x80 => 1_u64 << (256 - u32::from(spc)).min(31),
+            spc => u64::from(spc),
+        };
+
+        sectors_per_cluster * u64::from(self.bytes_per_sector)
+    }
+
+    pub fn mft_record_size(&self) -> u64 {
+        match self.clusters_per_mft_record {
+            n if n > 0 => n as u64 * self.cluster_size(),
+            n => 1_u64 << u32::from(n.unsigned_abs()).min(31),
+        }
+    }
+
+    /// The offset of the MFT from the start of the volume, in bytes.
+    pub fn mft_offset(&self) -> u64 {
+        self.mft_lcn.saturating_mul(self.cluster_size())
+    }
+}
+
+/// A contiguous part of the MFT, mapped to the volume.
+#[derive(Debug, Clone)]
+struct MappedRun {
+    /// Offset inside the MFT.
+    start: u64,
+    length: u64,
+    /// Offset inside the volume, or `None` for sparse runs.
+    volume_offset: Option<u64>,
+}
+
+/// Presents the MFT of an NTFS volume as a contiguous stream, following its data runs.
+pub struct VolumeMftReader<R: Read + Seek> {
+    volume: R,
+    boot_sector: BootSector,
+    runs: Vec<MappedRun>,
+    size: u64,
+    position: u64,
+}
+
+impl<R: Read + Seek> VolumeMftReader<R> {
+    /// Locates the MFT of the volume, which should start at the current position of the stream.
+    pub fn new(mut volume: R) -> Result<Self> {
+        let volume_start = volume.stream_position()?;
+        let boot_sector = BootSector::from_reader(&mut volume)?;
+        debug!("Found NTFS boot sector: {:?}", boot_sector);
+
+        volume.seek(SeekFrom::Start(volume_start + boot_sector.mft_offset()))?;
+
+        let mut record = vec![0; boot_sector.mft_record_size() as usize];
+        volume.read_exact(&mut record)?;
+        let mft_entry = MftEntry::from_buffer(record, 0)?;
+
+        let data_attribute = mft_entry
+            .iter_attributes_matching(Some(vec![MftAttributeType::DATA]))
+            .filter_map(Result::ok)
+            .find(|attribute| attribute.header.name.is_empty())
+            .ok_or_else(|| Error::InvalidBootSector {
+                detail: "the first MFT entry has no $DATA attribute".to_string(),
+            })?;
+
+        let file_size = match &data_attribute.header.residential_header {
+            ResidentialHeader::NonResident(header) => header.file_size,
+            ResidentialHeader::Resident(_) => {
+                return Err(Error::InvalidBootSector {
+                    detail: "the $DATA attribute of the MFT is resident".to_string(),
+                })
+            }
+        };
+
+        let data_runs = data_attribute
+            .data
+            .into_data_runs()
+            .map(|attribute| attribute.data_runs)
+            .unwrap_or_default();
+
+        let cluster_size = boot_sector.cluster_size();
+        let mut runs = Vec::with_capacity(data_runs.len());
+        let mut start = 0_u64;
+
+        for run in data_runs {
+            let length = run.lcn_length.saturating_mul(cluster_size);
+            let volume_offset = match run.run_type {
+                RunType::Standard => {
+                    Some(volume_start.saturating_add(run.lcn_offset.saturating_mul(cluster_size)))
+                }
+                RunType::Sparse => None,
+            };
+
+            runs.push(MappedRun {
+                start,
+                length,
+                volume_offset,
+            });
+            start = start.saturating_add(length);
+        }
+
+        // Heavily fragmented MFTs continue their runs in extension entries, via an attribute list.
+        // We only follow the runs of the base entry.
+        if start < file_size {
+            warn!(
+                "MFT data runs only cover {} out of {} bytes, the rest of the MFT will be ignored",
+                start, file_size
+            );
+        }
+
+        Ok(VolumeMftReader {
+            volume,
+            boot_sector,
+            runs,
+            size: file_size.min(start),
+            position: 0,
+        })
+    }
+
+    pub fn boot_sector(&self) -> &BootSector {
+        &self.boot_sector
+    }
+
+    /// The size of the MFT, in bytes.
+    pub fn len(&self) -> u64 {
+        self.size
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.size == 0
+    }
+
+    pub fn into_inner(self) -> R {
+        self.volume
+    }
+}
+
+impl<R: Read + Seek> Read for VolumeMftReader<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        if self.position >= self.size || buf.is_empty() {
+            return Ok(0);
+        }
+
+        let run = match self
+            .runs
+            .iter()
+            .find(|run| run.start <= self.position && self.position < run.start + run.length)
+        {
+            Some(run) => run,
+            None => return Ok(0),
+        };
+
+        let offset_in_run = self.position - run.start;
+        let available = (run.length - offset_in_run).min(self.size - self.position);
+        let to_read = (buf.len() as u64).min(available) as usize;
+
+        let read = match run.volume_offset {
+            Some(volume_offset) => {
+                self.volume
+                    .seek(SeekFrom::Start(volume_offset + offset_in_run))?;
+                self.volume.read(&mut buf[..to_read])?
+            }
+            None => {
+                buf[..to_read].iter_mut().for_each(|b| *b = 0);
+                to_read
+            }
+        };
+
+        self.position += read as u64;
+        Ok(read)
+    }
+}
+
+impl<R: Read + Seek> Seek for VolumeMftReader<R> {
+    fn seek(&mut self, pos: SeekFrom) -> io::Result<u64> {
+        self.position = seek_position(self.position, self.size, pos)?;
+        Ok(self.position)
+    }
+}
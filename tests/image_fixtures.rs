@@ -0,0 +1,481 @@
+#![allow(dead_code)]
+#![cfg(feature = "images")]
+//! Builders for small synthetic NTFS volumes and images, used by the image tests.
+use mft::MftParser;
+use std::fs;
+use std::io::{Read, Seek, Write};
+use std::path::Path;
+
+pub const SECTOR_SIZE: usize = 512;
+pub const SECTORS_PER_CLUSTER: usize = 8;
+pub const CLUSTER_SIZE: usize = SECTOR_SIZE * SECTORS_PER_CLUSTER;
+pub const RECORD_SIZE: usize = 1024;
+pub const VOLUME_CLUSTERS: usize = 12;
+
+/// The MFT is split into two fragments, with unrelated data between them.
+pub const MFT_RUNS: [usize; 2] = [4, 8];
+
+const EWF_SIGNATURE: &[u8; 8] = b"EVF\x09\x0d\x0a\xff\x00";
+
+/// Parses every entry, serialized to JSON for comparisons.
+pub fn entries_as_json<T: Read + Seek>(parser: &mut MftParser<T>) -> Vec<serde_json::Value> {
+    parser
+        .iter_entries()
+        .map(|entry| serde_json::to_value(entry.unwrap()).unwrap())
+        .collect()
+}
+
+/// Builds the `$MFT` record, describing an MFT made of one cluster at each of `MFT_RUNS`.
+pub fn mft_record() -> Vec<u8> {
+    let mft_size = (MFT_RUNS.len() * CLUSTER_SIZE) as u64;
+    let mut record = vec![0; RECORD_SIZE];
+
+    // Header
+    record[0..4].copy_from_slice(b"FILE");
+    record[4..6].copy_from_slice(&0x30_u16.to_le_bytes()); // Update sequence array offset
+    record[6..8].copy_from_slice(&3_u16.to_le_bytes()); // Update sequence array size
+    record[16..18].copy_from_slice(&1_u16.to_le_bytes()); // Sequence
+    record[18..20].copy_from_slice(&1_u16.to_le_bytes()); // Hard links
+    record[20..22].copy_from_slice(&0x38_u16.to_le_bytes()); // First attribute
+    record[22..24].copy_from_slice(&1_u16.to_le_bytes()); // Allocated
+    record[24..28].copy_from_slice(&0x88_u32.to_le_bytes()); // Used size
+    record[28..32].copy_from_slice(&(RECORD_SIZE as u32).to_le_bytes());
+
+    // Non resident, unnamed $DATA
+    let runs = [
+        0x11,
+        0x01,
+        MFT_RUNS[0] as u8,
+        0x11,
+        0x01,
+        (MFT_RUNS[1] - MFT_RUNS[0]) as u8,
+        0x00,
+        0x00,
+    ];
+    let mut attribute = vec![0; 0x40];
+    attribute[0..4].copy_from_slice(&0x80_u32.to_le_bytes());
+    attribute[4..8].copy_from_slice(&(0x40 + runs.len() as u32).to_le_bytes());
+    attribute[8] = 1;
+    attribute[10..12].copy_from_slice(&0x40_u16.to_le_bytes());
+    attribute[0x18..0x20].copy_from_slice(&(MFT_RUNS.len() as u64 - 1).to_le_bytes());
+    attribute[0x20..0x22].copy_from_slice(&0x40_u16.to_le_bytes());
+    attribute[0x28..0x30].copy_from_slice(&mft_size.to_le_bytes());
+    attribute[0x30..0x38].copy_from_slice(&mft_size.to_le_bytes());
+    attribute[0x38..0x40].copy_from_slice(&mft_size.to_le_bytes());
+    attribute.extend_from_slice(&runs);
+
+    record[0x38..0x38 + attribute.len()].copy_from_slice(&attribute);
+    let end = 0x38 + attribute.len();
+    record[end..end + 4].copy_from_slice(&0xFFFF_FFFF_u32.to_le_bytes());
+
+    // Fixups
+    let update_sequence = [0x01, 0x00];
+    record[0x30..0x32].copy_from_slice(&update_sequence);
+    for stride in 0..RECORD_SIZE / SECTOR_SIZE {
+        let end_of_sector = (stride + 1) * SECTOR_SIZE - 2;
+        let original = [record[end_of_sector], record[end_of_sector + 1]];
+        record[0x32 + stride * 2..0x34 + stride * 2].copy_from_slice(&original);
+        record[end_of_sector..end_of_sector + 2].copy_from_slice(&update_sequence);
+    }
+
+    record
+}
+
+/// The contents of the MFT: the `$MFT` record followed by the given records, padded with zeroed entries.
+pub fn mft_contents(records: &[Vec<u8>]) -> Vec<u8> {
+    let mut mft = mft_record();
+    for record in records {
+        mft.extend_from_slice(record);
+    }
+    mft.resize(MFT_RUNS.len() * CLUSTER_SIZE, 0);
+
+    mft
+}
+
+pub fn boot_sector() -> Vec<u8> {
+    let mut sector = vec![0; SECTOR_SIZE];
+    sector[0..3].copy_from_slice(&[0xEB, 0x52, 0x90]);
+    sector[3..11].copy_from_slice(b"NTFS    ");
+    sector[11..13].copy_from_slice(&(SECTOR_SIZE as u16).to_le_bytes());
+    sector[13] = SECTORS_PER_CLUSTER as u8;
+    sector[0x28..0x30]
+        .copy_from_slice(&((VOLUME_CLUSTERS * SECTORS_PER_CLUSTER) as u64).to_le_bytes());
+    sector[0x30..0x38].copy_from_slice(&(MFT_RUNS[0] as u64).to_le_bytes());
+    sector[0x38..0x40].copy_from_slice(&(MFT_RUNS[1] as u64 + 1).to_le_bytes());
+    // 2^10 bytes per record.
+    sector[0x40] = (-10_i8) as u8;
+    sector[0x44] = 1;
+    sector[0x48..0x50].copy_from_slice(&0x1234_5678_9ABC_DEF0_u64.to_le_bytes());
+    sector[510..512].copy_from_slice(&[0x55, 0xAA]);
+
+    sector
+}
+
+/// Builds a volume holding `mft` at the clusters in `MFT_RUNS`.
+/// Every other cluster is filled with junk, so reading the wrong cluster is noticeable.
+pub fn ntfs_volume(mft: &[u8]) -> Vec<u8> {
+    let mut volume = vec![0xAA; VOLUME_CLUSTERS * CLUSTER_SIZE];
+    volume[..SECTOR_SIZE].copy_from_slice(&boot_sector());
+
+    for (fragment, lcn) in mft.chunks(CLUSTER_SIZE).zip(MFT_RUNS) {
+        let start = lcn * CLUSTER_SIZE;
+        volume[start..start + fragment.len()].copy_from_slice(fragment);
+    }
+
+    volume
+}
+
+/// Writes `media` as raw segments named `{stem}.001`, `{stem}.002`, ...
+pub fn write_split_raw(dir: &Path, stem: &str, media: &[u8], segment_size: usize) {
+    for (i, segment) in media.chunks(segment_size).enumerate() {
+        fs::write(dir.join(format!("{}.{:03}", stem, i + 1)), segment).unwrap();
+    }
+}
+
+fn ewf_section(segment: &mut Vec<u8>, section_type: &str, data: &[u8], last: bool) {
+    let offset = segment.len() as u64;
+    let size = 76 + data.len() as u64;
+    let next = if last { offset } else { offset + size };
+
+    let mut descriptor = [0_u8; 76];
+    descriptor[..section_type.len()].copy_from_slice(section_type.as_bytes());
+    descriptor[16..24].copy_from_slice(&next.to_le_bytes());
+    descriptor[24..32].copy_from_slice(&size.to_le_bytes());
+
+    segment.extend_from_slice(&descriptor);
+    segment.extend_from_slice(data);
+}
+
+fn zlib(data: &[u8]) -> Vec<u8> {
+    let mut encoder = flate2::write::ZlibEncoder::new(vec![], flate2::Compression::default());
+    encoder.write_all(data).unwrap();
+    encoder.finish().unwrap()
+}
+
+/// Writes `media` as an EWF image, `{stem}.E01`, `{stem}.E02`, ...
+/// Every other chunk is compressed.
+pub fn write_ewf(dir: &Path, stem: &str, media: &[u8], chunks_per_segment: usize) {
+    let sectors_per_chunk = SECTORS_PER_CLUSTER;
+    let chunk_size = sectors_per_chunk * SECTOR_SIZE;
+    let chunks: Vec<&[u8]> = media.chunks(chunk_size).collect();
+    let segments: Vec<&[&[u8]]> = chunks.chunks(chunks_per_segment).collect();
+
+    for (segment_index, segment_chunks) in segments.iter().enumerate() {
+        let mut segment = EWF_SIGNATURE.to_vec();
+        segment.push(1);
+        segment.extend_from_slice(&(segment_index as u16 + 1).to_le_bytes());
+        segment.extend_from_slice(&[0, 0]);
+
+        if segment_index == 0 {
+            let mut volume = vec![0; 94];
+            volume[4..8].copy_from_slice(&(chunks.len() as u32).to_le_bytes());
+            volume[8..12].copy_from_slice(&(sectors_per_chunk as u32).to_le_bytes());
+            volume[12..16].copy_from_slice(&(SECTOR_SIZE as u32).to_le_bytes());
+            volume[16..24].copy_from_slice(&((media.len() / SECTOR_SIZE) as u64).to_le_bytes());
+            ewf_section(&mut segment, "volume", &volume, false);
+        }
+
+        // Chunk offsets are relative to the base offset (0), so they are absolute in the file.
+        let sectors_data_start = segment.len() as u64 + 76;
+        let mut sectors = vec![];
+        let mut table = vec![];
+        for (i, chunk) in segment_chunks.iter().enumerate() {
+            let offset = (sectors_data_start + sectors.len() as u64) as u32;
+
+            if i % 2 == 0 {
+                table.push(offset | 0x8000_0000);
+                sectors.extend_from_slice(&zlib(chunk));
+            } else {
+                table.push(offset);
+                sectors.extend_from_slice(chunk);
+                // Adler32 checksum, which is not verified.
+                sectors.extend_from_slice(&[0; 4]);
+            }
+        }
+        ewf_section(&mut segment, "sectors", &sectors, false);
+
+        let mut table_data = vec![0; 24];
+        table_data[0..4].copy_from_slice(&(table.len() as u32).to_le_bytes());
+        for entry in table {
+            table_data.extend_from_slice(&entry.to_le_bytes());
+        }
+        table_data.extend_from_slice(&[0; 4]);
+        ewf_section(&mut segment, "table", &table_data, false);
+
+        let last = segment_index == segments.len() - 1;
+        ewf_section(&mut segment, if last { "done" } else { "next" }, &[], true);
+
+        fs::write(
+            dir.join(format!("{}.E{:02}", stem, segment_index + 1)),
+            segment,
+        )
+        .unwrap();
+    }
+}
+
+/// Partitions are aligned to 64 sectors, and the first one starts at LBA 64.
+const PARTITION_ALIGNMENT: usize = 64;
+
+fn align(sectors: usize) -> usize {
+    sectors.div_ceil(PARTITION_ALIGNMENT) * PARTITION_ALIGNMENT
+}
+
+fn mbr_entry(sector: &mut [u8], slot: usize, system_id: u8, start_lba: usize, sectors: usize) {
+    let entry = &mut sector[0x1BE + slot * 16..0x1BE + (slot + 1) * 16];
+    entry[4] = system_id;
+    entry[8..12].copy_from_slice(&(start_lba as u32).to_le_bytes());
+    entry[12..16].copy_from_slice(&(sectors as u32).to_le_bytes());
+}
+
+/// Builds an MBR disk with the given primary partitions `(system_id, contents)`,
+/// followed by an extended partition holding the logical partitions.
+pub fn mbr_disk(primary: &[(u8, Vec<u8>)], logical: &[(u8, Vec<u8>)]) -> Vec<u8> {
+    let mut disk = vec![0; PARTITION_ALIGNMENT * SECTOR_SIZE];
+    disk[510..512].copy_from_slice(&[0x55, 0xAA]);
+
+    for (slot, (system_id, contents)) in primary.iter().enumerate() {
+        let start_lba = disk.len() / SECTOR_SIZE;
+        let sectors = align(contents.len() / SECTOR_SIZE);
+        mbr_entry(&mut disk, slot, *system_id, start_lba, sectors);
+
+        disk.extend_from_slice(contents);
+        disk.resize((start_lba + sectors) * SECTOR_SIZE, 0);
+    }
+
+    if logical.is_empty() {
+        return disk;
+    }
+
+    let extended_lba = disk.len() / SECTOR_SIZE;
+    for (i, (system_id, contents)) in logical.iter().enumerate() {
+        let ebr_lba = disk.len() / SECTOR_SIZE;
+        let sectors = align(contents.len() / SECTOR_SIZE);
+
+        let mut ebr = vec![0; PARTITION_ALIGNMENT * SECTOR_SIZE];
+        ebr[510..512].copy_from_slice(&[0x55, 0xAA]);
+        mbr_entry(&mut ebr, 0, *system_id, PARTITION_ALIGNMENT, sectors);
+        if i + 1 < logical.len() {
+            let next_ebr_lba = ebr_lba + PARTITION_ALIGNMENT + sectors;
+            mbr_entry(
+                &mut ebr,
+                1,
+                0x05,
+                next_ebr_lba - extended_lba,
+                PARTITION_ALIGNMENT,
+            );
+        }
+
+        disk.extend_from_slice(&ebr);
+        disk.extend_from_slice(contents);
+        disk.resize((ebr_lba + PARTITION_ALIGNMENT + sectors) * SECTOR_SIZE, 0);
+    }
+
+    let extended_sectors = disk.len() / SECTOR_SIZE - extended_lba;
+    mbr_entry(
+        &mut disk,
+        primary.len(),
+        0x05,
+        extended_lba,
+        extended_sectors,
+    );
+
+    disk
+}
+
+/// Microsoft basic data partition, `EBD0A0A2-B9E5-4433-87C0-68B6B72699C7`.
+pub const BASIC_DATA_PARTITION: [u8; 16] = [
+    0xA2, 0xA0, 0xD0, 0xEB, 0xE5, 0xB9, 0x33, 0x44, 0x87, 0xC0, 0x68, 0xB6, 0xB7, 0x26, 0x99, 0xC7,
+];
+
+/// Builds a GPT disk (with 512 byte sectors) with the given basic data partitions `(name, contents)`.
+pub fn gpt_disk(partitions: &[(&str, Vec<u8>)]) -> Vec<u8> {
+    let entry_count = 128;
+    let entry_size = 128;
+
+    let mut disk = vec![0; PARTITION_ALIGNMENT * SECTOR_SIZE];
+    disk[510..512].copy_from_slice(&[0x55, 0xAA]);
+    mbr_entry(&mut disk, 0, 0xEE, 1, u32::MAX as usize);
+
+    let header = &mut disk[SECTOR_SIZE..SECTOR_SIZE + 92];
+    header[0..8].copy_from_slice(b"EFI PART");
+    header[8..12].copy_from_slice(&0x0001_0000_u32.to_le_bytes());
+    header[12..16].copy_from_slice(&92_u32.to_le_bytes());
+    header[72..80].copy_from_slice(&2_u64.to_le_bytes());
+    header[80..84].copy_from_slice(&(entry_count as u32).to_le_bytes());
+    header[84..88].copy_from_slice(&(entry_size as u32).to_le_bytes());
+
+    for (i, (name, contents)) in partitions.iter().enumerate() {
+        let first_lba = disk.len() / SECTOR_SIZE;
+        let sectors = align(contents.len() / SECTOR_SIZE);
+
+        let entry_offset = 2 * SECTOR_SIZE + i * entry_size;
+        let entry = &mut disk[entry_offset..entry_offset + entry_size];
+        entry[0..16].copy_from_slice(&BASIC_DATA_PARTITION);
+        entry[16] = i as u8 + 1;
+        entry[32..40].copy_from_slice(&(first_lba as u64).to_le_bytes());
+        entry[40..48].copy_from_slice(&((first_lba + sectors - 1) as u64).to_le_bytes());
+        for (j, c) in name.encode_utf16().enumerate() {
+            entry[56 + j * 2..58 + j * 2].copy_from_slice(&c.to_le_bytes());
+        }
+
+        disk.extend_from_slice(contents);
+        disk.resize((first_lba + sectors) * SECTOR_SIZE, 0);
+    }
+
+    disk
+}
+
+fn vhd_footer(disk_type: u32, data_offset: u64, size: u64) -> Vec<u8> {
+    let mut footer = vec![0; 512];
+    footer[0..8].copy_from_slice(b"conectix");
+    footer[8..12].copy_from_slice(&2_u32.to_be_bytes());
+    footer[12..16].copy_from_slice(&0x0001_0000_u32.to_be_bytes());
+    footer[16..24].copy_from_slice(&data_offset.to_be_bytes());
+    footer[40..48].copy_from_slice(&size.to_be_bytes());
+    footer[48..56].copy_from_slice(&size.to_be_bytes());
+    footer[60..64].copy_from_slice(&disk_type.to_be_bytes());
+
+    let checksum = !footer.iter().map(|&b| u32::from(b)).sum::<u32>();
+    footer[64..68].copy_from_slice(&checksum.to_be_bytes());
+
+    footer
+}
+
+/// Writes `media` as a fixed VHD, which is the media followed by a footer.
+pub fn write_vhd_fixed(path: &Path, media: &[u8]) {
+    let mut vhd = media.to_vec();
+    vhd.extend_from_slice(&vhd_footer(2, u64::MAX, media.len() as u64));
+
+    fs::write(path, vhd).unwrap();
+}
+
+/// Writes `media` as a dynamic VHD.
+/// Zeroed blocks are left unallocated, and zeroed sectors are left out of the sector bitmap
+/// (with junk in their place, so reading them is noticeable).
+pub fn write_vhd_dynamic(path: &Path, media: &[u8], block_size: usize) {
+    let block_count = media.len().div_ceil(block_size);
+    let table_offset = 1536;
+    let bitmap_size = (block_size / SECTOR_SIZE).div_ceil(8).div_ceil(SECTOR_SIZE) * SECTOR_SIZE;
+
+    let mut vhd = vhd_footer(3, 512, media.len() as u64);
+
+    let mut header = vec![0; 1024];
+    header[0..8].copy_from_slice(b"cxsparse");
+    header[8..16].copy_from_slice(&u64::MAX.to_be_bytes());
+    header[16..24].copy_from_slice(&(table_offset as u64).to_be_bytes());
+    header[24..28].copy_from_slice(&0x0001_0000_u32.to_be_bytes());
+    header[28..32].copy_from_slice(&(block_count as u32).to_be_bytes());
+    header[32..36].copy_from_slice(&(block_size as u32).to_be_bytes());
+    vhd.extend_from_slice(&header);
+
+    let table_size = (block_count * 4).div_ceil(SECTOR_SIZE) * SECTOR_SIZE;
+    vhd.resize(table_offset + table_size, 0xFF);
+
+    for (i, block) in media.chunks(block_size).enumerate() {
+        if block.iter().all(|&b| b == 0) {
+            continue;
+        }
+
+        let block_sector = (vhd.len() / SECTOR_SIZE) as u32;
+        vhd[table_offset + i * 4..table_offset + i * 4 + 4]
+            .copy_from_slice(&block_sector.to_be_bytes());
+
+        let mut bitmap = vec![0; bitmap_size];
+        let mut data = vec![0xEE; block_size];
+        for (sector_number, sector) in block.chunks(SECTOR_SIZE).enumerate() {
+            if sector.iter().any(|&b| b != 0) {
+                bitmap[sector_number / 8] |= 0x80 >> (sector_number % 8);
+                data[sector_number * SECTOR_SIZE..sector_number * SECTOR_SIZE + sector.len()]
+                    .copy_from_slice(sector);
+            }
+        }
+
+        vhd.extend_from_slice(&bitmap);
+        vhd.extend_from_slice(&data);
+    }
+
+    vhd.extend_from_slice(&vhd_footer(3, 512, media.len() as u64));
+
+    fs::write(path, vhd).unwrap();
+}
+
+const MB: usize = 1024 * 1024;
+
+/// `2DC27766-F623-4200-9D64-115E9BFD4A08`
+const VHDX_BAT_REGION: [u8; 16] = [
+    0x66, 0x77, 0xC2, 0x2D, 0x23, 0xF6, 0x00, 0x42, 0x9D, 0x64, 0x11, 0x5E, 0x9B, 0xFD, 0x4A, 0x08,
+];
+/// `8B7CA206-4790-4B9A-B8FE-575F050F886E`
+const VHDX_METADATA_REGION: [u8; 16] = [
+    0x06, 0xA2, 0x7C, 0x8B, 0x90, 0x47, 0x9A, 0x4B, 0xB8, 0xFE, 0x57, 0x5F, 0x05, 0x0F, 0x88, 0x6E,
+];
+/// File parameters, virtual disk size and logical sector size.
+const VHDX_METADATA_ITEMS: [[u8; 16]; 3] = [
+    [
+        0x37, 0x67, 0xA1, 0xCA, 0x36, 0xFA, 0x43, 0x4D, 0xB3, 0xB6, 0x33, 0xF0, 0xAA, 0x44, 0xE7,
+        0x6B,
+    ],
+    [
+        0x24, 0x42, 0xA5, 0x2F, 0x1B, 0xCD, 0x76, 0x48, 0xB2, 0x11, 0x5D, 0xBE, 0xD8, 0x3B, 0xF4,
+        0xB8,
+    ],
+    [
+        0x1D, 0xBF, 0x41, 0x81, 0x6F, 0xA9, 0x09, 0x47, 0xBA, 0x47, 0xF2, 0x33, 0xA8, 0xFA, 0xAB,
+        0x5F,
+    ],
+];
+
+/// Writes `media` as a VHDX with 1MB blocks. Zeroed blocks are not present.
+pub fn write_vhdx(path: &Path, media: &[u8]) {
+    let mut vhdx = vec![0; 3 * MB];
+    vhdx[0..8].copy_from_slice(b"vhdxfile");
+
+    for (offset, sequence_number) in [(64 * 1024, 2_u64), (128 * 1024, 1)] {
+        vhdx[offset..offset + 4].copy_from_slice(b"head");
+        vhdx[offset + 8..offset + 16].copy_from_slice(&sequence_number.to_le_bytes());
+    }
+
+    let regions = 192 * 1024;
+    vhdx[regions..regions + 4].copy_from_slice(b"regi");
+    vhdx[regions + 8..regions + 12].copy_from_slice(&2_u32.to_le_bytes());
+    for (i, (guid, offset)) in [(VHDX_BAT_REGION, MB), (VHDX_METADATA_REGION, 2 * MB)]
+        .iter()
+        .enumerate()
+    {
+        let entry = regions + 16 + i * 32;
+        vhdx[entry..entry + 16].copy_from_slice(guid);
+        vhdx[entry + 16..entry + 24].copy_from_slice(&(*offset as u64).to_le_bytes());
+        vhdx[entry + 24..entry + 28].copy_from_slice(&(MB as u32).to_le_bytes());
+        vhdx[entry + 28..entry + 32].copy_from_slice(&1_u32.to_le_bytes());
+    }
+
+    let metadata = 2 * MB;
+    vhdx[metadata..metadata + 8].copy_from_slice(b"metadata");
+    vhdx[metadata + 10..metadata + 12].copy_from_slice(&3_u16.to_le_bytes());
+    for (i, guid) in VHDX_METADATA_ITEMS.iter().enumerate() {
+        let entry = metadata + 32 + i * 32;
+        let item_offset = 64 * 1024 + i * 8;
+        vhdx[entry..entry + 16].copy_from_slice(guid);
+        vhdx[entry + 16..entry + 20].copy_from_slice(&(item_offset as u32).to_le_bytes());
+        vhdx[entry + 20..entry + 24].copy_from_slice(&8_u32.to_le_bytes());
+    }
+    let items = metadata + 64 * 1024;
+    vhdx[items..items + 4].copy_from_slice(&(MB as u32).to_le_bytes());
+    vhdx[items + 8..items + 16].copy_from_slice(&(media.len() as u64).to_le_bytes());
+    vhdx[items + 16..items + 20].copy_from_slice(&(SECTOR_SIZE as u32).to_le_bytes());
+
+    for (i, block) in media.chunks(MB).enumerate() {
+        if block.iter().all(|&b| b == 0) {
+            continue;
+        }
+
+        // Fully present, at the next MB of the file.
+        let entry = ((vhdx.len() / MB) as u64) << 20 | 6;
+        vhdx[MB + i * 8..MB + i * 8 + 8].copy_from_slice(&entry.to_le_bytes());
+
+        vhdx.extend_from_slice(block);
+        vhdx.resize(vhdx.len().div_ceil(MB) * MB, 0);
+    }
+
+    fs::write(path, vhdx).unwrap();
+}
@@ -0,0 +1,115 @@
+use mft::analysis::{TimestompAnalyzer, TimestompRule};
+use mft::csv::FlatMftEntryWithName;
+use mft::MftEntry;
+
+use chrono::{TimeZone, Utc};
+use std::path::PathBuf;
+
+const ENTRY: &[u8; 1024] = include_bytes!("../samples/entry_single_file");
+/// The content of the $STANDARD_INFORMATION attribute.
+const STANDARD_INFO: usize = 56 + 24;
+
+fn rules(entry: &MftEntry, analyzer: &TimestompAnalyzer) -> Vec<TimestompRule> {
+    analyzer
+        .analyze(entry)
+        .into_iter()
+        .map(|finding| finding.rule)
+        .collect()
+}
+
+#[test]
+fn test_it_flags_standard_info_created_before_file_name() {
+    // Unpacked from an archive: $STANDARD_INFORMATION has the (whole second) times from the archive.
+    let entry = MftEntry::from_buffer(ENTRY.to_vec(), 0).unwrap();
+
+    assert_eq!(
+        rules(&entry, &TimestompAnalyzer::new()),
+        vec![
+            TimestompRule::SiCreatedBeforeFnCreated,
+            TimestompRule::SiZeroFraction
+        ]
+    );
+}
+
+#[test]
+fn test_it_flags_modified_before_created() {
+    let mut buffer = ENTRY.to_vec();
+    // Modified a day before created, with a sub-second part.
+    let created = u64::from_le_bytes(buffer[STANDARD_INFO..STANDARD_INFO + 8].try_into().unwrap());
+    let modified = created - 864_000_000_000 + 1_230;
+    buffer[STANDARD_INFO + 8..STANDARD_INFO + 16].copy_from_slice(&modified.to_le_bytes());
+
+    let entry = MftEntry::from_buffer(buffer, 0).unwrap();
+    let findings = TimestompAnalyzer::new().analyze(&entry);
+
+    assert!(findings
+        .iter()
+        .any(|f| f.rule == TimestompRule::SiModifiedBeforeCreated));
+    let zero_fraction = findings
+        .iter()
+        .find(|f| f.rule == TimestompRule::SiZeroFraction)
+        .unwrap();
+    assert!(zero_fraction
+        .message
+        .ends_with(": accessed, MFT modified, created"));
+}
+
+#[test]
+fn test_it_only_compares_times_in_range() {
+    // $STANDARD_INFORMATION created is unset, then out of range.
+    for created in [0, u64::MAX] {
+        let mut buffer = ENTRY.to_vec();
+        buffer[STANDARD_INFO..STANDARD_INFO + 8].copy_from_slice(&created.to_le_bytes());
+        let entry = MftEntry::from_buffer(buffer, 0).unwrap();
+
+        let found = rules(&entry, &TimestompAnalyzer::new());
+        assert!(!found.contains(&TimestompRule::SiCreatedBeforeFnCreated));
+        assert!(!found.contains(&TimestompRule::SiModifiedBeforeCreated));
+
+        // Which is what the lifetime of the volume is checked for.
+        let analyzer = TimestompAnalyzer::new().with_volume_lifetime(
+            Utc.with_ymd_and_hms(2001, 1, 1, 0, 0, 0).unwrap(),
+            Utc.with_ymd_and_hms(2020, 1, 1, 0, 0, 0).unwrap(),
+        );
+        assert_eq!(
+            rules(&entry, &analyzer).contains(&TimestompRule::OutsideVolumeLifetime),
+            created == u64::MAX
+        );
+    }
+}
+
+#[test]
+fn test_it_flags_times_outside_of_the_volume_lifetime() {
+    let entry = MftEntry::from_buffer(ENTRY.to_vec(), 0).unwrap();
+
+    let created = Utc.with_ymd_and_hms(2009, 1, 1, 0, 0, 0).unwrap();
+    let until = Utc.with_ymd_and_hms(2020, 1, 1, 0, 0, 0).unwrap();
+    let analyzer = TimestompAnalyzer::new().with_volume_lifetime(created, until);
+    assert!(rules(&entry, &analyzer).contains(&TimestompRule::OutsideVolumeLifetime));
+
+    let created = Utc.with_ymd_and_hms(2001, 1, 1, 0, 0, 0).unwrap();
+    let analyzer = TimestompAnalyzer::new().with_volume_lifetime(created, until);
+    assert!(!rules(&entry, &analyzer).contains(&TimestompRule::OutsideVolumeLifetime));
+
+    // Not the $MFT entry, so the lifetime of the volume is unknown.
+    assert_eq!(
+        TimestompAnalyzer::for_volume(&entry),
+        TimestompAnalyzer::new()
+    );
+}
+
+#[test]
+fn test_findings_are_csv_columns() {
+    let entry = MftEntry::from_buffer(ENTRY.to_vec(), 0).unwrap();
+
+    let row = FlatMftEntryWithName::from_entry_and_path(&entry, PathBuf::new());
+    assert!(row.si_created_before_fn_created);
+    assert!(row.si_zero_fraction);
+    assert!(!row.si_modified_before_created);
+    assert!(!row.outside_volume_lifetime);
+
+    let created = Utc.with_ymd_and_hms(2009, 1, 1, 0, 0, 0).unwrap();
+    let analyzer = TimestompAnalyzer::new().with_volume_lifetime(created, Utc::now());
+    let row = row.with_findings(&analyzer.analyze(&entry));
+    assert!(row.outside_volume_lifetime);
+}
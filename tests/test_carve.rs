@@ -0,0 +1,83 @@
+use mft::carve::{CarvedEntry, MftCarver};
+
+const ENTRY: &[u8; 1024] = include_bytes!("../samples/entry_single_file");
+
+fn with_record_number(record_number: u32) -> Vec<u8> {
+    let mut entry = ENTRY.to_vec();
+    entry[0x2C..0x30].copy_from_slice(&record_number.to_le_bytes());
+    entry
+}
+
+fn carve(data: &[u8], sector_size: usize) -> Vec<CarvedEntry> {
+    MftCarver::new(data)
+        .with_sector_size(sector_size)
+        .iter_entries()
+        .map(Result::unwrap)
+        .collect()
+}
+
+#[test]
+fn test_it_carves_records_at_sector_boundaries() {
+    let mut data = vec![0xAB; 4 * 1024 * 1024];
+    // Spans the boundary between two reads.
+    let offsets = [1536, 2560, 1024 * 1024 - 512, 3 * 1024 * 1024];
+    for (i, offset) in offsets.iter().enumerate() {
+        data[*offset..*offset + 1024].copy_from_slice(&with_record_number(i as u32 + 10));
+    }
+    // A signature, but not a record.
+    data[8192..8196].copy_from_slice(b"FILE");
+
+    let carved = carve(&data, 512);
+
+    let found: Vec<(u64, u64)> = carved
+        .iter()
+        .map(|c| (c.offset, c.entry.header.record_number))
+        .collect();
+    assert_eq!(
+        found,
+        vec![
+            (1536, 10),
+            (2560, 11),
+            (1024 * 1024 - 512, 12),
+            (3 * 1024 * 1024, 13)
+        ]
+    );
+    assert!(carved.iter().all(|c| c.entry.valid_fixup == Some(true)));
+}
+
+#[test]
+fn test_it_respects_the_sector_size() {
+    let mut data = vec![0; 8192];
+    data[4096..5120].copy_from_slice(ENTRY);
+    data[6656..7680].copy_from_slice(ENTRY);
+
+    assert_eq!(carve(&data, 512).len(), 2);
+
+    let carved = carve(&data, 4096);
+    assert_eq!(carved.len(), 1);
+    assert_eq!(carved[0].offset, 4096);
+}
+
+#[test]
+fn test_it_rejects_torn_records_unless_marked_baad() {
+    let mut torn = ENTRY.to_vec();
+    torn[1022] = 0xFF;
+
+    let mut data = vec![0; 4096];
+    data[1024..2048].copy_from_slice(&torn);
+    assert!(carve(&data, 512).is_empty());
+
+    // chkdsk marks such records as BAAD, and they are worth keeping.
+    data[1024..1028].copy_from_slice(b"BAAD");
+    let carved = carve(&data, 512);
+    assert_eq!(carved.len(), 1);
+    assert_eq!(&carved[0].entry.header.signature, b"BAAD");
+
+    let carved: Vec<CarvedEntry> = MftCarver::new(&data[..])
+        .with_recovery(true)
+        .iter_entries()
+        .map(Result::unwrap)
+        .collect();
+    assert!(carved[0].entry.recovered);
+    assert_eq!(carved[0].entry.valid_fixup, Some(false));
+}
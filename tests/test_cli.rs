@@ -3,6 +3,8 @@ mod fixtures;
 use fixtures::*;
 
 use assert_cmd::prelude::*;
+use mft::attribute::header::ResidentialHeader;
+use mft::attribute::MftAttributeType;
 use std::fs;
 use std::fs::File;
 use std::io::{Read, Write};
@@ -75,3 +77,333 @@ fn test_it_exports_resident_streams() {
 
     assert_eq!(fs::read_dir(d.path()).unwrap().count(), 2142)
 }
+
+#[test]
+fn test_it_streams_from_stdin() {
+    let sample = fs::read(mft_sample_name("entry_single_file")).unwrap();
+
+    let mut cmd = assert_cmd::Command::cargo_bin("mft_dump").expect("failed to find binary");
+    cmd.args(["-o", "csv", "-"]).write_stdin(sample);
+
+    let output = cmd.assert().success().get_output().stdout.clone();
+    let output = String::from_utf8(output).unwrap();
+
+    // Header + a single entry.
+    assert_eq!(output.lines().count(), 2);
+}
+
+#[test]
+fn test_it_writes_an_error_report() {
+    let d = tempdir().unwrap();
+    let sample = d.as_ref().join("MFT");
+    let report = d.as_ref().join("errors.jsonl");
+
+    // The second attribute of the first entry has an unknown type, and the second entry has a bad signature.
+    let mut mft = include_bytes!("../samples/entry_single_file").to_vec();
+    let entry = mft::MftEntry::from_buffer(mft.clone(), 0).unwrap();
+    let first_attribute = entry.iter_attributes().next().unwrap().unwrap();
+    let second_attribute = (first_attribute.header.start_offset
+        + u64::from(first_attribute.header.record_length)) as usize;
+    mft[second_attribute..second_attribute + 4].copy_from_slice(&[0x42, 0, 0, 0]);
+
+    let mut bad_entry = vec![0; mft.len()];
+    bad_entry[..4].copy_from_slice(b"XXXX");
+    mft.extend(bad_entry);
+    fs::write(&sample, mft).unwrap();
+
+    let mut cmd = Command::cargo_bin("mft_dump").expect("failed to find binary");
+    cmd.args([
+        "-o",
+        "jsonl",
+        "--errors",
+        &report.to_string_lossy(),
+        &sample.to_string_lossy(),
+    ]);
+    cmd.assert().success();
+
+    let report = fs::read_to_string(&report).unwrap();
+    let lines: Vec<serde_json::Value> = report
+        .lines()
+        .map(|line| serde_json::from_str(line).unwrap())
+        .collect();
+
+    assert_eq!(lines.len(), 2);
+    assert_eq!(lines[0]["record_number"], 0);
+    assert_eq!(lines[0]["attribute_offset"], second_attribute as u64);
+    assert!(lines[0]["message"]
+        .as_str()
+        .unwrap()
+        .contains("AttributeType"));
+    assert_eq!(lines[1]["record_number"], 1);
+    assert!(lines[1]["attribute_offset"].is_null());
+    assert!(lines[1]["message"]
+        .as_str()
+        .unwrap()
+        .contains("Bad signature"));
+}
+
+#[test]
+fn test_it_adds_residual_attributes_with_slack() {
+    let d = tempdir().unwrap();
+    let sample = d.as_ref().join("MFT");
+
+    // A copy of the $STANDARD_INFORMATION attribute, past the used size of the entry.
+    let mut mft = include_bytes!("../samples/entry_single_file").to_vec();
+    mft.copy_within(56..152, 520);
+    fs::write(&sample, mft).unwrap();
+
+    for (args, expected) in [(vec![], None), (vec!["--slack"], Some(1))] {
+        let mut cmd = Command::cargo_bin("mft_dump").expect("failed to find binary");
+        cmd.args(["-o", "jsonl"]).args(args).arg(sample.as_os_str());
+        let output = cmd.output().unwrap();
+        assert!(output.status.success());
+
+        let entry: serde_json::Value = serde_json::from_slice(&output.stdout).unwrap();
+        let residual_attributes = entry["residual_attributes"].as_array().map(Vec::len);
+        assert_eq!(residual_attributes, expected);
+        assert_eq!(entry["attributes"].as_array().unwrap().len(), 4);
+    }
+}
+
+#[test]
+fn test_it_carves_records_from_an_image() {
+    let d = tempdir().unwrap();
+    let sample = d.as_ref().join("image");
+
+    let mut image = vec![0; 8192];
+    image[3072..4096].copy_from_slice(include_bytes!("../samples/entry_single_file"));
+    fs::write(&sample, image).unwrap();
+
+    let mut cmd = Command::cargo_bin("mft_dump").expect("failed to find binary");
+    cmd.args(["carve", "-o", "jsonl"]).arg(sample.as_os_str());
+    let output = cmd.output().unwrap();
+    assert!(output.status.success());
+
+    let entry: serde_json::Value = serde_json::from_slice(&output.stdout).unwrap();
+    assert_eq!(entry["carved_offset"], 3072);
+    assert_eq!(entry["header"]["record_number"], 26370);
+
+    let mut cmd = Command::cargo_bin("mft_dump").expect("failed to find binary");
+    cmd.args(["carve", "-o", "csv"]).arg(sample.as_os_str());
+    let output = cmd.output().unwrap();
+    assert!(output.status.success());
+
+    let csv = String::from_utf8(output.stdout).unwrap();
+    let mut lines = csv.lines();
+    assert!(lines.next().unwrap().ends_with("CarvedOffset"));
+    assert!(lines.next().unwrap().ends_with(",3072"));
+}
+
+#[test]
+fn test_it_writes_a_bodyfile() {
+    let sample = include_bytes!("../samples/entry_single_file");
+
+    // Streamed, so the lines are written once the paths are known.
+    let mut cmd = assert_cmd::Command::cargo_bin("mft_dump").expect("failed to find binary");
+    cmd.args(["-o", "bodyfile", "-"])
+        .write_stdin(sample.to_vec());
+
+    let output = cmd.assert().success().get_output().stdout.clone();
+    let bodyfile = String::from_utf8(output).unwrap();
+    let lines: Vec<Vec<&str>> = bodyfile.lines().map(|l| l.split('|').collect()).collect();
+
+    assert_eq!(lines.len(), 3);
+    assert!(lines.iter().all(|fields| fields.len() == 11));
+    assert_eq!(lines[0][1], "[Unknown]/test_cfuncs.py");
+    assert_eq!(lines[2][1], "[Unknown]/test_cfuncs.py ($FILE_NAME)");
+    assert_eq!(lines[0][8], "1204258356");
+}
+
+#[test]
+fn test_it_writes_a_sorted_timeline() {
+    let sample = include_bytes!("../samples/entry_single_file");
+
+    let mut cmd = assert_cmd::Command::cargo_bin("mft_dump").expect("failed to find binary");
+    cmd.args([
+        "timeline",
+        "-o",
+        "jsonl",
+        "--from",
+        "2009-01-01",
+        "--max-events-in-memory",
+        "2",
+        "-",
+    ])
+    .write_stdin(sample.to_vec());
+
+    let output = cmd.assert().success().get_output().stdout.clone();
+    let events: Vec<serde_json::Value> = String::from_utf8(output)
+        .unwrap()
+        .lines()
+        .map(|line| serde_json::from_str(line).unwrap())
+        .collect();
+
+    // The modified and created times of $STANDARD_INFORMATION are from 2008.
+    assert_eq!(events.len(), 10);
+    let timestamps: Vec<&str> = events
+        .iter()
+        .map(|e| e["timestamp"].as_str().unwrap())
+        .collect();
+    assert!(timestamps.windows(2).all(|w| w[0] <= w[1]));
+    assert!(timestamps.iter().all(|t| *t >= "2009-01-01"));
+    assert!(events
+        .iter()
+        .all(|e| e["full_path"] == "[Unknown]/test_cfuncs.py"));
+}
+
+#[test]
+fn test_it_writes_timestamps_in_the_chosen_format_and_time_zone() {
+    let sample = include_bytes!("../samples/entry_single_file");
+
+    let created = |args: &[&str]| {
+        let mut cmd = assert_cmd::Command::cargo_bin("mft_dump").expect("failed to find binary");
+        cmd.args(args)
+            .args(["-o", "jsonl", "-"])
+            .write_stdin(sample.to_vec());
+
+        let output = cmd.assert().success().get_output().stdout.clone();
+        let entry: serde_json::Value = serde_json::from_slice(&output).unwrap();
+        entry["attributes"][0]["data"]["content"]["created"].clone()
+    };
+
+    assert_eq!(created(&[]), "2008-02-29T04:12:36.0000000Z");
+    assert_eq!(
+        created(&["--timezone", "-05:30"]),
+        "2008-02-28T22:42:36.0000000-05:30"
+    );
+    assert_eq!(created(&["--timestamp-format", "epoch"]), 1204258356);
+    assert_eq!(
+        created(&["--timestamp-format", "filetime"]),
+        128487319560000000_u64
+    );
+}
+
+#[test]
+fn test_it_writes_the_selected_fields() {
+    let sample = include_bytes!("../samples/entry_single_file");
+
+    let output = |format: &str| {
+        let mut cmd = assert_cmd::Command::cargo_bin("mft_dump").expect("failed to find binary");
+        cmd.args([
+            "--fields",
+            "entry_id,full_path,si_created",
+            "-o",
+            format,
+            "-",
+        ])
+        .write_stdin(sample.to_vec());
+
+        String::from_utf8(cmd.assert().success().get_output().stdout.clone()).unwrap()
+    };
+
+    assert_eq!(
+        output("csv"),
+        "entry_id,full_path,si_created\n0,[Unknown]/test_cfuncs.py,2008-02-29T04:12:36.0000000Z\n"
+    );
+    assert_eq!(
+        output("jsonl"),
+        "{\"entry_id\":0,\"full_path\":\"[Unknown]/test_cfuncs.py\",\"si_created\":\"2008-02-29T04:12:36.0000000Z\"}\n"
+    );
+
+    let mut cmd = assert_cmd::Command::cargo_bin("mft_dump").expect("failed to find binary");
+    cmd.args(["--fields", "entry_id,nope", "-"])
+        .write_stdin(sample.to_vec())
+        .assert()
+        .failure();
+}
+
+#[test]
+fn test_it_adds_derived_fields_to_json_entries() {
+    let sample = include_bytes!("../samples/entry_single_file");
+
+    let entry = |args: &[&str]| {
+        let mut cmd = assert_cmd::Command::cargo_bin("mft_dump").expect("failed to find binary");
+        cmd.args(args)
+            .args(["-o", "jsonl", "-"])
+            .write_stdin(sample.to_vec());
+
+        let output = cmd.assert().success().get_output().stdout.clone();
+        serde_json::from_slice::<serde_json::Value>(&output).unwrap()
+    };
+
+    let derived = entry(&[]);
+    assert_eq!(derived["full_path"], "[Unknown]/test_cfuncs.py");
+    assert_eq!(
+        derived["hard_link_paths"],
+        serde_json::json!(["[Unknown]/test_cfuncs.py"])
+    );
+    assert_eq!(derived["is_directory"], false);
+    assert_eq!(derived["file_size"], 8072);
+    assert!(derived["header"].is_object());
+
+    let raw = entry(&["--raw"]);
+    assert!(raw.get("full_path").is_none());
+    assert!(raw["header"].is_object());
+}
+
+#[test]
+fn test_it_streams_many_entries() {
+    const ENTRIES: u64 = 3000;
+    let d = tempdir().unwrap();
+    let sample = d.as_ref().join("MFT");
+
+    // Every entry is a file in the directory which is the last entry, so no path is known until the end.
+    let mut file = include_bytes!("../samples/entry_single_file").to_vec();
+    let entry = mft::MftEntry::from_buffer(file.clone(), 0).unwrap();
+    for attribute in entry.iter_attributes().filter_map(Result::ok) {
+        if let (MftAttributeType::FileName, ResidentialHeader::Resident(header)) = (
+            &attribute.header.type_code,
+            &attribute.header.residential_header,
+        ) {
+            let parent = attribute.header.start_offset as usize + header.data_offset as usize;
+            file[parent..parent + 8].copy_from_slice(&(ENTRIES - 1).to_le_bytes());
+        }
+    }
+
+    let mut mft = vec![];
+    for _ in 0..ENTRIES - 1 {
+        mft.extend_from_slice(&file);
+    }
+    mft.extend_from_slice(include_bytes!(
+        "../samples/entry_multiple_index_root_entries"
+    ));
+    fs::write(&sample, &mft).unwrap();
+
+    let dump = |format: &str, input: &str, stdin: Vec<u8>| {
+        let mut cmd = assert_cmd::Command::cargo_bin("mft_dump").expect("failed to find binary");
+        cmd.args([
+            "-o",
+            format,
+            "--slack",
+            "--timestamp-format",
+            "epoch-fraction",
+        ])
+        .arg(input)
+        .write_stdin(stdin);
+        cmd.assert().success().get_output().stdout.clone()
+    };
+
+    let streamed = dump("jsonl", "-", mft.clone());
+    let lines: Vec<serde_json::Value> = streamed
+        .split(|&b| b == b'\n')
+        .filter(|line| !line.is_empty())
+        .map(|line| serde_json::from_slice(line).unwrap())
+        .collect();
+    assert_eq!(lines.len(), ENTRIES as usize);
+    assert_eq!(lines[0]["full_path"], "[Unknown]/test/test_cfuncs.py");
+    assert_eq!(
+        lines[ENTRIES as usize - 2]["header"]["record_number"],
+        ENTRIES - 2
+    );
+
+    // The same as when the paths are resolved while reading.
+    assert!(streamed == dump("jsonl", sample.to_str().unwrap(), vec![]));
+
+    for format in ["csv", "bodyfile"] {
+        assert!(
+            dump(format, "-", mft.clone()) == dump(format, sample.to_str().unwrap(), vec![]),
+            "{} output differs when streamed",
+            format
+        );
+    }
+}
@@ -0,0 +1,129 @@
+#![cfg(feature = "compression")]
+mod fixtures;
+
+use fixtures::*;
+use mft::err::Error;
+use mft::MftParser;
+use std::fs::{self, File};
+use std::io::Write;
+use std::path::Path;
+use tempfile::tempdir;
+
+fn sample() -> Vec<u8> {
+    fs::read(mft_sample_name("entry_single_file")).unwrap()
+}
+
+fn gzip(data: &[u8]) -> Vec<u8> {
+    let mut encoder = flate2::write::GzEncoder::new(vec![], flate2::Compression::default());
+    encoder.write_all(data).unwrap();
+    encoder.finish().unwrap()
+}
+
+/// Wraps the data in a zstd frame made of raw (uncompressed) blocks.
+fn zstd_raw_frame(data: &[u8]) -> Vec<u8> {
+    // Magic, frame header descriptor (no checksum, no content size), window descriptor (1MB).
+    let mut frame = vec![0x28, 0xB5, 0x2F, 0xFD, 0x00, 0x50];
+    let chunks: Vec<&[u8]> = data.chunks(1024).collect();
+
+    for (i, chunk) in chunks.iter().enumerate() {
+        let last_block = u32::from(i == chunks.len() - 1);
+        let block_header = last_block | ((chunk.len() as u32) << 3);
+        frame.extend_from_slice(&block_header.to_le_bytes()[..3]);
+        frame.extend_from_slice(chunk);
+    }
+
+    frame
+}
+
+fn zip(path: &Path, members: &[(&str, &[u8])]) {
+    let mut writer = zip::ZipWriter::new(File::create(path).unwrap());
+    for (name, data) in members {
+        writer
+            .start_file(*name, zip::write::FileOptions::default())
+            .unwrap();
+        writer.write_all(data).unwrap();
+    }
+    writer.finish().unwrap();
+}
+
+fn assert_parses_as_sample(path: impl AsRef<Path>) {
+    let mut parser = MftParser::from_path(path).unwrap();
+    assert_eq!(parser.get_entry_count(), 1);
+
+    let entry = parser.get_entry(0).unwrap();
+    let expected = mft::MftEntry::from_buffer(sample(), 0).unwrap();
+    assert_eq!(entry.data, expected.data);
+}
+
+#[test]
+fn test_it_reads_gzip() {
+    let d = tempdir().unwrap();
+    let path = d.path().join("$MFT.gz");
+    fs::write(&path, gzip(&sample())).unwrap();
+
+    assert_parses_as_sample(path);
+}
+
+#[test]
+fn test_it_reads_zstd() {
+    let d = tempdir().unwrap();
+    let path = d.path().join("$MFT.zst");
+    fs::write(&path, zstd_raw_frame(&sample())).unwrap();
+
+    assert_parses_as_sample(path);
+}
+
+#[test]
+fn test_it_reads_zip_members() {
+    let d = tempdir().unwrap();
+    let path = d.path().join("triage.zip");
+    zip(
+        &path,
+        &[
+            ("C/$MFT", &sample()),
+            ("C/$LogFile", b"not an mft"),
+            ("D/$MFT", &gzip(&sample())),
+        ],
+    );
+
+    assert_parses_as_sample(format!("{}::C/$MFT", path.display()));
+    assert_parses_as_sample(format!("{}::D\\$MFT", path.display()));
+
+    // Two candidates and no member selected.
+    assert!(MftParser::from_path(&path).is_err());
+    assert!(MftParser::from_path(format!("{}::E/$MFT", path.display())).is_err());
+}
+
+#[test]
+fn test_it_picks_the_only_zip_member() {
+    let d = tempdir().unwrap();
+    let path = d.path().join("mft.zip");
+    zip(&path, &[("collection/mft.bin", &sample())]);
+
+    assert_parses_as_sample(path);
+}
+
+#[test]
+fn test_it_streams_compressed_stdin() {
+    let mut cmd = assert_cmd::Command::cargo_bin("mft_dump").expect("failed to find binary");
+    cmd.args(["-o", "jsonl", "-"]).write_stdin(gzip(&sample()));
+
+    let output = cmd.assert().success().get_output().stdout.clone();
+    assert_eq!(String::from_utf8(output).unwrap().lines().count(), 1);
+}
+
+#[test]
+fn test_it_limits_the_unpacked_size() {
+    let d = tempdir().unwrap();
+    let gz = d.path().join("$MFT.gz");
+    fs::write(&gz, gzip(&sample())).unwrap();
+    let zip_path = d.path().join("mft.zip");
+    zip(&zip_path, &[("$MFT", &sample())]);
+
+    for path in [&gz, &zip_path] {
+        assert!(mft::container::open_with_limit(path, 1024).is_ok());
+
+        let error = mft::container::open_with_limit(path, 1023).unwrap_err();
+        assert!(matches!(error, Error::FailedToReadArchive { .. }));
+    }
+}
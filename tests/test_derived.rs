@@ -0,0 +1,89 @@
+use mft::attribute::header::ResidentialHeader;
+use mft::attribute::x30::FileNamespace;
+use mft::attribute::MftAttributeType;
+use mft::derived::DerivedFields;
+use mft::stream::PathTable;
+use mft::{MftEntry, MftParser};
+
+use std::path::PathBuf;
+
+const ENTRY: &[u8; 1024] = include_bytes!("../samples/entry_single_file");
+
+/// The entry, with its DOS name turned into a POSIX name, as if it was a second hard link.
+fn entry_with_two_links() -> Vec<u8> {
+    let mut buffer = ENTRY.to_vec();
+    let entry = MftEntry::from_buffer(buffer.clone(), 0).unwrap();
+
+    let dos_name = entry
+        .iter_attributes_matching(Some(vec![MftAttributeType::FileName]))
+        .filter_map(Result::ok)
+        .find(|a| a.data.clone().into_file_name().unwrap().namespace == FileNamespace::DOS)
+        .unwrap();
+    let data_offset = match dos_name.header.residential_header {
+        ResidentialHeader::Resident(resident) => resident.data_offset,
+        ResidentialHeader::NonResident(_) => unreachable!(),
+    };
+    // The namespace is the 66th byte of $FILE_NAME.
+    buffer[dos_name.header.start_offset as usize + data_offset as usize + 65] = 0;
+
+    buffer
+}
+
+#[test]
+fn test_dos_names_are_not_links() {
+    let entry = MftEntry::from_buffer(ENTRY.to_vec(), 0).unwrap();
+    let names: Vec<String> = entry.link_names().into_iter().map(|n| n.name).collect();
+    assert_eq!(names, vec!["test_cfuncs.py"]);
+
+    let entry = MftEntry::from_buffer(entry_with_two_links(), 0).unwrap();
+    let names: Vec<String> = entry.link_names().into_iter().map(|n| n.name).collect();
+    assert_eq!(names, vec!["TEST_C~3.PY", "test_cfuncs.py"]);
+}
+
+#[test]
+fn test_derived_fields_have_every_hard_link_path() {
+    let mut parser = MftParser::from_buffer(entry_with_two_links()).unwrap();
+    let entry = parser.get_entry(0).unwrap();
+
+    let derived = DerivedFields::from_entry(&entry, &mut parser).unwrap();
+    assert_eq!(
+        derived,
+        DerivedFields {
+            full_path: Some(PathBuf::from("[Unknown]/test_cfuncs.py")),
+            hard_link_paths: vec![
+                PathBuf::from("[Unknown]/TEST_C~3.PY"),
+                PathBuf::from("[Unknown]/test_cfuncs.py")
+            ],
+            is_deleted: false,
+            is_directory: false,
+            file_size: 8072,
+            alternate_data_streams: vec![],
+        }
+    );
+
+    // The same paths when streaming.
+    let mut paths = PathTable::new();
+    paths.add_entry(&entry);
+    assert_eq!(
+        DerivedFields::from_entry_and_path_table(&entry, &paths),
+        derived
+    );
+}
+
+#[test]
+fn test_derived_fields_list_alternate_data_streams() {
+    let entry = MftEntry::from_buffer(
+        include_bytes!("../samples/entry_data_run_at_offset").to_vec(),
+        0,
+    )
+    .unwrap();
+
+    let derived = DerivedFields::from_entry_and_path_table(&entry, &PathTable::new());
+    assert_eq!(derived.alternate_data_streams, vec!["$J"]);
+    assert_eq!(derived.file_size, 2152925272);
+    assert_eq!(derived.full_path, None);
+    assert!(derived.hard_link_paths.is_empty());
+
+    let json = serde_json::to_value(&derived).unwrap();
+    assert_eq!(json["full_path"], serde_json::Value::Null);
+}
@@ -0,0 +1,339 @@
+mod fixtures;
+
+use fixtures::*;
+
+use mft::attribute::x30::FileNameAttr;
+use mft::attribute::{AttributeRecord, FileAttributeFlags, MftAttributeContent};
+use mft::carve::CarvedEntry;
+use mft::csv::FlatMftEntryWithName;
+use mft::derived::DerivedFields;
+use mft::entry::{EntryFlags, FixupOptions, FixupPolicy};
+use mft::stream::PathTable;
+use mft::timestamp::{TimestampFormat, TimestampOptions};
+use mft::timezone::OutputTimeZone;
+use mft::{FileTime, MftEntry};
+
+use serde_json::{json, Value};
+
+use std::fs;
+use std::path::PathBuf;
+
+/// Every sample record, of both the samples and the test data.
+fn sample_entries() -> Vec<MftEntry> {
+    let testdata = samples_dir().parent().unwrap().join("testdata");
+    [samples_dir(), testdata]
+        .iter()
+        .flat_map(|dir| fs::read_dir(dir).unwrap())
+        .map(|entry| entry.unwrap().path())
+        .filter(|path: &PathBuf| fs::metadata(path).unwrap().len() == 1024)
+        .map(|path| MftEntry::from_buffer(fs::read(path).unwrap(), 0).unwrap())
+        .collect()
+}
+
+/// Serializes the entry, deserializes it, and checks that it serializes the same way again,
+/// and that the CSV row and derived fields of both are the same.
+fn assert_round_trips(entry: &MftEntry) -> MftEntry {
+    let serialized = serde_json::to_value(entry).unwrap();
+    let deserialized: MftEntry = serde_json::from_value(serialized.clone()).unwrap();
+
+    assert_eq!(serde_json::to_value(&deserialized).unwrap(), serialized);
+
+    let row = |entry: &MftEntry| {
+        let row = FlatMftEntryWithName::from_entry_and_path(entry, PathBuf::from("path"));
+        serde_json::to_value(row).unwrap()
+    };
+    assert_eq!(row(&deserialized), row(entry));
+
+    let derived = |entry: &MftEntry| {
+        let mut paths = PathTable::new();
+        paths.add_entry(entry);
+        DerivedFields::from_entry_and_path_table(entry, &paths)
+    };
+    assert_eq!(derived(&deserialized), derived(entry));
+
+    deserialized
+}
+
+fn file_names(entry: &MftEntry) -> Vec<FileNameAttr> {
+    entry
+        .iter_attributes()
+        .filter_map(Result::ok)
+        .filter_map(|attribute| attribute.data.into_file_name())
+        .collect()
+}
+
+#[test]
+fn test_entries_round_trip() {
+    let entries = sample_entries();
+    assert!(entries.len() >= 6);
+
+    for entry in &entries {
+        let deserialized = assert_round_trips(entry);
+
+        assert!(deserialized.data.is_empty());
+        assert_eq!(
+            deserialized.header.record_number,
+            entry.header.record_number
+        );
+        assert_eq!(deserialized.header.flags, entry.header.flags);
+        assert_eq!(deserialized.is_dir(), entry.is_dir());
+        assert_eq!(
+            deserialized.attribute_records().len(),
+            entry.attribute_records().len()
+        );
+        assert_eq!(file_names(&deserialized), file_names(entry));
+
+        // The borrowed view (and the entry copied back from it) has the same attributes.
+        let entry_ref = deserialized.as_entry_ref();
+        assert_eq!(
+            entry_ref.iter_attributes().count(),
+            entry.iter_attributes().count()
+        );
+        assert_eq!(file_names(&entry_ref.to_entry()), file_names(entry));
+        assert_eq!(
+            deserialized.find_best_name_attribute(),
+            entry.find_best_name_attribute()
+        );
+    }
+
+    // Every kind of content was round-tripped.
+    let mut types: Vec<&str> = entries
+        .iter()
+        .flat_map(|entry| entry.attribute_records())
+        .filter_map(|record| match record {
+            AttributeRecord::Attribute(attribute) => Some(match attribute.data {
+                MftAttributeContent::AttrX10(_) => "StandardInformation",
+                MftAttributeContent::AttrX30(_) => "FileName",
+                MftAttributeContent::AttrX80(_) => "Data",
+                MftAttributeContent::AttrX90(_) => "IndexRoot",
+                MftAttributeContent::DataRun(_) => "NonResident",
+                MftAttributeContent::Raw(_) => "Raw",
+                MftAttributeContent::AttrX20(_) => "AttributeList",
+                MftAttributeContent::AttrX40(_) => "ObjectId",
+                MftAttributeContent::None => "Empty",
+            }),
+            AttributeRecord::Error(_) => None,
+        })
+        .collect();
+    types.sort_unstable();
+    types.dedup();
+    assert_eq!(
+        types,
+        [
+            "Data",
+            "FileName",
+            "IndexRoot",
+            "NonResident",
+            "ObjectId",
+            "Raw",
+            "StandardInformation"
+        ]
+    );
+}
+
+#[test]
+fn test_damaged_entries_round_trip() {
+    // A torn record, whose original data is kept.
+    let torn = MftEntry::from_buffer_with_fixup(
+        include_bytes!("../samples/entry_102130_fixup_issue").to_vec(),
+        102130,
+        FixupOptions {
+            policy: FixupPolicy::Preserve,
+            ..FixupOptions::default()
+        },
+    )
+    .unwrap();
+    assert!(torn.original_data.is_some());
+
+    let deserialized = assert_round_trips(&torn);
+    assert_eq!(deserialized.original_data, torn.original_data);
+    assert_eq!(deserialized.anomalies, torn.anomalies);
+
+    // An attribute with an unknown type, kept as an error with its raw bytes.
+    let mut buffer = include_bytes!("../samples/entry_single_file").to_vec();
+    let entry = MftEntry::from_buffer(buffer.clone(), 0).unwrap();
+    let first_attribute = entry.iter_attributes().next().unwrap().unwrap();
+    let second_attribute = (first_attribute.header.start_offset
+        + u64::from(first_attribute.header.record_length)) as usize;
+    buffer[second_attribute..second_attribute + 4].copy_from_slice(&[0x42, 0, 0, 0]);
+    let entry = MftEntry::from_buffer(buffer, 0).unwrap();
+
+    let deserialized = assert_round_trips(&entry);
+    let error = deserialized.attribute_records()[1]
+        .error()
+        .cloned()
+        .unwrap();
+    assert_eq!(error.raw, entry.attribute_records()[1].error().unwrap().raw);
+    // The anomaly of the attribute is derived from the error again, rather than kept twice.
+    assert!(deserialized.anomalies.is_empty());
+    assert_eq!(deserialized.attributes_with_anomalies().1.len(), 1);
+}
+
+#[test]
+fn test_timestamps_round_trip_in_every_lossless_format() {
+    let entry =
+        MftEntry::from_buffer(include_bytes!("../samples/entry_single_file").to_vec(), 0).unwrap();
+    let times = |entry: &MftEntry| -> Vec<FileTime> {
+        file_names(entry)
+            .iter()
+            .flat_map(|name| {
+                [
+                    name.created,
+                    name.modified,
+                    name.mft_modified,
+                    name.accessed,
+                ]
+            })
+            .collect()
+    };
+
+    let options = [
+        TimestampOptions::default(),
+        TimestampOptions::default().with_time_zone(OutputTimeZone::parse("-05:30").unwrap()),
+        TimestampOptions::default().with_format(TimestampFormat::EpochFraction),
+        TimestampOptions::default().with_format(TimestampFormat::Filetime),
+    ];
+
+    for options in &options {
+        let deserialized = options.scope(|| assert_round_trips(&entry));
+        assert_eq!(times(&deserialized), times(&entry));
+    }
+
+    // Epoch seconds drop the fraction of the second, but still read back as the same seconds.
+    let options = TimestampOptions::default().with_format(TimestampFormat::EpochSeconds);
+    let deserialized = options.scope(|| assert_round_trips(&entry));
+    for (deserialized, time) in times(&deserialized).iter().zip(times(&entry)) {
+        assert_eq!(deserialized.subsecond_intervals(), 0);
+        assert_eq!(
+            deserialized.raw(),
+            time.raw() - u64::from(time.subsecond_intervals())
+        );
+    }
+}
+
+#[test]
+fn test_attribute_lists_round_trip() {
+    // None of the samples have an attribute list.
+    let attribute_list = json!({
+        "type": "AttributeList",
+        "content": {
+            "entries": [{
+                "attribute_type": 128,
+                "record_length": 40,
+                "name_length": 4,
+                "name_offset": 26,
+                "lowest_vcn": 0,
+                "segment_reference": {"entry": 38, "sequence": 2},
+                "reserved": 3,
+                "name": "$SDS"
+            }]
+        }
+    });
+
+    let content: MftAttributeContent = serde_json::from_value(attribute_list.clone()).unwrap();
+    let entries = content.clone().into_attribute_list().unwrap().entries;
+    assert_eq!(entries[0].segment_reference.entry, 38);
+    assert_eq!(serde_json::to_value(&content).unwrap(), attribute_list);
+}
+
+#[test]
+fn test_file_times_are_deserialized() {
+    let time = |value: Value| serde_json::from_value::<FileTime>(value).unwrap();
+
+    assert_eq!(time(json!(null)), FileTime::from_raw(0));
+    assert_eq!(
+        time(json!("2008-02-29T04:12:36.1234567Z")),
+        time(json!("2008-02-28T22:42:36.1234567-05:30"))
+    );
+    assert_eq!(
+        time(json!("2008-02-29T04:12:36.1234567Z")).raw(),
+        128487319561234567
+    );
+    assert_eq!(
+        time(json!({ "out_of_range": u64::MAX })),
+        FileTime::from_raw(u64::MAX)
+    );
+
+    let epoch = TimestampOptions::default().with_format(TimestampFormat::EpochSeconds);
+    epoch.scope(|| {
+        assert_eq!(time(json!(0)).raw(), 116444736000000000);
+        assert_eq!(time(json!(-1)).raw(), 116444736000000000 - 10_000_000);
+        assert_eq!(
+            time(json!({ "out_of_range": u64::MAX })),
+            FileTime::from_raw(u64::MAX)
+        );
+        assert!(serde_json::from_value::<FileTime>(json!(u64::MAX)).is_err());
+    });
+    assert_eq!(
+        time(json!("-0.5000000")).raw(),
+        116444736000000000 - 5_000_000
+    );
+    assert_eq!(time(json!("1.5")).raw(), 116444736000000000 + 15_000_000);
+
+    for invalid in [
+        json!("yesterday"),
+        json!("1.12345678"),
+        json!(true),
+        json!({ "raw": 1 }),
+        json!({ "out_of_range": 1, "raw": 1 }),
+    ] {
+        assert!(serde_json::from_value::<FileTime>(invalid).is_err());
+    }
+}
+
+#[test]
+fn test_flags_are_deserialized_from_their_value() {
+    let flags: FileAttributeFlags =
+        serde_json::from_value(json!({"names": ["FILE_ATTRIBUTE_ARCHIVE"], "raw": 32})).unwrap();
+    assert_eq!(flags, FileAttributeFlags::FILE_ATTRIBUTE_ARCHIVE);
+
+    // The names are only there for readers.
+    let flags: EntryFlags = serde_json::from_value(json!({"names": [], "raw": 3})).unwrap();
+    assert_eq!(flags, EntryFlags::ALLOCATED | EntryFlags::INDEX_PRESENT);
+
+    assert!(serde_json::from_value::<EntryFlags>(json!({"names": [], "raw": 0x10000})).is_err());
+    // Bits without a name are rejected rather than dropped.
+    assert!(serde_json::from_value::<EntryFlags>(json!({"names": [], "raw": 0x10})).is_err());
+    assert!(serde_json::from_value::<EntryFlags>(json!("ALLOCATED")).is_err());
+}
+
+#[test]
+fn test_mft_dump_output_is_deserialized() {
+    let sample = include_bytes!("../samples/entry_single_file");
+
+    let mut cmd = assert_cmd::Command::cargo_bin("mft_dump").expect("failed to find binary");
+    cmd.args(["-o", "jsonl", "-"]).write_stdin(sample.to_vec());
+    let output = cmd.assert().success().get_output().stdout.clone();
+    let line: Value = serde_json::from_slice(&output).unwrap();
+
+    // The entry and its derived fields are read from the same line.
+    let entry: MftEntry = serde_json::from_value(line.clone()).unwrap();
+    let derived: DerivedFields = serde_json::from_value(line.clone()).unwrap();
+    assert_eq!(
+        derived.full_path,
+        Some(PathBuf::from("[Unknown]/test_cfuncs.py"))
+    );
+
+    let serialized = serde_json::to_value(&entry).unwrap();
+    for key in ["header", "attributes", "valid_fixup", "anomalies"] {
+        assert_eq!(serialized[key], line[key]);
+    }
+
+    // Deserialized entries work like parsed ones.
+    let mut paths = PathTable::new();
+    paths.add_entry(&entry);
+    assert_eq!(
+        DerivedFields::from_entry_and_path_table(&entry, &paths).hard_link_paths,
+        derived.hard_link_paths
+    );
+
+    // Carved entries keep their offset.
+    let carved: CarvedEntry =
+        serde_json::from_value(json!({"carved_offset": 3072, "header": line["header"], "attributes": line["attributes"], "valid_fixup": true, "anomalies": []}))
+            .unwrap();
+    assert_eq!(carved.offset, 3072);
+    assert_eq!(
+        carved.entry.header.record_number,
+        entry.header.record_number
+    );
+}
@@ -1,14 +1,19 @@
 mod fixtures;
 
 use fixtures::*;
+use mft::anomaly::AnomalyKind;
+use mft::csv::FlatMftEntryWithName;
 use mft::entry::MftEntry;
+use mft::err::Error;
 use mft::mft::MftParser;
-use mft::attribute::{MftAttribute, MftAttributeType, FileAttributeFlags};
+use mft::attribute::{MftAttribute, MftAttributeType, FileAttributeFlags, ResidualAttribute};
+use mft::attribute::header::ResidentialHeader;
 use mft::attribute::x90::{IndexEntryHeader, IndexEntryFlags, IndexCollationRules};
 use mft::attribute::x30::{FileNameAttr, FileNamespace};
 use winstructs::ntfs:: mft_reference::MftReference;
-use winstructs::timestamp::WinTimestamp;
+use mft::FileTime;
 use serde_json;
+use std::path::PathBuf;
 
 #[test]
 fn test_entry_invalid_fixup_value() {
@@ -26,6 +31,59 @@ fn test_entry_invalid_fixup_value() {
     );
 }
 
+#[test]
+fn test_entry_invalid_fixup_value_is_an_anomaly() {
+    let mft_entry_buffer = include_bytes!("../samples/entry_102130_fixup_issue");
+
+    let entry =
+        MftEntry::from_buffer(mft_entry_buffer.to_vec(), 102130).expect("Failed to parse entry");
+
+    assert!(!entry.anomalies.is_empty());
+    for anomaly in &entry.anomalies {
+        assert_eq!(anomaly.kind, AnomalyKind::FixupMismatch);
+        assert_eq!(anomaly.offset % 512, 510);
+        assert_eq!(anomaly.attribute_instance, None);
+    }
+
+    let mft_json_value = serde_json::to_value(&entry).expect("Error serializing MftEntry");
+    let anomalies = mft_json_value["anomalies"].as_array().unwrap();
+    assert_eq!(anomalies.len(), entry.anomalies.len());
+    assert_eq!(anomalies[0]["kind"], "FixupMismatch");
+}
+
+#[test]
+fn test_entry_attribute_error_is_an_anomaly() {
+    let mut mft_entry_buffer = include_bytes!("../samples/entry_single_file").to_vec();
+
+    let entry = MftEntry::from_buffer(mft_entry_buffer.clone(), 0).unwrap();
+    assert!(entry.anomalies.is_empty());
+    let (attributes, anomalies) = entry.attributes_with_anomalies();
+    assert!(anomalies.is_empty());
+
+    // Corrupt the type code of the second attribute.
+    let first_attribute = usize::from(entry.header.first_attribute_record_offset);
+    let second_attribute = first_attribute + attributes[0].header.record_length as usize;
+    mft_entry_buffer[second_attribute..second_attribute + 4].copy_from_slice(&[0x42, 0, 0, 0]);
+
+    let entry = MftEntry::from_buffer(mft_entry_buffer, 0).unwrap();
+    let (corrupted_attributes, anomalies) = entry.attributes_with_anomalies();
+
+    assert_eq!(corrupted_attributes.len(), 1);
+    assert_eq!(anomalies.len(), 1);
+    assert_eq!(anomalies[0].kind, AnomalyKind::AttributeError);
+    assert_eq!(anomalies[0].offset, second_attribute as u64);
+    assert!(anomalies[0].message.contains("AttributeType"));
+
+    let mft_json_value = serde_json::to_value(&entry).unwrap();
+    assert_eq!(mft_json_value["attributes"].as_array().unwrap().len(), 2);
+    assert_eq!(mft_json_value["attributes"][1]["type_code"], 0x42);
+    assert_eq!(mft_json_value["anomalies"][0]["kind"], "AttributeError");
+    assert_eq!(
+        mft_json_value["anomalies"][0]["offset"],
+        second_attribute as u64
+    );
+}
+
 #[test]
 fn test_entry_index_root() {
     let sample = mft_sample_name("entry_multiple_index_root_entries");
@@ -40,8 +98,8 @@ fn test_entry_index_root() {
                 let index_entries = index_root.index_entries.index_entries;
                 assert_eq!(index_entries.len(), 4);
 
-                let created = WinTimestamp::new(&[0x00, 0x00, 0xC1, 0x03, 0xDB, 0x6A, 0xC6, 0x01]).unwrap().to_datetime();
-                let mft_modified = WinTimestamp::new(&[0x76, 0x86, 0xF6, 0x8C, 0x04, 0x64, 0xCA, 0x01]).unwrap().to_datetime();
+                let created = FileTime::from_raw(u64::from_le_bytes([0x00, 0x00, 0xC1, 0x03, 0xDB, 0x6A, 0xC6, 0x01]));
+                let mft_modified = FileTime::from_raw(u64::from_le_bytes([0x76, 0x86, 0xF6, 0x8C, 0x04, 0x64, 0xCA, 0x01]));
 
                 let index_entry_comp = IndexEntryHeader {                    
                     mft_reference: MftReference {
@@ -75,3 +133,403 @@ fn test_entry_index_root() {
         }
     }
 }
+
+#[test]
+fn test_entry_attribute_error_has_location() {
+    let mut mft_entry_buffer = include_bytes!("../samples/entry_single_file").to_vec();
+
+    let entry = MftEntry::from_buffer(mft_entry_buffer.clone(), 7).unwrap();
+    let first_attribute = entry.iter_attributes().next().unwrap().unwrap();
+    let second_attribute =
+        first_attribute.header.start_offset + u64::from(first_attribute.header.record_length);
+    let offset = second_attribute as usize;
+    mft_entry_buffer[offset..offset + 4].copy_from_slice(&[0x42, 0, 0, 0]);
+
+    let entry = MftEntry::from_buffer(mft_entry_buffer, 7).unwrap();
+    let error = entry
+        .iter_attributes()
+        .find_map(Result::err)
+        .expect("The second attribute is corrupted");
+
+    assert_eq!(error.record_number(), Some(7));
+    assert_eq!(error.attribute_offset(), Some(second_attribute));
+    assert_eq!(error.attribute_type(), None);
+    assert!(error.to_string().starts_with(&format!(
+        "[entry: 7, attribute at offset {}] Unknown `AttributeType`",
+        second_attribute
+    )));
+
+    let report = error.report();
+    assert_eq!(report.record_number, Some(7));
+    assert_eq!(report.message, "Unknown `AttributeType`: 0042");
+}
+
+#[test]
+fn test_entry_signature_error_has_record_number() {
+    let mut mft_entry_buffer = include_bytes!("../samples/entry_single_file").to_vec();
+    mft_entry_buffer[..4].copy_from_slice(b"XXXX");
+
+    let error = MftEntry::from_buffer(mft_entry_buffer, 3).unwrap_err();
+
+    assert_eq!(error.record_number(), Some(3));
+    assert_eq!(error.attribute_offset(), None);
+    assert!(matches!(
+        error.without_location(),
+        Error::InvalidEntrySignature { .. }
+    ));
+}
+
+#[test]
+fn test_entry_attribute_error_is_serialized() {
+    let mut mft_entry_buffer = include_bytes!("../samples/entry_single_file").to_vec();
+
+    let entry = MftEntry::from_buffer(mft_entry_buffer.clone(), 0).unwrap();
+    let (attributes, _) = entry.attributes_with_anomalies();
+    let file_name = attributes
+        .iter()
+        .find(|a| a.header.type_code == MftAttributeType::FileName)
+        .unwrap();
+
+    // An invalid namespace fails the $FILE_NAME attribute, after its header was parsed.
+    let offset = file_name.header.start_offset as usize;
+    let content_offset = match &file_name.header.residential_header {
+        ResidentialHeader::Resident(resident) => usize::from(resident.data_offset),
+        ResidentialHeader::NonResident(_) => panic!("$FILE_NAME is always resident"),
+    };
+    mft_entry_buffer[offset + content_offset + 65] = 0x42;
+
+    let entry = MftEntry::from_buffer(mft_entry_buffer, 0).unwrap();
+    let records = entry.attribute_records();
+    assert_eq!(records.len(), attributes.len());
+
+    let error = records.iter().find_map(|r| r.error()).unwrap();
+    assert_eq!(error.type_code, Some(0x30));
+    assert_eq!(error.attribute_type, Some(MftAttributeType::FileName));
+    assert_eq!(error.offset, offset as u64);
+    assert_eq!(error.instance, Some(file_name.header.instance));
+    assert_eq!(error.raw.len(), file_name.header.record_length as usize);
+
+    let mft_json_value = serde_json::to_value(&entry).unwrap();
+    let serialized = mft_json_value["attributes"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .find(|a| a.get("error").is_some())
+        .unwrap();
+    assert_eq!(serialized["type_code"], 0x30);
+    assert_eq!(serialized["attribute_type"], "FileName");
+    assert_eq!(serialized["offset"], offset as u64);
+    assert!(serialized["raw"].as_str().unwrap().starts_with("30000000"));
+
+    let flat = FlatMftEntryWithName::from_entry_and_path(&entry, PathBuf::new());
+    assert_eq!(flat.anomaly_count, 1);
+    assert!(flat
+        .attribute_errors
+        .starts_with(&format!("FileName at {}: ", offset)));
+}
+
+#[test]
+fn test_entry_ref_matches_owned_entry() {
+    let mft_entry_buffer = include_bytes!("../samples/entry_single_file");
+    let mut parser = MftParser::from_buffer(mft_entry_buffer.to_vec()).unwrap();
+
+    let owned = parser.get_entry(0).unwrap();
+    let owned_attributes: Vec<MftAttribute> = owned.iter_attributes().filter_map(Result::ok).collect();
+
+    let borrowed = parser.get_entry_ref(0).unwrap();
+    assert_eq!(borrowed.data, &owned.data[..]);
+    assert_eq!(borrowed.valid_fixup, owned.valid_fixup);
+    assert_eq!(borrowed.anomalies, owned.anomalies);
+
+    let borrowed_attributes: Vec<MftAttribute> = borrowed.iter_attributes().filter_map(Result::ok).collect();
+    assert_eq!(borrowed_attributes.len(), owned_attributes.len());
+
+    assert_eq!(
+        serde_json::to_value(borrowed.to_entry()).unwrap(),
+        serde_json::to_value(owned).unwrap()
+    );
+
+    assert!(parser.get_entry_ref(1).is_err());
+}
+
+#[cfg(feature = "mmap")]
+#[test]
+fn test_entry_ref_from_memory_mapped_file() {
+    let mft_entry_buffer = include_bytes!("../samples/entry_single_file");
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("MFT");
+    let mut data = mft_entry_buffer.to_vec();
+    data.extend_from_slice(mft_entry_buffer);
+    std::fs::write(&path, data).unwrap();
+
+    let mut parser = MftParser::from_path_mmap(&path).unwrap();
+    assert_eq!(parser.get_entry_count(), 2);
+
+    let owned = MftEntry::from_buffer(mft_entry_buffer.to_vec(), 1).unwrap();
+    let borrowed = parser.get_entry_ref(1).unwrap();
+    assert_eq!(borrowed.data, &owned.data[..]);
+    assert_eq!(borrowed.header.record_number, 1);
+    assert_eq!(
+        serde_json::to_value(borrowed.to_entry()).unwrap(),
+        serde_json::to_value(&owned).unwrap()
+    );
+
+    assert!(parser.get_entry_ref(2).is_err());
+}
+
+#[test]
+fn test_entry_invalid_fixup_value_is_an_error_when_strict() {
+    let mft_entry_buffer = include_bytes!("../samples/entry_102130_fixup_issue");
+    let fixup = mft::FixupOptions {
+        policy: mft::FixupPolicy::Strict,
+        ..Default::default()
+    };
+
+    let error = MftEntry::from_buffer_with_fixup(mft_entry_buffer.to_vec(), 102130, fixup)
+        .unwrap_err();
+    assert_eq!(error.record_number(), Some(102130));
+    assert!(matches!(
+        error.without_location(),
+        Error::FixupMismatch { offset, .. } if offset % 512 == 510
+    ));
+
+    // A valid entry is not affected by the policy.
+    let mft_entry_buffer = include_bytes!("../samples/entry_single_file");
+    let entry = MftEntry::from_buffer_with_fixup(mft_entry_buffer.to_vec(), 0, fixup).unwrap();
+    assert_eq!(entry.valid_fixup, Some(true));
+}
+
+#[test]
+fn test_entry_invalid_fixup_value_is_preserved() {
+    let mft_entry_buffer = include_bytes!("../samples/entry_102130_fixup_issue");
+    let fixup = mft::FixupOptions {
+        policy: mft::FixupPolicy::Preserve,
+        ..Default::default()
+    };
+
+    let entry =
+        MftEntry::from_buffer_with_fixup(mft_entry_buffer.to_vec(), 102130, fixup).unwrap();
+    let lenient = MftEntry::from_buffer(mft_entry_buffer.to_vec(), 102130).unwrap();
+
+    assert_eq!(entry.data, lenient.data);
+    assert_eq!(entry.anomalies, lenient.anomalies);
+    assert_eq!(entry.original_data.as_deref(), Some(&mft_entry_buffer[..]));
+    assert!(lenient.original_data.is_none());
+
+    let mft_json_value = serde_json::to_value(&entry).unwrap();
+    assert_eq!(
+        mft_json_value["original_data"].as_str().unwrap().len(),
+        mft_entry_buffer.len() * 2
+    );
+    assert!(serde_json::to_value(&lenient).unwrap().get("original_data").is_none());
+
+    // Nothing to preserve for a valid entry.
+    let mft_entry_buffer = include_bytes!("../samples/entry_single_file");
+    let entry = MftEntry::from_buffer_with_fixup(mft_entry_buffer.to_vec(), 0, fixup).unwrap();
+    assert!(entry.original_data.is_none());
+}
+
+#[test]
+fn test_entry_fixup_stride() {
+    let mut mft_entry_buffer = include_bytes!("../samples/entry_single_file").to_vec();
+    let usa_offset = usize::from(u16::from_le_bytes([mft_entry_buffer[4], mft_entry_buffer[5]]));
+    // A single stride, covering the whole entry.
+    mft_entry_buffer[6..8].copy_from_slice(&[2, 0]);
+    let fixup = mft::FixupOptions {
+        stride: 1024,
+        ..Default::default()
+    };
+
+    let entry = MftEntry::from_buffer_with_fixup(mft_entry_buffer.clone(), 0, fixup).unwrap();
+
+    assert_eq!(entry.valid_fixup, Some(true));
+    assert_eq!(entry.data[510..512], mft_entry_buffer[510..512]);
+    assert_eq!(
+        entry.data[1022..1024],
+        mft_entry_buffer[usa_offset + 2..usa_offset + 4]
+    );
+
+    // The array cannot cover more strides than there are in the entry.
+    let fixup = mft::FixupOptions {
+        stride: 4096,
+        ..Default::default()
+    };
+    let error = MftEntry::from_buffer_with_fixup(mft_entry_buffer, 0, fixup).unwrap_err();
+    assert!(matches!(
+        error.without_location(),
+        Error::InvalidUpdateSequenceArray { .. }
+    ));
+}
+
+fn attribute_types(entry: &MftEntry) -> Vec<MftAttributeType> {
+    entry
+        .iter_attributes()
+        .map(|attribute| attribute.unwrap().header.type_code)
+        .collect()
+}
+
+#[test]
+fn test_entry_recovers_baad_records() {
+    let mft_entry_buffer = include_bytes!("../samples/entry_single_file").to_vec();
+    let intact = MftEntry::from_buffer(mft_entry_buffer.clone(), 0).unwrap();
+
+    let mut baad = mft_entry_buffer.clone();
+    baad[..4].copy_from_slice(b"BAAD");
+
+    // Without recovery, fixups are not applied.
+    let entry = MftEntry::from_buffer(baad.clone(), 0).unwrap();
+    assert_eq!(entry.valid_fixup, None);
+    assert!(!entry.recovered);
+
+    let entry = MftEntry::recover(baad, 0, Default::default()).unwrap();
+    assert!(entry.recovered);
+    assert_eq!(entry.valid_fixup, Some(true));
+    assert_eq!(entry.data[4..], intact.data[4..]);
+    assert_eq!(attribute_types(&entry), attribute_types(&intact));
+    assert_eq!(entry.anomalies.len(), 1);
+    assert_eq!(entry.anomalies[0].kind, AnomalyKind::Recovered);
+
+    let mft_json_value = serde_json::to_value(&entry).unwrap();
+    assert_eq!(mft_json_value["recovered"], true);
+    assert_eq!(mft_json_value["anomalies"][0]["kind"], "Recovered");
+}
+
+#[test]
+fn test_entry_recovers_records_with_broken_headers() {
+    let mut mft_entry_buffer = include_bytes!("../samples/entry_single_file").to_vec();
+    let intact = MftEntry::from_buffer(mft_entry_buffer.clone(), 0).unwrap();
+
+    // An unknown signature, and a first attribute offset pointing into the header.
+    mft_entry_buffer[..4].copy_from_slice(b"XXXX");
+    mft_entry_buffer[20..22].copy_from_slice(&[0x08, 0]);
+    assert!(MftEntry::from_buffer(mft_entry_buffer.clone(), 0).is_err());
+
+    let entry = MftEntry::recover(mft_entry_buffer, 0, Default::default()).unwrap();
+    assert!(entry.recovered);
+    assert_eq!(
+        entry.header.first_attribute_record_offset,
+        intact.header.first_attribute_record_offset
+    );
+    assert_eq!(attribute_types(&entry), attribute_types(&intact));
+    assert_eq!(entry.anomalies.len(), 2);
+}
+
+#[test]
+fn test_entry_recovery_skips_implausible_attributes() {
+    let mut mft_entry_buffer = include_bytes!("../samples/entry_single_file").to_vec();
+    let intact = MftEntry::from_buffer(mft_entry_buffer.clone(), 0).unwrap();
+    let attributes: Vec<MftAttribute> = intact.iter_attributes().map(Result::unwrap).collect();
+
+    // The record length of the second attribute is broken.
+    mft_entry_buffer[..4].copy_from_slice(b"BAAD");
+    let second_attribute = attributes[1].header.start_offset as usize;
+    mft_entry_buffer[second_attribute + 4..second_attribute + 8].copy_from_slice(&[7, 0, 0, 0]);
+
+    let entry = MftEntry::recover(mft_entry_buffer, 0, Default::default()).unwrap();
+
+    let mut expected = attribute_types(&intact);
+    expected.remove(1);
+    assert_eq!(attribute_types(&entry), expected);
+}
+
+#[test]
+fn test_entry_recovery_leaves_intact_records_alone() {
+    let mft_entry_buffer = include_bytes!("../samples/entry_single_file").to_vec();
+    let intact = MftEntry::from_buffer(mft_entry_buffer.clone(), 0).unwrap();
+
+    let entry = MftEntry::recover(mft_entry_buffer, 0, Default::default()).unwrap();
+    assert!(!entry.recovered);
+    assert!(entry.anomalies.is_empty());
+    assert_eq!(entry.data, intact.data);
+    assert!(serde_json::to_value(&entry).unwrap().get("recovered").is_none());
+
+    // Records which are garbage through and through are still rejected.
+    assert!(MftEntry::recover(vec![0xAB; 1024], 0, Default::default()).is_err());
+}
+
+#[test]
+fn test_entry_carves_residual_attributes_from_slack() {
+    let mut mft_entry_buffer = include_bytes!("../samples/entry_single_file").to_vec();
+    let intact = MftEntry::from_buffer(mft_entry_buffer.clone(), 0).unwrap();
+    assert_eq!(intact.iter_residual_attributes().count(), 0);
+
+    // Leave copies of the second $FILE_NAME and of the $DATA attribute past the used size.
+    let attributes: Vec<MftAttribute> = intact.iter_attributes().map(Result::unwrap).collect();
+    let mut slack_offset = 520;
+    for attribute in &attributes[2..] {
+        let start = attribute.header.start_offset as usize;
+        let end = start + attribute.header.record_length as usize;
+        mft_entry_buffer.copy_within(start..end, slack_offset);
+        slack_offset += end - start;
+    }
+
+    let entry = MftEntry::from_buffer(mft_entry_buffer, 0).unwrap();
+    assert_eq!(attribute_types(&entry), attribute_types(&intact));
+
+    let carved: Vec<ResidualAttribute> = entry.iter_residual_attributes().collect();
+    let residual: Vec<MftAttribute> = carved.iter().map(|residual| residual.attribute.clone()).collect();
+    assert_eq!(residual.len(), 2);
+    assert_eq!(residual[0].header.start_offset, 520);
+    assert_eq!(
+        residual[0].data.clone().into_file_name().unwrap().name,
+        attributes[2].data.clone().into_file_name().unwrap().name
+    );
+    assert_eq!(residual[1].header.type_code, MftAttributeType::DATA);
+
+    // Serialized residual attributes are labeled with their offset into the slack.
+    let used_entry_size = u64::from(entry.header.used_entry_size);
+    let serialized = serde_json::to_value(&carved[1]).unwrap();
+    assert_eq!(serialized["slack_offset"], residual[1].header.start_offset - used_entry_size);
+    assert_eq!(serialized["header"], serde_json::to_value(&residual[1].header).unwrap());
+}
+
+#[test]
+fn test_entry_bodyfile_lines() {
+    let entry = MftEntry::from_buffer(include_bytes!("../samples/entry_single_file").to_vec(), 7)
+        .unwrap();
+
+    let lines: Vec<String> = mft::bodyfile::BodyfileLine::from_entry_and_path(
+        &entry,
+        PathBuf::from("dir/test_cfuncs.py"),
+    )
+    .iter()
+    .map(ToString::to_string)
+    .collect();
+
+    assert_eq!(
+        lines,
+        vec![
+            "0|dir/test_cfuncs.py|7-16-0|r/rrwxrwxrwx|0|0|8072|1258077404|1204258356|1258077404|1204258356",
+            "0|dir/TEST_C~3.PY ($FILE_NAME)|7-48-3|r/rrwxrwxrwx|0|0|8072|1258077404|1258077404|1258077404|1258077404",
+            "0|dir/test_cfuncs.py ($FILE_NAME)|7-48-2|r/rrwxrwxrwx|0|0|8072|1258077404|1258077404|1258077404|1258077404",
+        ]
+    );
+}
+
+#[test]
+fn test_csv_row_has_every_name_and_stream() {
+    let entry = MftEntry::from_buffer(include_bytes!("../samples/entry_single_file").to_vec(), 0).unwrap();
+    let flat = FlatMftEntryWithName::from_entry_and_path(&entry, PathBuf::new());
+
+    // The long name, even though the DOS name comes first.
+    assert_eq!(flat.file_name.as_deref(), Some("test_cfuncs.py"));
+    assert_eq!(flat.file_name_namespace, Some(FileNamespace::Win32));
+    assert_eq!(flat.parent_entry_id, Some(26359));
+    assert_eq!(flat.parent_entry_sequence, Some(1));
+    assert_eq!(flat.short_file_name.as_deref(), Some("TEST_C~3.PY"));
+    assert_eq!(
+        flat.file_names,
+        "TEST_C~3.PY (DOS, parent 26359-1); test_cfuncs.py (Win32, parent 26359-1)"
+    );
+    assert!(flat.standard_info_mft_modified.is_some());
+    assert_eq!(flat.standard_info_usn, Some(29607584));
+    assert_eq!(flat.standard_info_security_id, Some(261));
+    assert_eq!(flat.data_streams, ":8072");
+
+    let entry = MftEntry::from_buffer(include_bytes!("../samples/entry_data_run_at_offset").to_vec(), 0).unwrap();
+    let flat = FlatMftEntryWithName::from_entry_and_path(&entry, PathBuf::new());
+    assert!(flat.has_alternate_data_streams);
+    assert_eq!(flat.data_streams, "$J:2152925272");
+    assert_eq!(flat.file_name, None);
+    assert_eq!(flat.file_names, "");
+}
@@ -0,0 +1,94 @@
+use mft::csv::FlatMftEntryWithName;
+use mft::err::Error;
+use mft::fields::{find_field, FieldSelection, FIELDS};
+use mft::MftEntry;
+
+use std::collections::HashSet;
+use std::path::PathBuf;
+
+fn row() -> FlatMftEntryWithName {
+    let entry =
+        MftEntry::from_buffer(include_bytes!("../samples/entry_single_file").to_vec(), 0).unwrap();
+    let mut row =
+        FlatMftEntryWithName::from_entry_and_path(&entry, PathBuf::from("dir/test_cfuncs.py"));
+    row.carved_offset = Some(3072);
+    row
+}
+
+#[test]
+fn test_the_catalog_has_every_column() {
+    let columns: Vec<String> = match serde_json::to_value(row()).unwrap() {
+        serde_json::Value::Object(columns) => columns.keys().cloned().collect(),
+        _ => unreachable!(),
+    };
+
+    let catalog: HashSet<&str> = FIELDS.iter().map(|field| field.column).collect();
+    for column in &columns {
+        assert!(
+            catalog.contains(column.as_str()),
+            "{} is not in the catalog",
+            column
+        );
+    }
+    assert_eq!(catalog.len(), columns.len());
+
+    let names: HashSet<&str> = FIELDS.iter().map(|field| field.name).collect();
+    assert_eq!(names.len(), FIELDS.len());
+}
+
+#[test]
+fn test_it_projects_rows_in_the_selected_order() {
+    let selection =
+        FieldSelection::parse("full_path, entry_id,si_created,carved_offset,fn_name").unwrap();
+    assert_eq!(
+        selection.header(),
+        vec![
+            "full_path",
+            "entry_id",
+            "si_created",
+            "carved_offset",
+            "fn_name"
+        ]
+    );
+
+    let projected = selection.project(&row());
+    assert_eq!(
+        projected.to_record(),
+        vec![
+            "dir/test_cfuncs.py",
+            "0",
+            "2008-02-29T04:12:36.0000000Z",
+            "3072",
+            "test_cfuncs.py"
+        ]
+    );
+    assert_eq!(
+        serde_json::to_string(&projected).unwrap(),
+        r#"{"full_path":"dir/test_cfuncs.py","entry_id":0,"si_created":"2008-02-29T04:12:36.0000000Z","carved_offset":3072,"fn_name":"test_cfuncs.py"}"#
+    );
+
+    // Missing values are empty.
+    let mut row = row();
+    row.carved_offset = None;
+    let projected = FieldSelection::parse("carved_offset")
+        .unwrap()
+        .project(&row);
+    assert_eq!(
+        projected.get("carved_offset"),
+        Some(&serde_json::Value::Null)
+    );
+    assert_eq!(projected.to_record(), vec![""]);
+}
+
+#[test]
+fn test_it_rejects_unknown_fields() {
+    assert!(find_field("si_created").is_some());
+    assert!(find_field("StandardInfoCreated").is_none());
+
+    for selection in ["entry_id,nope", "", ","] {
+        assert!(matches!(
+            FieldSelection::parse(selection),
+            Err(Error::InvalidFieldSelection { .. })
+        ));
+    }
+}
@@ -0,0 +1,281 @@
+#![cfg(feature = "images")]
+mod fixtures;
+mod image_fixtures;
+
+use fixtures::*;
+use image_fixtures::*;
+
+use mft::image::{EwfReader, SplitRawReader, VhdReader, VhdxReader};
+use mft::volume::BootSector;
+use mft::MftParser;
+use std::fs;
+use std::io::{Cursor, Read, Seek, SeekFrom};
+use tempfile::tempdir;
+
+fn sample_records() -> Vec<Vec<u8>> {
+    [
+        "entry_single_file",
+        "entry_multiple_index_root_entries",
+        "entry_data_run_at_offset",
+        "entry_102130_fixup_issue",
+    ]
+    .iter()
+    .map(|name| fs::read(mft_sample_name(name)).unwrap())
+    .collect()
+}
+
+#[test]
+fn test_parses_boot_sector() {
+    let boot_sector = BootSector::from_reader(&mut Cursor::new(boot_sector())).unwrap();
+
+    assert_eq!(boot_sector.cluster_size(), CLUSTER_SIZE as u64);
+    assert_eq!(boot_sector.mft_record_size(), RECORD_SIZE as u64);
+    assert_eq!(
+        boot_sector.mft_offset(),
+        (MFT_RUNS[0] * CLUSTER_SIZE) as u64
+    );
+    assert_eq!(boot_sector.volume_serial_number, 0x1234_5678_9ABC_DEF0);
+}
+
+#[test]
+fn test_rejects_non_ntfs_boot_sector() {
+    let mut sector = boot_sector();
+    sector[3..11].copy_from_slice(b"EXFAT   ");
+
+    assert!(!BootSector::is_ntfs_volume(&mut Cursor::new(&sector)).unwrap());
+    assert!(BootSector::from_reader(&mut Cursor::new(&sector)).is_err());
+}
+
+#[test]
+fn test_volume_parser_follows_mft_data_runs() {
+    ensure_env_logger_initialized();
+    let mft = mft_contents(&sample_records());
+
+    let mut expected_parser = MftParser::from_buffer(mft.clone()).unwrap();
+    let mut parser = MftParser::from_volume(Cursor::new(ntfs_volume(&mft))).unwrap();
+
+    assert_eq!(parser.get_entry_count(), expected_parser.get_entry_count());
+    assert_eq!(
+        entries_as_json(&mut parser),
+        entries_as_json(&mut expected_parser)
+    );
+}
+
+#[test]
+fn test_ewf_reader_reads_media() {
+    let d = tempdir().unwrap();
+    let volume = ntfs_volume(&mft_contents(&sample_records()));
+    write_ewf(d.path(), "image", &volume, 5);
+
+    let mut reader = EwfReader::open(d.path().join("image.E01")).unwrap();
+    assert_eq!(reader.len(), volume.len() as u64);
+
+    let mut media = vec![];
+    reader.read_to_end(&mut media).unwrap();
+    assert!(
+        media == volume,
+        "EWF media does not match the imaged volume"
+    );
+
+    // Reads which cross a chunk (and segment) boundary.
+    let offset = 5 * CLUSTER_SIZE - 100;
+    reader.seek(SeekFrom::Start(offset as u64)).unwrap();
+    let mut buffer = vec![0; 300];
+    reader.read_exact(&mut buffer).unwrap();
+    assert_eq!(buffer, &volume[offset..offset + 300]);
+}
+
+#[test]
+fn test_ewf_reader_fails_on_missing_segment() {
+    let d = tempdir().unwrap();
+    let volume = ntfs_volume(&mft_contents(&sample_records()));
+    write_ewf(d.path(), "image", &volume, 5);
+    fs::remove_file(d.path().join("image.E02")).unwrap();
+
+    assert!(EwfReader::open(d.path().join("image.E01")).is_err());
+}
+
+#[test]
+fn test_ewf_reader_rejects_overflowing_chunk_offsets() {
+    let d = tempdir().unwrap();
+    let volume = ntfs_volume(&mft_contents(&sample_records()));
+    write_ewf(d.path(), "image", &volume, 5);
+
+    let path = d.path().join("image.E01");
+    let mut segment = fs::read(&path).unwrap();
+    let table = segment
+        .windows(6)
+        .position(|window| window == b"table\0")
+        .unwrap();
+    let base_offset = table + 76 + 8;
+    segment[base_offset..base_offset + 8].copy_from_slice(&u64::MAX.to_le_bytes());
+    fs::write(&path, segment).unwrap();
+
+    let error = EwfReader::open(&path).err().unwrap();
+    assert!(error.to_string().contains("chunk offset"), "{}", error);
+}
+
+#[test]
+fn test_split_raw_reader_reads_media() {
+    let d = tempdir().unwrap();
+    let volume = ntfs_volume(&mft_contents(&sample_records()));
+    write_split_raw(d.path(), "image", &volume, 20000);
+
+    let mut reader = SplitRawReader::open(d.path().join("image.001")).unwrap();
+    assert_eq!(reader.len(), volume.len() as u64);
+
+    let mut media = vec![];
+    reader.read_to_end(&mut media).unwrap();
+    assert!(
+        media == volume,
+        "split media does not match the imaged volume"
+    );
+}
+
+#[test]
+fn test_it_locates_mft_in_images() {
+    let d = tempdir().unwrap();
+    let mft = mft_contents(&sample_records());
+    let volume = ntfs_volume(&mft);
+
+    write_ewf(d.path(), "image", &volume, 5);
+    write_split_raw(d.path(), "image", &volume, 20000);
+    fs::write(d.path().join("volume.dd"), &volume).unwrap();
+    fs::write(d.path().join("MFT"), &mft).unwrap();
+
+    let expected = entries_as_json(&mut MftParser::from_buffer(mft).unwrap());
+
+    for name in ["image.E01", "image.001", "volume.dd", "MFT"] {
+        let mut parser = MftParser::from_image_path(d.path().join(name)).unwrap();
+        assert_eq!(entries_as_json(&mut parser), expected, "{}", name);
+    }
+}
+
+#[test]
+fn test_it_dumps_ewf_image() {
+    let d = tempdir().unwrap();
+    let mft = mft_contents(&sample_records());
+    write_ewf(d.path(), "image", &ntfs_volume(&mft), 5);
+    fs::write(d.path().join("MFT"), &mft).unwrap();
+
+    let dump = |name: &str| {
+        let mut cmd = assert_cmd::Command::cargo_bin("mft_dump").expect("failed to find binary");
+        cmd.args(["-o", "jsonl", d.path().join(name).to_str().unwrap()]);
+        cmd.assert().success().get_output().stdout.clone()
+    };
+
+    let output = dump("image.E01");
+    assert!(!output.is_empty());
+    assert_eq!(output, dump("MFT"));
+}
+
+/// A disk with an unallocated gap, and a region of junk after the NTFS partition.
+fn sparse_disk(mft: &[u8]) -> Vec<u8> {
+    let mut disk = mbr_disk(&[(0x07, ntfs_volume(mft))], &[]);
+    disk.resize(3 * 1024 * 1024, 0);
+    let tail = disk.len() - 4096;
+    disk[tail..].fill(0x5A);
+
+    disk
+}
+
+#[test]
+fn test_vhd_reader_reads_media() {
+    let d = tempdir().unwrap();
+    let disk = sparse_disk(&mft_contents(&sample_records()));
+
+    write_vhd_fixed(&d.path().join("fixed.vhd"), &disk);
+    write_vhd_dynamic(&d.path().join("dynamic.vhd"), &disk, 16 * 1024);
+
+    for name in ["fixed.vhd", "dynamic.vhd"] {
+        let mut reader = VhdReader::open(d.path().join(name)).unwrap();
+        assert_eq!(reader.len(), disk.len() as u64);
+
+        let mut media = vec![];
+        reader.read_to_end(&mut media).unwrap();
+        assert!(media == disk, "{} media does not match the disk", name);
+    }
+}
+
+#[test]
+fn test_vhd_reader_rejects_a_block_allocation_table_larger_than_the_file() {
+    let d = tempdir().unwrap();
+    let path = d.path().join("dynamic.vhd");
+    write_vhd_dynamic(&path, &vec![0x11; 16 * 1024], 16 * 1024);
+
+    // A table of 2^32 - 1 sector sized blocks, which would take 16GB.
+    let mut vhd = fs::read(&path).unwrap();
+    let footer = vhd.len() - 512;
+    let size = u64::from(u32::MAX) * 512;
+    vhd[footer + 48..footer + 56].copy_from_slice(&size.to_be_bytes());
+    vhd[512 + 28..512 + 32].copy_from_slice(&u32::MAX.to_be_bytes());
+    vhd[512 + 32..512 + 36].copy_from_slice(&512_u32.to_be_bytes());
+    fs::write(&path, vhd).unwrap();
+
+    let error = VhdReader::open(&path).err().unwrap();
+    assert!(
+        error.to_string().contains("outside of the file"),
+        "{}",
+        error
+    );
+}
+
+#[test]
+fn test_vhdx_reader_reads_media() {
+    let d = tempdir().unwrap();
+    let disk = sparse_disk(&mft_contents(&sample_records()));
+    write_vhdx(&d.path().join("disk.vhdx"), &disk);
+
+    let mut reader = VhdxReader::open(d.path().join("disk.vhdx")).unwrap();
+    assert_eq!(reader.len(), disk.len() as u64);
+
+    let mut media = vec![];
+    reader.read_to_end(&mut media).unwrap();
+    assert!(media == disk, "VHDX media does not match the disk");
+}
+
+#[test]
+fn test_vhdx_reader_rejects_a_bat_larger_than_the_file() {
+    let d = tempdir().unwrap();
+    let path = d.path().join("disk.vhdx");
+    write_vhdx(&path, &vec![0x11; 1024 * 1024]);
+
+    // 500M blocks of 1MB, in a BAT region which claims to be almost 4GB.
+    let mut vhdx = fs::read(&path).unwrap();
+    let bat_entry = 192 * 1024 + 16;
+    vhdx[bat_entry + 24..bat_entry + 28].copy_from_slice(&0xFFFF_F000_u32.to_le_bytes());
+    let size = 500_000_000_u64 * 1024 * 1024;
+    let items = 2 * 1024 * 1024 + 64 * 1024;
+    vhdx[items + 8..items + 16].copy_from_slice(&size.to_le_bytes());
+    fs::write(&path, vhdx).unwrap();
+
+    let error = VhdxReader::open(&path).err().unwrap();
+    assert!(
+        error.to_string().contains("outside of the file"),
+        "{}",
+        error
+    );
+}
+
+#[test]
+fn test_it_dumps_virtual_disks() {
+    let d = tempdir().unwrap();
+    let mft = mft_contents(&sample_records());
+    let disk = sparse_disk(&mft);
+
+    write_vhd_fixed(&d.path().join("fixed.vhd"), &disk);
+    write_vhd_dynamic(&d.path().join("dynamic.vhd"), &disk, 16 * 1024);
+    write_vhdx(&d.path().join("disk.vhdx"), &disk);
+    fs::write(d.path().join("MFT"), &mft).unwrap();
+
+    let dump = |name: &str| {
+        let mut cmd = assert_cmd::Command::cargo_bin("mft_dump").expect("failed to find binary");
+        cmd.args(["-o", "jsonl", d.path().join(name).to_str().unwrap()]);
+        cmd.assert().success().get_output().stdout.clone()
+    };
+
+    let expected = dump("MFT");
+    for name in ["fixed.vhd", "dynamic.vhd", "disk.vhdx"] {
+        assert!(dump(name) == expected, "{} output does not match", name);
+    }
+}
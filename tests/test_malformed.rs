@@ -0,0 +1,284 @@
+use mft::attribute::data_run::decode_data_runs;
+use mft::attribute::raw::RawAttribute;
+use mft::attribute::x20::AttributeListAttr;
+use mft::attribute::x80::DataAttr;
+use mft::attribute::x90::{IndexEntries, IndexEntryHeader};
+use mft::attribute::MftAttributeType;
+use mft::err::Error;
+use mft::stream::MftStreamParser;
+use mft::{MftEntry, MftParser, ParseLimits};
+
+use std::io::Cursor;
+
+const ENTRY: &[u8; 1024] = include_bytes!("../samples/entry_single_file");
+
+fn with_bytes(offset: usize, bytes: &[u8]) -> Vec<u8> {
+    let mut entry = ENTRY.to_vec();
+    entry[offset..offset + bytes.len()].copy_from_slice(bytes);
+    entry
+}
+
+#[test]
+fn test_it_rejects_update_sequence_arrays_out_of_bounds() {
+    // usa_size of 0, an offset outside of the entry, and more strides than the entry has.
+    for (offset, bytes) in [(6, [0, 0]), (4, [0xF0, 0xFF]), (6, [0xFF, 0xFF])] {
+        let error = MftEntry::from_buffer(with_bytes(offset, &bytes), 0).unwrap_err();
+
+        assert!(matches!(
+            error.without_location(),
+            Error::InvalidUpdateSequenceArray { .. }
+        ));
+    }
+}
+
+#[test]
+fn test_it_stops_on_an_empty_attribute_record() {
+    // The record length of the first attribute.
+    let entry = MftEntry::from_buffer(with_bytes(56 + 4, &[0, 0, 0, 0]), 0).unwrap();
+
+    let records = entry.attribute_records();
+    assert_eq!(records.len(), 1);
+    assert!(records[0]
+        .error()
+        .unwrap()
+        .error
+        .starts_with("Invalid record length"));
+}
+
+#[test]
+fn test_it_does_not_trust_attribute_sizes() {
+    let data = [0_u8; 16];
+
+    assert!(DataAttr::from_stream(&mut Cursor::new(data), usize::MAX).is_err());
+    assert!(
+        RawAttribute::from_stream(&mut Cursor::new(data), MftAttributeType::EA, usize::MAX)
+            .is_err()
+    );
+}
+
+#[test]
+fn test_it_rejects_empty_index_entries() {
+    let mut index_entry = [0_u8; 0x52];
+    // MFT reference 1-1, followed by a record length of 0.
+    index_entry[0] = 1;
+    index_entry[6] = 1;
+
+    let error = IndexEntryHeader::from_stream(&mut Cursor::new(index_entry)).unwrap_err();
+    assert!(matches!(
+        error,
+        Error::InvalidRecordLength { record_length: 0 }
+    ));
+}
+
+#[test]
+fn test_it_rejects_empty_attribute_list_entries() {
+    let attribute_list = [
+        0x10_u8, 0, 0, 0, 0, 0, 0, 0x1A, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0,
+    ];
+
+    let error = AttributeListAttr::from_stream(&mut Cursor::new(attribute_list), None).unwrap_err();
+    assert!(matches!(
+        error,
+        Error::InvalidRecordLength { record_length: 0 }
+    ));
+}
+
+#[test]
+fn test_it_rejects_overflowing_data_runs() {
+    let runs = [
+        0x81, 0x01, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0x7F, // LCN i64::MAX
+        0x11, 0x01, 0x01, // +1
+        0x00,
+    ];
+    assert_eq!(decode_data_runs(&runs), None);
+
+    // A run without a length is odd, but can be decoded.
+    assert!(decode_data_runs(&[0x10, 0x01, 0x00]).is_some());
+}
+
+#[test]
+fn test_it_rejects_an_mft_without_an_entry_size() {
+    assert!(MftParser::from_buffer(vec![0; 4096]).is_err());
+}
+
+#[test]
+fn test_it_rejects_implausible_entry_sizes() {
+    // Not a power of two, and larger than any record.
+    for entry_size in [1000_u32, 0x2_0000, 0xFFFF_FFFF] {
+        let buffer = with_bytes(28, &entry_size.to_le_bytes());
+
+        let error = MftParser::from_buffer(buffer.clone()).err().unwrap();
+        assert!(matches!(error, Error::InvalidEntrySize { .. }));
+
+        let error = MftStreamParser::from_read(&buffer[..], None).err().unwrap();
+        assert!(matches!(error, Error::InvalidEntrySize { .. }));
+        let error = MftStreamParser::from_read(&ENTRY[..], Some(entry_size))
+            .err()
+            .unwrap();
+        assert!(matches!(error, Error::InvalidEntrySize { .. }));
+    }
+}
+
+#[test]
+fn test_it_does_not_panic_on_corrupted_bytes() {
+    for offset in 0..ENTRY.len() {
+        for value in [0x00, 0x01, 0x7F, 0x80, 0xFF] {
+            let entry = match MftEntry::from_buffer(with_bytes(offset, &[value]), 0) {
+                Ok(entry) => entry,
+                Err(_) => continue,
+            };
+
+            serde_json::to_value(&entry).unwrap();
+            entry.find_best_name_attribute();
+            entry.iter_attributes().for_each(drop);
+        }
+    }
+}
+
+#[test]
+fn test_it_enforces_the_attribute_size_limit() {
+    let limits = ParseLimits {
+        max_attribute_size: 16,
+        ..ParseLimits::default()
+    };
+    let entry = MftEntry::from_buffer(ENTRY.to_vec(), 0)
+        .unwrap()
+        .with_limits(limits);
+
+    let error = entry.iter_attributes().find_map(|a| a.err()).unwrap();
+    assert!(matches!(
+        error.without_location(),
+        Error::LimitExceeded {
+            limit: "attribute size",
+            max: 16,
+            ..
+        }
+    ));
+
+    // The default limits leave the entry alone.
+    let entry = entry.with_limits(ParseLimits::default());
+    assert!(entry.iter_attributes().all(|a| a.is_ok()));
+}
+
+#[test]
+fn test_it_enforces_the_attribute_list_entries_limit() {
+    let attribute_list = [
+        0x10_u8, 0, 0, 0, 0x1A, 0, 0, 0x1A, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0,
+    ]
+    .repeat(3);
+    let limits = ParseLimits {
+        max_attribute_list_entries: 2,
+        ..ParseLimits::default()
+    };
+
+    let error = AttributeListAttr::from_stream_with_limits(
+        &mut Cursor::new(&attribute_list),
+        None,
+        &limits,
+    )
+    .unwrap_err();
+    assert!(matches!(
+        error,
+        Error::LimitExceeded {
+            value: 3,
+            max: 2,
+            ..
+        }
+    ));
+    assert!(AttributeListAttr::from_stream(&mut Cursor::new(&attribute_list), None).is_ok());
+}
+
+#[test]
+fn test_it_enforces_the_index_entries_limit() {
+    // Three entries for MFT reference 1-1, with an empty file name.
+    let mut node = [0_u8; 3 * 0x52];
+    for entry in node.chunks_mut(0x52) {
+        entry[0] = 1;
+        entry[6] = 1;
+        entry[8] = 0x52;
+    }
+    let limits = ParseLimits {
+        max_index_entries: 2,
+        ..ParseLimits::default()
+    };
+
+    let error = IndexEntries::from_stream_with_limits(&mut Cursor::new(node), 3 * 0x52, 0, &limits)
+        .unwrap_err();
+    assert!(matches!(
+        error,
+        Error::LimitExceeded {
+            value: 3,
+            max: 2,
+            ..
+        }
+    ));
+    assert!(IndexEntries::from_stream(&mut Cursor::new(node), 3 * 0x52, 0).is_ok());
+}
+
+#[test]
+fn test_it_bounds_cyclic_paths() {
+    // Entries 1 and 2 are directories, which are each other's parent.
+    let mft = mft_with_directories(&[(1, 2), (2, 1)]);
+    let limits = ParseLimits {
+        max_path_depth: 8,
+        ..ParseLimits::default()
+    };
+    let mut parser = MftParser::from_buffer(mft).unwrap().with_limits(limits);
+
+    let entry = parser.get_entry(1).unwrap();
+    let path = parser.get_full_path_for_entry(&entry).unwrap().unwrap();
+
+    assert!(path.starts_with("[Unknown]"));
+    assert_eq!(path.components().count(), 10);
+}
+
+/// Directories with the given record numbers and parents, after `ENTRY`.
+fn mft_with_directories(directories: &[(u8, u8)]) -> Vec<u8> {
+    let mut mft = ENTRY.to_vec();
+    for &(record_number, parent) in directories {
+        let mut entry = with_bytes(22, &[0x03]);
+        entry[44] = record_number;
+        // Both $FILE_NAME attributes.
+        entry[176..182].copy_from_slice(&[parent, 0, 0, 0, 0, 0]);
+        entry[288..294].copy_from_slice(&[parent, 0, 0, 0, 0, 0]);
+        mft.extend(entry);
+    }
+    mft
+}
+
+#[test]
+fn test_it_bounds_cyclic_paths_with_the_default_limits() {
+    let mut parser = MftParser::from_buffer(mft_with_directories(&[(1, 2), (2, 1)])).unwrap();
+
+    for entry_number in [1, 2] {
+        let entry = parser.get_entry(entry_number).unwrap();
+        let path = parser.get_full_path_for_entry(&entry).unwrap().unwrap();
+
+        assert!(path.starts_with("[Unknown]"));
+        assert_eq!(
+            path.components().count(),
+            ParseLimits::default().max_path_depth + 2
+        );
+    }
+}
+
+#[test]
+fn test_it_does_not_cache_cut_off_paths() {
+    // 4 is in 3, which is in 2, which is in 1, at the root.
+    let mft = mft_with_directories(&[(1, 5), (2, 1), (3, 2), (4, 3)]);
+    let limits = ParseLimits {
+        max_path_depth: 2,
+        ..ParseLimits::default()
+    };
+    let mut parser = MftParser::from_buffer(mft).unwrap().with_limits(limits);
+
+    let entry = parser.get_entry(4).unwrap();
+    let path = parser.get_full_path_for_entry(&entry).unwrap().unwrap();
+    assert!(path.starts_with("[Unknown]"));
+
+    // 3 is within the limit, regardless of the walk from 4.
+    let entry = parser.get_entry(3).unwrap();
+    let path = parser.get_full_path_for_entry(&entry).unwrap().unwrap();
+    assert!(!path.starts_with("[Unknown]"), "{}", path.display());
+    assert_eq!(path.components().count(), 3);
+}
@@ -0,0 +1,184 @@
+#![cfg(feature = "images")]
+mod fixtures;
+mod image_fixtures;
+
+use fixtures::*;
+use image_fixtures::*;
+
+use mft::err::Error;
+use mft::partition::{list_partitions, PartitionScheme, PartitionType};
+use mft::MftParser;
+use std::fs;
+use std::io::Cursor;
+
+fn sample_mft() -> Vec<u8> {
+    mft_contents(&[fs::read(mft_sample_name("entry_single_file")).unwrap()])
+}
+
+#[test]
+fn test_lists_mbr_partitions_with_logical_partitions() {
+    let volume = ntfs_volume(&sample_mft());
+    let disk = mbr_disk(
+        &[(0x0B, vec![0x11; 64 * 512])],
+        &[(0x07, vec![0x22; 64 * 512]), (0x07, volume.clone())],
+    );
+
+    let partitions = list_partitions(&mut Cursor::new(&disk)).unwrap();
+
+    let summary: Vec<(usize, PartitionType, bool)> = partitions
+        .iter()
+        .map(|p| (p.index, p.partition_type.clone(), p.is_ntfs))
+        .collect();
+    assert_eq!(
+        summary,
+        vec![
+            (1, PartitionType::Mbr(0x0B), false),
+            (5, PartitionType::Mbr(0x07), false),
+            (6, PartitionType::Mbr(0x07), true),
+        ]
+    );
+
+    let ntfs = &partitions[2];
+    assert_eq!(ntfs.scheme, PartitionScheme::Mbr);
+    let offset = ntfs.offset as usize;
+    assert_eq!(&disk[offset..offset + volume.len()], volume.as_slice());
+
+    let mut parser = MftParser::from_partition(Cursor::new(&disk), ntfs).unwrap();
+    let mut expected = MftParser::from_buffer(sample_mft()).unwrap();
+    assert_eq!(entries_as_json(&mut parser), entries_as_json(&mut expected));
+}
+
+#[test]
+fn test_lists_gpt_partitions() {
+    let disk = gpt_disk(&[
+        ("Recovery", vec![0x33; 64 * 512]),
+        ("Windows", ntfs_volume(&sample_mft())),
+    ]);
+
+    let partitions = list_partitions(&mut Cursor::new(&disk)).unwrap();
+
+    assert_eq!(partitions.len(), 2);
+    assert!(partitions.iter().all(|p| p.scheme == PartitionScheme::Gpt));
+    assert_eq!(partitions[1].name.as_deref(), Some("Windows"));
+    assert_eq!(
+        partitions[1].partition_type.to_string(),
+        "EBD0A0A2-B9E5-4433-87C0-68B6B72699C7"
+    );
+    assert!(!partitions[0].is_ntfs);
+    assert!(partitions[1].is_ntfs);
+}
+
+#[test]
+fn test_volume_has_no_partitions() {
+    let volume = ntfs_volume(&sample_mft());
+
+    assert!(list_partitions(&mut Cursor::new(&volume))
+        .unwrap()
+        .is_empty());
+}
+
+#[test]
+fn test_it_does_not_mistake_an_mft_for_a_disk() {
+    let d = tempfile::tempdir().unwrap();
+    let path = d.path().join("MFT");
+
+    // An update sequence number of 0xAA55 ends the first sector of the record with an MBR signature,
+    // and the slack of the record holds what looks like a partition entry.
+    let mut mft = sample_mft();
+    mft[494 + 4] = 0x07;
+    mft[494 + 12..494 + 16].copy_from_slice(&2_u32.to_le_bytes());
+    let usa_offset = usize::from(u16::from_le_bytes([mft[4], mft[5]]));
+    for offset in [usa_offset, 510, 1022] {
+        mft[offset..offset + 2].copy_from_slice(&[0x55, 0xAA]);
+    }
+    fs::write(&path, &mft).unwrap();
+
+    let expected = entries_as_json(&mut MftParser::from_buffer(mft).unwrap());
+
+    let mut parser = MftParser::from_image_path(&path).unwrap();
+    assert_eq!(entries_as_json(&mut parser), expected);
+
+    assert!(matches!(
+        MftParser::from_image_path_partition(&path, Some(1)),
+        Err(Error::NoSuchNtfsPartition { index: 1 })
+    ));
+    assert!(MftParser::from_image_path_volumes(&path)
+        .unwrap()
+        .is_empty());
+}
+
+#[cfg(feature = "images")]
+#[test]
+fn test_selects_ntfs_partitions_from_disk_images() {
+    let d = tempfile::tempdir().unwrap();
+    let mft = sample_mft();
+    let path = d.path().join("disk.dd");
+
+    fs::write(
+        &path,
+        gpt_disk(&[("A", ntfs_volume(&mft)), ("B", ntfs_volume(&mft))]),
+    )
+    .unwrap();
+
+    match MftParser::from_image_path(&path) {
+        Err(Error::AmbiguousNtfsPartition { indices }) => assert_eq!(indices, vec![1, 2]),
+        other => panic!(
+            "Expected an ambiguous partition error, got {:?}",
+            other.err()
+        ),
+    }
+
+    let expected = entries_as_json(&mut MftParser::from_buffer(mft).unwrap());
+
+    let mut parser = MftParser::from_image_path_partition(&path, Some(2)).unwrap();
+    assert_eq!(entries_as_json(&mut parser), expected);
+
+    assert!(matches!(
+        MftParser::from_image_path_partition(&path, Some(3)),
+        Err(Error::NoSuchNtfsPartition { index: 3 })
+    ));
+
+    let volumes = MftParser::from_image_path_volumes(&path).unwrap();
+    assert_eq!(volumes.len(), 2);
+    for (partition, mut parser) in volumes {
+        assert!(partition.is_ntfs);
+        assert_eq!(entries_as_json(&mut parser), expected);
+    }
+}
+
+#[cfg(feature = "images")]
+#[test]
+fn test_it_lists_and_dumps_partitions() {
+    let d = tempfile::tempdir().unwrap();
+    let mft = sample_mft();
+    let disk = d.path().join("disk.dd");
+    fs::write(
+        &disk,
+        mbr_disk(&[(0x0B, vec![0; 64 * 512]), (0x07, ntfs_volume(&mft))], &[]),
+    )
+    .unwrap();
+    fs::write(d.path().join("MFT"), &mft).unwrap();
+
+    let mut cmd = assert_cmd::Command::cargo_bin("mft_dump").expect("failed to find binary");
+    cmd.args(["--list-partitions", disk.to_str().unwrap()]);
+    let listing = String::from_utf8(cmd.assert().success().get_output().stdout.clone()).unwrap();
+
+    let lines: Vec<&str> = listing.lines().collect();
+    assert_eq!(lines.len(), 3);
+    assert!(lines[1].starts_with("1 ") && lines[1].contains(" no "));
+    assert!(lines[2].starts_with("2 ") && lines[2].contains(" yes "));
+
+    let dump = |args: &[&str]| {
+        let mut cmd = assert_cmd::Command::cargo_bin("mft_dump").expect("failed to find binary");
+        cmd.args(["-o", "jsonl"]).args(args);
+        cmd.assert().success().get_output().stdout.clone()
+    };
+
+    let expected = dump(&[d.path().join("MFT").to_str().unwrap()]);
+    assert_eq!(
+        dump(&["--partition", "2", disk.to_str().unwrap()]),
+        expected
+    );
+    // A single NTFS partition is selected automatically.
+    assert_eq!(dump(&[disk.to_str().unwrap()]), expected);
+}
@@ -0,0 +1,253 @@
+mod fixtures;
+
+use fixtures::*;
+
+use mft::schema::{ENTRY_JSON_SCHEMA, SCHEMA_VERSION};
+use serde_json::{json, Value};
+
+use std::fs;
+use std::path::PathBuf;
+
+/// Checks a value against a JSON Schema, supporting the keywords used by `ENTRY_JSON_SCHEMA`.
+/// Returns the path and reason of every mismatch.
+fn validate(root: &Value, schema: &Value, value: &Value, path: &str, errors: &mut Vec<String>) {
+    if let Some(reference) = schema.get("$ref").and_then(Value::as_str) {
+        let name = reference
+            .strip_prefix("#/$defs/")
+            .unwrap_or_else(|| panic!("unsupported $ref `{}`", reference));
+        let definition = &root["$defs"][name];
+        assert!(!definition.is_null(), "missing definition `{}`", name);
+        validate(root, definition, value, path, errors);
+    }
+
+    if let Some(types) = schema.get("type") {
+        let types: Vec<&str> = match types {
+            Value::String(t) => vec![t.as_str()],
+            Value::Array(ts) => ts.iter().filter_map(Value::as_str).collect(),
+            _ => panic!("unsupported type `{}`", types),
+        };
+        let matches = |t: &&str| match *t {
+            "object" => value.is_object(),
+            "array" => value.is_array(),
+            "string" => value.is_string(),
+            "integer" => value.is_i64() || value.is_u64(),
+            "number" => value.is_number(),
+            "boolean" => value.is_boolean(),
+            "null" => value.is_null(),
+            t => panic!("unsupported type `{}`", t),
+        };
+        if !types.iter().any(matches) {
+            errors.push(format!("{}: expected {:?}, got {}", path, types, value));
+            return;
+        }
+    }
+
+    if let Some(expected) = schema.get("const") {
+        if value != expected {
+            errors.push(format!("{}: expected {}, got {}", path, expected, value));
+        }
+    }
+
+    if let Some(variants) = schema.get("enum").and_then(Value::as_array) {
+        if !variants.contains(value) {
+            errors.push(format!("{}: {} is not one of {:?}", path, value, variants));
+        }
+    }
+
+    if let Some(schemas) = schema.get("oneOf").and_then(Value::as_array) {
+        let matching = schemas
+            .iter()
+            .filter(|schema| {
+                let mut errors = vec![];
+                validate(root, schema, value, path, &mut errors);
+                errors.is_empty()
+            })
+            .count();
+        if matching != 1 {
+            errors.push(format!("{}: {} of oneOf matched {}", path, matching, value));
+        }
+    }
+
+    if let Value::Object(object) = value {
+        let properties = schema.get("properties").and_then(Value::as_object);
+
+        for name in schema
+            .get("required")
+            .and_then(Value::as_array)
+            .into_iter()
+            .flatten()
+            .filter_map(Value::as_str)
+        {
+            if !object.contains_key(name) {
+                errors.push(format!("{}: missing `{}`", path, name));
+            }
+        }
+
+        for (name, property) in object {
+            let property_path = format!("{}/{}", path, name);
+            match properties.and_then(|properties| properties.get(name)) {
+                Some(schema) => validate(root, schema, property, &property_path, errors),
+                None if schema.get("additionalProperties") == Some(&Value::Bool(false)) => {
+                    errors.push(format!("{}: unexpected property", property_path))
+                }
+                None => {}
+            }
+        }
+    }
+
+    if let Value::Array(items) = value {
+        if let Some(min) = schema.get("minItems").and_then(Value::as_u64) {
+            if (items.len() as u64) < min {
+                errors.push(format!("{}: fewer than {} items", path, min));
+            }
+        }
+        if let Some(max) = schema.get("maxItems").and_then(Value::as_u64) {
+            if (items.len() as u64) > max {
+                errors.push(format!("{}: more than {} items", path, max));
+            }
+        }
+        if let Some(schema) = schema.get("items") {
+            for (i, item) in items.iter().enumerate() {
+                validate(root, schema, item, &format!("{}/{}", path, i), errors);
+            }
+        }
+    }
+}
+
+fn schema() -> Value {
+    serde_json::from_str(ENTRY_JSON_SCHEMA).expect("the schema is valid JSON")
+}
+
+fn assert_matches_schema(entry: &Value) {
+    let schema = schema();
+    let mut errors = vec![];
+    validate(&schema, &schema, entry, "", &mut errors);
+    assert!(errors.is_empty(), "{:#?}", errors);
+}
+
+/// The entries written by `mft_dump -o jsonl` for the given input and arguments.
+fn dump(args: &[&str], input: Vec<u8>) -> Vec<Value> {
+    let mut cmd = assert_cmd::Command::cargo_bin("mft_dump").expect("failed to find binary");
+    cmd.args(args).args(["-o", "jsonl", "-"]).write_stdin(input);
+
+    let output = cmd.assert().success().get_output().stdout.clone();
+    String::from_utf8(output)
+        .unwrap()
+        .lines()
+        .map(|line| serde_json::from_str(line).unwrap())
+        .collect()
+}
+
+/// Every sample record, of both the samples and the test data.
+fn sample_entries() -> Vec<Vec<u8>> {
+    let testdata = samples_dir().parent().unwrap().join("testdata");
+    [samples_dir(), testdata]
+        .iter()
+        .flat_map(|dir| fs::read_dir(dir).unwrap())
+        .map(|entry| entry.unwrap().path())
+        .filter(|path: &PathBuf| fs::metadata(path).unwrap().len() == 1024)
+        .map(|path| fs::read(path).unwrap())
+        .collect()
+}
+
+#[test]
+fn test_schema_describes_the_current_version() {
+    let schema = schema();
+    assert_eq!(
+        schema["properties"]["schema_version"]["const"],
+        SCHEMA_VERSION
+    );
+    assert!(schema["$defs"].as_object().unwrap().len() > 10);
+}
+
+#[test]
+fn test_json_output_matches_the_schema() {
+    let entries = sample_entries();
+    assert!(entries.len() >= 4);
+
+    let options: &[&[&str]] = &[
+        &[],
+        &["--raw"],
+        &["--slack"],
+        &["--fixup-policy", "preserve"],
+        &["--timestamp-format", "epoch"],
+        &[
+            "--timestamp-format",
+            "epoch-fraction",
+            "--timezone",
+            "+02:00",
+        ],
+        &["--timestamp-format", "filetime"],
+    ];
+
+    for entry in &entries {
+        for args in options {
+            for output in dump(args, entry.clone()) {
+                assert_eq!(output["schema_version"], SCHEMA_VERSION);
+                assert_matches_schema(&output);
+            }
+        }
+    }
+}
+
+#[test]
+fn test_carved_and_damaged_entries_match_the_schema() {
+    let sample = include_bytes!("../samples/entry_single_file");
+
+    // An attribute with an unknown type, reported as an error in the attributes.
+    let mut entry = sample.to_vec();
+    let parsed = mft::MftEntry::from_buffer(entry.clone(), 0).unwrap();
+    let first_attribute = parsed.iter_attributes().next().unwrap().unwrap();
+    let second_attribute = (first_attribute.header.start_offset
+        + u64::from(first_attribute.header.record_length)) as usize;
+    entry[second_attribute..second_attribute + 4].copy_from_slice(&[0x42, 0, 0, 0]);
+
+    let output = dump(&[], entry);
+    assert!(output[0]["attributes"][1]["error"].is_string());
+    assert_matches_schema(&output[0]);
+
+    let dir = tempfile::tempdir().unwrap();
+    let image = dir.path().join("image");
+    let mut data = vec![0; 4096];
+    data[1024..2048].copy_from_slice(sample);
+    fs::write(&image, data).unwrap();
+
+    let mut cmd = assert_cmd::Command::cargo_bin("mft_dump").expect("failed to find binary");
+    cmd.args(["carve", "-o", "jsonl"]).arg(&image);
+    let output = cmd.assert().success().get_output().stdout.clone();
+    let carved: Value = serde_json::from_slice(&output).unwrap();
+    assert_eq!(carved["carved_offset"], 1024);
+    assert_matches_schema(&carved);
+}
+
+#[test]
+fn test_attributes_are_tagged_and_flags_are_named() {
+    let output = dump(&[], include_bytes!("../samples/entry_single_file").to_vec());
+    let entry = &output[0];
+
+    assert_eq!(
+        entry["header"]["flags"],
+        json!({"names": ["ALLOCATED"], "raw": 1})
+    );
+
+    let standard_info = &entry["attributes"][0]["data"];
+    assert_eq!(standard_info["type"], "StandardInformation");
+    assert_eq!(
+        standard_info["content"]["file_flags"],
+        json!({"names": ["FILE_ATTRIBUTE_ARCHIVE"], "raw": 32})
+    );
+
+    // The validator rejects the untagged content, and flags as strings.
+    let mut untagged = entry.clone();
+    untagged["attributes"][0]["data"] = standard_info["content"].clone();
+    let schema = schema();
+    let mut errors = vec![];
+    validate(&schema, &schema, &untagged, "", &mut errors);
+    assert!(!errors.is_empty());
+
+    let mut flags_as_string = entry.clone();
+    flags_as_string["header"]["flags"] = json!("ALLOCATED");
+    let mut errors = vec![];
+    validate(&schema, &schema, &flags_as_string, "", &mut errors);
+    assert!(!errors.is_empty());
+}
@@ -0,0 +1,186 @@
+mod fixtures;
+
+use fixtures::*;
+use mft::stream::{MftStreamParser, PathTable};
+use mft::{MftEntry, MftParser, ParseLimits};
+use std::io::Read;
+use std::path::PathBuf;
+use winstructs::ntfs::mft_reference::MftReference;
+
+fn concatenated_samples() -> Vec<u8> {
+    let mut buffer = vec![];
+    for name in [
+        "entry_single_file",
+        "entry_multiple_index_root_entries",
+        "entry_data_run_at_offset",
+    ] {
+        std::fs::File::open(mft_sample_name(name))
+            .unwrap()
+            .read_to_end(&mut buffer)
+            .unwrap();
+    }
+    buffer
+}
+
+#[test]
+fn test_stream_parser_matches_seekable_parser() {
+    let buffer = concatenated_samples();
+
+    let mut parser = MftParser::from_buffer(buffer.clone()).unwrap();
+    let expected: Vec<MftEntry> = parser.iter_entries().filter_map(Result::ok).collect();
+
+    // `&[u8]` is `Read`, but not `Seek`.
+    let mut stream_parser = MftStreamParser::from_read(&buffer[..], None).unwrap();
+    assert_eq!(stream_parser.entry_size(), 1024);
+
+    let entries: Vec<MftEntry> = stream_parser
+        .iter_entries()
+        .filter_map(Result::ok)
+        .collect();
+    assert_eq!(entries.len(), expected.len());
+
+    let mut paths = PathTable::new();
+    for entry in &entries {
+        paths.add_entry(entry);
+    }
+
+    for (entry, expected) in entries.iter().zip(expected.iter()) {
+        assert_eq!(entry.header.record_number, expected.header.record_number);
+        assert_eq!(entry.data, expected.data);
+        assert_eq!(
+            paths.get_full_path(entry.header.record_number),
+            parser.get_full_path_for_entry(expected).unwrap()
+        );
+    }
+}
+
+/// The sample of `test_cfuncs.py`, as record `record_number` in `parent`.
+fn record(record_number: u8, parent: u8, flags: u8) -> Vec<u8> {
+    let mut buffer = std::fs::read(mft_sample_name("entry_single_file")).unwrap();
+    buffer[0x16] = flags;
+    buffer[0x2C] = record_number;
+    // Both $FILE_NAME attributes.
+    buffer[0xB0..0xB6].copy_from_slice(&[parent, 0, 0, 0, 0, 0]);
+    buffer[0x120..0x126].copy_from_slice(&[parent, 0, 0, 0, 0, 0]);
+    buffer
+}
+
+/// A directory record without names, which extends `base`.
+fn extension_record(record_number: u8, base: u8) -> Vec<u8> {
+    let mut buffer = record(record_number, 0, 0x03);
+    buffer[0x20..0x26].copy_from_slice(&[base, 0, 0, 0, 0, 0]);
+    // The $FILE_NAME attributes become $LOGGED_UTILITY_STREAM attributes.
+    buffer[0x98..0x9C].copy_from_slice(&0x100_u32.to_le_bytes());
+    buffer[0x108..0x10C].copy_from_slice(&0x100_u32.to_le_bytes());
+    buffer
+}
+
+#[test]
+fn test_path_table_matches_seekable_parser_paths() {
+    let mft: Vec<u8> = [
+        record(0, 5, 0x01),
+        record(1, 5, 0x03),
+        extension_record(2, 1),
+        // In the extension record of 1.
+        record(3, 2, 0x01),
+        record(4, 1, 0x03),
+        record(5, 5, 0x03),
+        record(6, 7, 0x03),
+        record(7, 6, 0x03),
+        // In a cycle, which is cut off at the limit.
+        record(8, 7, 0x01),
+    ]
+    .concat();
+    let limits = ParseLimits {
+        max_path_depth: 3,
+        ..ParseLimits::default()
+    };
+
+    let mut parser = MftParser::from_buffer(mft).unwrap().with_limits(limits);
+    let entries: Vec<MftEntry> = parser.iter_entries().map(Result::unwrap).collect();
+
+    let mut paths = PathTable::new().with_limits(limits);
+    for entry in &entries {
+        paths.add_entry(entry);
+    }
+
+    for entry in &entries {
+        assert_eq!(
+            paths.get_full_path(entry.header.record_number),
+            parser.get_full_path_for_entry(entry).unwrap(),
+            "entry {}",
+            entry.header.record_number
+        );
+    }
+
+    let name = PathBuf::from("test_cfuncs.py");
+    assert_eq!(paths.get_full_path(3), Some(name.join("test_cfuncs.py")));
+    assert!(paths.get_full_path(8).unwrap().starts_with("[Unknown]"));
+}
+
+#[test]
+fn test_stream_parser_reports_truncated_record() {
+    let buffer = concatenated_samples();
+    let truncated = &buffer[..1024 + 100];
+
+    let mut stream_parser = MftStreamParser::from_read(truncated, Some(1024)).unwrap();
+
+    assert!(stream_parser.next_entry().unwrap().is_ok());
+    assert!(stream_parser.next_entry().unwrap().is_err());
+    assert!(stream_parser.next_entry().is_none());
+}
+
+/// The sample at `name`, as record `record_number` with the given sequence number.
+fn sample_entry(name: &str, record_number: u64, sequence: u16, allocated: bool) -> MftEntry {
+    let mut buffer = std::fs::read(mft_sample_name(name)).unwrap();
+    buffer[0x10..0x12].copy_from_slice(&sequence.to_le_bytes());
+    if !allocated {
+        buffer[0x16] &= !0x01;
+    }
+    MftEntry::from_buffer(buffer, record_number).unwrap()
+}
+
+#[test]
+fn test_path_table_matches_sequence_numbers() {
+    // `test_cfuncs.py` is in directory 26359, which is `test`, with sequence number 1.
+    let file = sample_entry("entry_single_file", 100, 1, true);
+    let directory = |sequence, allocated| {
+        sample_entry(
+            "entry_multiple_index_root_entries",
+            26359,
+            sequence,
+            allocated,
+        )
+    };
+
+    let mut paths = PathTable::new().with_sequence_matching(true);
+    paths.add_entry(&file);
+    paths.add_entry(&directory(1, true));
+    let expected = PathBuf::from("[Unknown]/test/test_cfuncs.py");
+    assert_eq!(paths.get_full_path_for_entry(&file), Some(expected.clone()));
+
+    // The directory was deleted, which increments its sequence number.
+    paths.add_entry(&directory(2, false));
+    assert_eq!(paths.get_full_path_for_entry(&file), Some(expected.clone()));
+
+    // The record was reused, so the file can't be placed.
+    paths.add_entry(&directory(3, true));
+    assert_eq!(
+        paths.get_full_path_for_entry(&file),
+        Some(PathBuf::from("[Unknown]/test_cfuncs.py"))
+    );
+    // And another use of the record of the file is not the file.
+    assert_eq!(
+        paths.get_full_path_for_reference(MftReference::new(100, 2)),
+        None
+    );
+
+    // Without matching, the last entry of every record is used.
+    let mut paths = PathTable::new();
+    paths.add_entry(&file);
+    paths.add_entry(&directory(3, true));
+    assert_eq!(paths.get_full_path_for_entry(&file), Some(expected));
+    assert!(paths
+        .get_full_path_for_reference(MftReference::new(100, 2))
+        .is_some());
+}
@@ -0,0 +1,107 @@
+use mft::timeline::{TimelineEvent, TimelineSorter, TimelineSource, TimestampKind};
+use mft::MftEntry;
+
+use std::path::PathBuf;
+
+fn entry(sample: &[u8]) -> MftEntry {
+    MftEntry::from_buffer(sample.to_vec(), 0).unwrap()
+}
+
+#[test]
+fn test_it_explodes_an_entry_into_events() {
+    let entry = entry(include_bytes!("../samples/entry_single_file"));
+
+    let events: Vec<TimelineEvent> =
+        TimelineEvent::iter_from_entry(&entry, PathBuf::from("test_cfuncs.py")).collect();
+
+    // $STANDARD_INFORMATION, and two $FILE_NAME attributes.
+    assert_eq!(events.len(), 12);
+    assert_eq!(
+        events
+            .iter()
+            .filter(|e| e.source == TimelineSource::StandardInformation)
+            .count(),
+        4
+    );
+
+    let names: Vec<Option<&str>> = events
+        .iter()
+        .step_by(4)
+        .map(|e| e.name.as_deref())
+        .collect();
+    assert_eq!(
+        names,
+        vec![None, Some("TEST_C~3.PY"), Some("test_cfuncs.py")]
+    );
+
+    let kinds: Vec<char> = events.iter().take(4).map(|e| e.kind.macb()).collect();
+    assert_eq!(kinds, vec!['M', 'A', 'C', 'B']);
+    assert!(events
+        .iter()
+        .all(|e| e.full_path.as_os_str() == "test_cfuncs.py"));
+}
+
+#[test]
+fn test_it_includes_index_entries() {
+    let entry = entry(include_bytes!(
+        "../samples/entry_multiple_index_root_entries"
+    ));
+
+    let index_entries: Vec<TimelineEvent> = TimelineEvent::iter_from_entry(&entry, PathBuf::new())
+        .filter(|e| e.source == TimelineSource::IndexEntry)
+        .collect();
+
+    assert!(!index_entries.is_empty());
+    assert_eq!(index_entries.len() % 4, 0);
+    assert!(index_entries
+        .iter()
+        .all(|e| e.name.is_some() && e.referenced_entry_id.is_some()));
+
+    let test_cfuncs = index_entries
+        .iter()
+        .find(|e| e.name.as_deref() == Some("test_cfuncs.py"))
+        .unwrap();
+    assert_eq!(test_cfuncs.referenced_entry_id, Some(26370));
+    assert_eq!(test_cfuncs.referenced_sequence, Some(1));
+}
+
+#[test]
+fn test_sorter_merges_spilled_runs() {
+    let mut events = vec![];
+    for sample in [
+        &include_bytes!("../samples/entry_single_file")[..],
+        &include_bytes!("../samples/entry_multiple_index_root_entries")[..],
+        &include_bytes!("../samples/entry_data_run_at_offset")[..],
+    ] {
+        let entry = entry(sample);
+        events.extend(TimelineEvent::iter_from_entry(&entry, PathBuf::from("a/b")));
+    }
+
+    let mut expected = events.clone();
+    expected.sort();
+
+    let mut sorter = TimelineSorter::new().with_max_in_memory(5);
+    for event in events {
+        sorter.push(event).unwrap();
+    }
+    assert!(sorter.spilled_runs() > 1);
+
+    let sorted: Vec<TimelineEvent> = sorter.into_sorted().unwrap().map(Result::unwrap).collect();
+    assert_eq!(sorted, expected);
+    assert!(sorted.windows(2).all(|w| w[0].timestamp <= w[1].timestamp));
+}
+
+#[test]
+fn test_timestamp_kinds_are_macb() {
+    let macb: String = [
+        TimestampKind::Modified,
+        TimestampKind::Accessed,
+        TimestampKind::MftModified,
+        TimestampKind::Created,
+    ]
+    .iter()
+    .map(|k| k.macb())
+    .collect();
+
+    assert_eq!(macb, "MACB");
+}
@@ -0,0 +1,206 @@
+use mft::csv::FlatMftEntryWithName;
+use mft::err::Error;
+use mft::timestamp::{FileTimeValue, TimestampFormat, TimestampOptions};
+use mft::timezone::OutputTimeZone;
+use mft::{FileTime, MftEntry};
+
+use chrono::{FixedOffset, TimeZone, Utc};
+use std::path::PathBuf;
+
+#[test]
+fn test_it_keeps_100ns_precision() {
+    // 2013-10-19 12:16:53.2760403 UTC
+    let time = FileTime::from_raw(0x01CE_CCC5_188B_C753);
+
+    assert_eq!(time.raw(), 0x01CE_CCC5_188B_C753);
+    assert_eq!(time.subsecond_intervals(), 2_760_403);
+    assert_eq!(time.to_string(), "2013-10-19T12:16:53.2760403Z");
+    assert_eq!(
+        serde_json::to_value(time).unwrap(),
+        "2013-10-19T12:16:53.2760403Z"
+    );
+
+    let expected = Utc
+        .with_ymd_and_hms(2013, 10, 19, 12, 16, 53)
+        .unwrap()
+        .checked_add_signed(chrono::Duration::nanoseconds(276_040_300))
+        .unwrap();
+    assert_eq!(time.to_datetime(), Some(expected));
+    assert_eq!(FileTime::from(expected), time);
+}
+
+#[test]
+fn test_it_represents_unset_times() {
+    let time = FileTime::from_raw(0);
+
+    assert_eq!(time.value(), FileTimeValue::NotSet);
+    assert!(!time.is_set());
+    assert_eq!(time.to_datetime(), None);
+    assert_eq!(serde_json::to_value(time).unwrap(), serde_json::Value::Null);
+}
+
+#[test]
+fn test_it_represents_out_of_range_times() {
+    for raw in [u64::MAX, 0x8000_0000_0000_0000, 0x7FFF_FFFF_FFFF_FFFF] {
+        let time = FileTime::from_raw(raw);
+
+        assert_eq!(time.value(), FileTimeValue::OutOfRange);
+        assert!(time.is_set());
+        assert_eq!(time.to_datetime(), None);
+        // The raw value is all there is to show, apart from any time.
+        assert_eq!(
+            serde_json::to_value(time).unwrap(),
+            serde_json::json!({ "out_of_range": raw })
+        );
+        assert_eq!(
+            serde_json::from_value::<FileTime>(serde_json::to_value(time).unwrap()).unwrap(),
+            time
+        );
+    }
+
+    // The first 100ns interval of the year 10000.
+    let last = Utc.with_ymd_and_hms(9999, 12, 31, 23, 59, 59).unwrap();
+    let last = FileTime::from(last).raw() + 9_999_999;
+    assert!(matches!(
+        FileTime::from_raw(last).value(),
+        FileTimeValue::Time(_)
+    ));
+    assert_eq!(
+        FileTime::from_raw(last + 1).value(),
+        FileTimeValue::OutOfRange
+    );
+}
+
+#[test]
+fn test_times_are_ordered_chronologically() {
+    let earlier = FileTime::from(Utc.with_ymd_and_hms(2001, 1, 1, 0, 0, 0).unwrap());
+    let later = FileTime::from(Utc.with_ymd_and_hms(2020, 1, 1, 0, 0, 0).unwrap());
+
+    assert!(earlier < later);
+    assert!(FileTime::from_raw(0) < earlier);
+}
+
+#[test]
+fn test_it_serializes_times_in_the_chosen_format() {
+    // 2013-10-19 12:16:53.2760403 UTC
+    let time = FileTime::from_raw(0x01CE_CCC5_188B_C753);
+    let serialize =
+        |options: TimestampOptions| options.scope(|| serde_json::to_value(time).unwrap());
+
+    assert_eq!(
+        serialize(TimestampOptions::default().with_format(TimestampFormat::EpochSeconds)),
+        1_382_185_013
+    );
+    assert_eq!(
+        serialize(TimestampOptions::default().with_format(TimestampFormat::EpochFraction)),
+        "1382185013.2760403"
+    );
+    assert_eq!(
+        serialize(TimestampOptions::default().with_format(TimestampFormat::Filetime)),
+        0x01CE_CCC5_188B_C753_u64
+    );
+
+    let offset = OutputTimeZone::parse("-05:30").unwrap();
+    assert_eq!(
+        serialize(TimestampOptions::default().with_time_zone(offset)),
+        "2013-10-19T06:46:53.2760403-05:30"
+    );
+
+    // Unset times are null whatever the format, and the options only last for the scope.
+    let options = TimestampOptions::default().with_format(TimestampFormat::EpochSeconds);
+    assert_eq!(
+        options.scope(|| serde_json::to_value(FileTime::from_raw(0)).unwrap()),
+        serde_json::Value::Null
+    );
+    assert_eq!(
+        serde_json::to_value(time).unwrap(),
+        "2013-10-19T12:16:53.2760403Z"
+    );
+
+    // Before the Unix epoch.
+    let time = FileTime::from(Utc.with_ymd_and_hms(1969, 12, 31, 23, 59, 59).unwrap());
+    let time = FileTime::from_raw(time.raw() + 1);
+    assert_eq!(
+        TimestampOptions::default()
+            .with_format(TimestampFormat::EpochFraction)
+            .scope(|| serde_json::to_value(time).unwrap()),
+        "-0.9999999"
+    );
+    assert_eq!(
+        TimestampOptions::default()
+            .with_format(TimestampFormat::EpochSeconds)
+            .scope(|| serde_json::to_value(time).unwrap()),
+        -1
+    );
+}
+
+#[test]
+fn test_it_parses_time_zones() {
+    assert_eq!(OutputTimeZone::parse("UTC").unwrap(), OutputTimeZone::Utc);
+    for (s, seconds) in [("+02:00", 7200), ("-0530", -19800), ("+3", 10800)] {
+        assert_eq!(
+            OutputTimeZone::parse(s).unwrap(),
+            OutputTimeZone::Fixed(FixedOffset::east_opt(seconds).unwrap())
+        );
+    }
+
+    for s in [
+        "+25:00",
+        "-05:75",
+        "+1é1",
+        "++3",
+        "../../etc/passwd",
+        "/etc/localtime",
+    ] {
+        assert!(matches!(
+            OutputTimeZone::parse(s),
+            Err(Error::InvalidTimeZone { .. })
+        ));
+    }
+}
+
+#[test]
+fn test_it_uses_the_rules_of_named_time_zones() {
+    let offset_at = |zone: &str, y, m, d, h, min, s| {
+        let time = Utc.with_ymd_and_hms(y, m, d, h, min, s).unwrap();
+        OutputTimeZone::parse(zone)
+            .unwrap()
+            .offset_at(&time)
+            .local_minus_utc()
+    };
+
+    assert_eq!(offset_at("Europe/Berlin", 2021, 1, 15, 0, 0, 0), 3600);
+    assert_eq!(offset_at("Europe/Berlin", 2021, 3, 28, 0, 59, 59), 3600);
+    assert_eq!(offset_at("Europe/Berlin", 2021, 3, 28, 1, 0, 0), 7200);
+    assert_eq!(offset_at("Europe/Berlin", 2021, 10, 31, 0, 59, 59), 7200);
+    assert_eq!(offset_at("Europe/Berlin", 2021, 10, 31, 1, 0, 0), 3600);
+
+    // Southern hemisphere, with daylight saving time over the new year.
+    assert_eq!(offset_at("Australia/Sydney", 2021, 1, 15, 0, 0, 0), 39600);
+    assert_eq!(offset_at("Australia/Sydney", 2021, 7, 1, 0, 0, 0), 36000);
+
+    assert_eq!(offset_at("Asia/Kolkata", 2021, 1, 1, 0, 0, 0), 19800);
+
+    assert!(matches!(
+        OutputTimeZone::parse("Nowhere/Nothing"),
+        Err(Error::InvalidTimeZone { .. })
+    ));
+}
+
+#[test]
+fn test_out_of_range_times_are_strings_in_csv() {
+    let entry =
+        MftEntry::from_buffer(include_bytes!("../samples/entry_single_file").to_vec(), 0).unwrap();
+    let mut row = FlatMftEntryWithName::from_entry_and_path(&entry, PathBuf::new());
+    row.standard_info_created = Some(FileTime::from_raw(u64::MAX));
+
+    let mut writer = csv::Writer::from_writer(vec![]);
+    writer.serialize(&row).unwrap();
+    let csv = String::from_utf8(writer.into_inner().unwrap()).unwrap();
+
+    assert!(
+        csv.contains(",Out of range (0xffffffffffffffff),"),
+        "{}",
+        csv
+    );
+}